@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use stylua_lib::{format_code, Config, OutputVerification};
+
+// Compares formatting with and without reparsing the output, which is the cost of passing `--verify` to the CLI
+pub fn format_date_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("format date.lua with verification");
+
+    group.bench_function("None", |b| {
+        b.iter(|| {
+            format_code(
+                black_box(include_str!("./date.lua")),
+                black_box(Config::default()),
+                black_box(None),
+                black_box(OutputVerification::None),
+            )
+        })
+    });
+
+    group.bench_function("Full", |b| {
+        b.iter(|| {
+            format_code(
+                black_box(include_str!("./date.lua")),
+                black_box(Config::default()),
+                black_box(None),
+                black_box(OutputVerification::Full),
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(40);
+    targets = format_date_verification
+}
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+local x = --[[ inline ]] 1 -- trailing
+--[==[
+	multiline ]] comment
+]==]
+local function foo(--[[a]] a, b --[[b]])
+	return a -- comment
+		+ b
+end
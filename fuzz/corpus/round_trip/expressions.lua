@@ -0,0 +1,5 @@
+local x = -(-1) - - 2 ^ - 3 .. "a" .. 1 .. 2
+local y = not not (a and b or c) == (d ~= e)
+local z = (f)(g)[h].i:j(k)
+local w = #t + #(t) * ((1 + 2) / 3) % 4
+local long = aaaaaaaaaaaaaaaaaaaa + bbbbbbbbbbbbbbbbbbbbbb * cccccccccccccccccccccccc - dddddddddddddddddddddd / eeeeeeeeeeeeeeeeeeeeeeeeeeeee
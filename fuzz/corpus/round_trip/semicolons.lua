@@ -0,0 +1,5 @@
+local a = b
+(c)(d)
+local e = f;
+(g)()
+return;
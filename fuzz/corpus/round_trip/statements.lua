@@ -0,0 +1,7 @@
+;;; local a; local b = 1;
+do end while true do break end repeat local x until x
+for i = 1, 10, -1 do end for k, v in pairs(t) do end
+if a then elseif b then else end
+function a.b.c:d(...) return ... end
+local function e() return end
+local t = { 1, 2; 3, [4] = 5, six = 6, }
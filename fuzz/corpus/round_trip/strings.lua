@@ -0,0 +1,8 @@
+local a = "double 'quoted'"
+local b = 'single "quoted"'
+local c = [[long
+string]]
+local d = [==[with ]] inside]==]
+local e = "escapes \"\n\t\\ \z
+	continued"
+print"call" print[[call]] print{ table }
@@ -11,7 +11,7 @@ use stylua_lib::SortRequiresConfig;
 #[cfg(feature = "editorconfig")]
 use stylua_lib::editorconfig;
 
-static CONFIG_FILE_NAME: [&str; 2] = ["stylua.toml", ".stylua.toml"];
+pub(crate) static CONFIG_FILE_NAME: [&str; 2] = ["stylua.toml", ".stylua.toml"];
 
 fn read_config_file(path: &Path) -> Result<Config> {
     let contents = fs::read_to_string(path).context("Failed to read config file")?;
@@ -20,15 +20,58 @@ fn read_config_file(path: &Path) -> Result<Config> {
     Ok(config)
 }
 
+/// Reads the options set by the configuration file, without filling in defaults for the options it does not set
+#[cfg(feature = "serialize")]
+fn read_config_file_options(path: &Path) -> Result<toml::Table> {
+    let contents = fs::read_to_string(path).context("Failed to read config file")?;
+    let options = toml::from_str(&contents).context("Config file not in correct format")?;
+
+    Ok(options)
+}
+
+/// Sets the options on top of the configuration. Options which are tables, such as `sort_requires`, are merged
+/// with the table in the configuration
+#[cfg(feature = "serialize")]
+fn layer_options(config: Config, options: &toml::Table) -> Result<Config> {
+    fn merge(value: &mut serde_json::Value, options: serde_json::Value) {
+        match (value, options) {
+            (serde_json::Value::Object(value), serde_json::Value::Object(options)) => {
+                for (key, option) in options {
+                    merge(value.entry(key).or_insert(serde_json::Value::Null), option);
+                }
+            }
+            (value, option) => *value = option,
+        }
+    }
+
+    let mut value = serde_json::to_value(config)?;
+    merge(&mut value, serde_json::to_value(options)?);
+    Ok(serde_json::from_value(value)?)
+}
+
 fn read_and_apply_overrides(path: &Path, opt: &Opt) -> Result<Config> {
     read_config_file(path).map(|config| load_overrides(config, opt))
 }
 
 pub struct ConfigResolver<'a> {
-    config_cache: HashMap<PathBuf, Option<Config>>,
-    forced_configuration: Option<Config>,
+    /// The configuration resolved for each directory searched, shared by every file within the directory.
+    /// Entries are removed through [`ConfigResolver::invalidate_directory`] when a configuration file changes
+    config_cache: HashMap<PathBuf, Option<(Config, PathBuf)>>,
+    /// The configuration used for every file, and the configuration file it was read from
+    forced_configuration: Option<(Config, PathBuf)>,
     current_directory: PathBuf,
     default_configuration: Config,
+    /// Configurations used in place of the default configuration for documents of a particular language ID
+    language_default_configurations: HashMap<String, Config>,
+    /// The directories searched, in order, for a user-level configuration file when no configuration file is found
+    /// for a path
+    global_config_directories: Vec<PathBuf>,
+    /// The options set by each configuration file which has been layered over a configuration, keyed by file path
+    #[cfg(feature = "serialize")]
+    config_file_options: HashMap<PathBuf, toml::Table>,
+    /// Whether the global configuration directories are searched, which is the case with `--search-parent-directories`
+    /// or once enabled through [`ConfigResolver::enable_global_configuration`]
+    search_global_configuration: bool,
     opt: &'a Opt,
 }
 
@@ -43,6 +86,7 @@ impl ConfigResolver<'_> {
                     config_path.display()
                 );
                 read_and_apply_overrides(config_path, opt)
+                    .map(|config| (config, config_path.to_path_buf()))
             })
             .transpose()?;
 
@@ -51,10 +95,57 @@ impl ConfigResolver<'_> {
             forced_configuration,
             current_directory: env::current_dir().context("Could not find current directory")?,
             default_configuration: load_overrides(Config::default(), opt),
+            language_default_configurations: HashMap::new(),
+            global_config_directories: global_config_directories(),
+            #[cfg(feature = "serialize")]
+            config_file_options: HashMap::new(),
+            search_global_configuration: opt.search_parent_directories,
             opt,
         })
     }
 
+    /// Replaces the configuration used when no configuration file is found.
+    /// Any command line overrides are applied on top of the provided configuration.
+    pub fn set_default_configuration(&mut self, config: Config) {
+        self.default_configuration = load_overrides(config, self.opt);
+    }
+
+    /// Replaces the configuration used when no configuration file is found, for documents with the given language ID.
+    /// Any command line overrides are applied on top of the provided configuration.
+    pub fn set_language_default_configuration(&mut self, language_id: String, config: Config) {
+        self.language_default_configurations
+            .insert(language_id, load_overrides(config, self.opt));
+    }
+
+    /// Uses the configuration file at the path for every file, in place of any configuration files which would be
+    /// found for them, as if it had been provided through `--config-path`
+    pub fn set_forced_configuration_path(&mut self, config_path: &Path) -> Result<()> {
+        debug!(
+            "config: explicit config path provided at {}",
+            config_path.display()
+        );
+        let config = read_and_apply_overrides(config_path, self.opt)?;
+        self.forced_configuration = Some((config, config_path.to_path_buf()));
+        Ok(())
+    }
+
+    /// Falls back to a user-level configuration file, such as `$XDG_CONFIG_HOME/stylua/stylua.toml`, when no
+    /// configuration file is found for a path, even without `--search-parent-directories`
+    pub fn enable_global_configuration(&mut self) {
+        self.search_global_configuration = true;
+        self.config_cache.clear();
+    }
+
+    /// Removes any cached configuration for the directory and the directories beneath it, so that it is resolved again.
+    /// Used when a configuration file in the directory has been created, changed or deleted.
+    pub fn invalidate_directory(&mut self, directory: &Path) {
+        self.config_cache
+            .retain(|cached_directory, _| !cached_directory.starts_with(directory));
+        #[cfg(feature = "serialize")]
+        self.config_file_options
+            .retain(|file_path, _| !file_path.starts_with(directory));
+    }
+
     /// Returns the root used when searching for configuration
     /// If `--search-parent-directories`, then there is no root, and we keep searching
     /// Else, the root is the current working directory, and we do not search higher than the cwd
@@ -70,44 +161,122 @@ impl ConfigResolver<'_> {
         }
     }
 
+    /// Returns the configuration used when no configuration file is found, for documents with the given language ID
+    pub(crate) fn default_configuration(&self, language_id: Option<&str>) -> Config {
+        language_id
+            .and_then(|language_id| self.language_default_configurations.get(language_id))
+            .copied()
+            .unwrap_or(self.default_configuration)
+    }
+
     pub(crate) fn load_configuration_with_search_root(
         &mut self,
         path: &Path,
         search_root_override: Option<PathBuf>,
+        language_id: Option<&str>,
+    ) -> Result<Config> {
+        let default_configuration = self.default_configuration(language_id);
+        self.load_configuration_with_default(path, search_root_override, default_configuration)
+    }
+
+    /// Loads the configuration for the path, using the provided configuration when no configuration file is found,
+    /// and for any options which the configuration file does not set.
+    /// Any `.editorconfig` properties for the path are applied on top of the provided configuration
+    pub(crate) fn load_configuration_with_default(
+        &mut self,
+        path: &Path,
+        search_root_override: Option<PathBuf>,
+        default_configuration: Config,
     ) -> Result<Config> {
-        if let Some(configuration) = self.forced_configuration {
+        if let Some((configuration, _)) = self.forced_configuration {
             return Ok(configuration);
         }
 
-        let root = self.get_configuration_search_root(search_root_override);
-
-        let absolute_path = self.current_directory.join(path);
-        let parent_path = &absolute_path
-            .parent()
-            .with_context(|| format!("no parent directory found for {}", path.display()))?;
-
-        match self.find_config_file(parent_path, root)? {
-            Some(config) => Ok(config),
+        match self.find_config_file_for_path(path, search_root_override)? {
+            Some((config, file_path)) => {
+                Ok(self.layer_config_file(config, &file_path, default_configuration))
+            }
             None => {
                 #[cfg(feature = "editorconfig")]
                 if self.opt.no_editorconfig {
-                    Ok(self.default_configuration)
+                    Ok(default_configuration)
                 } else {
-                    editorconfig::parse(self.default_configuration, path)
+                    editorconfig::parse(default_configuration, path)
                         .context("could not parse editorconfig")
                 }
                 #[cfg(not(feature = "editorconfig"))]
-                Ok(self.default_configuration)
+                Ok(default_configuration)
             }
         }
     }
 
+    /// Applies the options set by the configuration file on top of the provided configuration, in place of the
+    /// built-in defaults. The configuration read from the file is used as it is if the file can no longer be read
+    #[cfg(feature = "serialize")]
+    fn layer_config_file(
+        &mut self,
+        config: Config,
+        file_path: &Path,
+        default_configuration: Config,
+    ) -> Config {
+        if !self.config_file_options.contains_key(file_path) {
+            match read_config_file_options(file_path) {
+                Ok(options) => {
+                    self.config_file_options
+                        .insert(file_path.to_path_buf(), options);
+                }
+                Err(_) => return config,
+            }
+        }
+
+        layer_options(default_configuration, &self.config_file_options[file_path])
+            .map(|layered_config| load_overrides(layered_config, self.opt))
+            .unwrap_or(config)
+    }
+
+    #[cfg(not(feature = "serialize"))]
+    fn layer_config_file(&mut self, config: Config, _: &Path, _: Config) -> Config {
+        config
+    }
+
+    /// Returns the path of the configuration file used for the path, or `None` if no configuration file is found
+    /// and the configuration is instead resolved from the defaults and any `.editorconfig` properties
+    pub(crate) fn find_configuration_file(
+        &mut self,
+        path: &Path,
+        search_root_override: Option<PathBuf>,
+    ) -> Result<Option<PathBuf>> {
+        if let Some((_, config_path)) = &self.forced_configuration {
+            return Ok(Some(config_path.clone()));
+        }
+
+        Ok(self
+            .find_config_file_for_path(path, search_root_override)?
+            .map(|(_, file_path)| file_path))
+    }
+
+    /// Looks for a configuration file in the directories containing the path
+    fn find_config_file_for_path(
+        &mut self,
+        path: &Path,
+        search_root_override: Option<PathBuf>,
+    ) -> Result<Option<(Config, PathBuf)>> {
+        let root = self.get_configuration_search_root(search_root_override);
+
+        let absolute_path = self.current_directory.join(path);
+        let parent_path = &absolute_path
+            .parent()
+            .with_context(|| format!("no parent directory found for {}", path.display()))?;
+
+        self.find_config_file(parent_path, root)
+    }
+
     pub fn load_configuration(&mut self, path: &Path) -> Result<Config> {
-        self.load_configuration_with_search_root(path, None)
+        self.load_configuration_with_search_root(path, None, None)
     }
 
     pub fn load_configuration_for_stdin(&mut self) -> Result<Config> {
-        if let Some(configuration) = self.forced_configuration {
+        if let Some((configuration, _)) = self.forced_configuration {
             return Ok(configuration);
         }
 
@@ -117,7 +286,7 @@ impl ConfigResolver<'_> {
         match &self.opt.stdin_filepath {
             Some(filepath) => self.load_configuration(filepath),
             None => match self.find_config_file(&my_current_directory, root)? {
-                Some(config) => Ok(config),
+                Some((config, _)) => Ok(config),
                 None => {
                     #[cfg(feature = "editorconfig")]
                     if self.opt.no_editorconfig {
@@ -133,7 +302,10 @@ impl ConfigResolver<'_> {
         }
     }
 
-    fn lookup_config_file_in_directory(&self, directory: &Path) -> Result<Option<Config>> {
+    fn lookup_config_file_in_directory(
+        &self,
+        directory: &Path,
+    ) -> Result<Option<(Config, PathBuf)>> {
         debug!("config: looking for config in {}", directory.display());
         let config_file = find_toml_file(directory);
         match config_file {
@@ -141,22 +313,22 @@ impl ConfigResolver<'_> {
                 debug!("config: found config at {}", file_path.display());
                 let config = read_and_apply_overrides(&file_path, self.opt)?;
                 debug!("config: {:#?}", config);
-                Ok(Some(config))
+                Ok(Some((config, file_path)))
             }
             None => Ok(None),
         }
     }
 
-    /// Looks for a configuration file in the directory provided
+    /// Looks for a configuration file in the directory provided, returning its configuration and path
     /// Keep searching recursively upwards until we hit the root (if provided), then stop
     /// When `--search-parent-directories` is enabled, root = None, else root = Some(cwd)
     fn find_config_file(
         &mut self,
         directory: &Path,
         root: Option<PathBuf>,
-    ) -> Result<Option<Config>> {
+    ) -> Result<Option<(Config, PathBuf)>> {
         if let Some(config) = self.config_cache.get(directory) {
-            return Ok(*config);
+            return Ok(config.clone());
         }
 
         let resolved_configuration = match self.lookup_config_file_in_directory(directory)? {
@@ -167,7 +339,7 @@ impl ConfigResolver<'_> {
 
                 if should_stop {
                     debug!("config: no configuration file found");
-                    if self.opt.search_parent_directories {
+                    if self.search_global_configuration {
                         if let Some(config) = self.search_config_locations()? {
                             return Ok(Some(config));
                         }
@@ -182,55 +354,40 @@ impl ConfigResolver<'_> {
         };
 
         self.config_cache
-            .insert(directory.to_path_buf(), resolved_configuration);
+            .insert(directory.to_path_buf(), resolved_configuration.clone());
         Ok(resolved_configuration)
     }
 
-    /// Looks for a configuration file at either `$XDG_CONFIG_HOME`, `$XDG_CONFIG_HOME/stylua`, `$HOME/.config` or `$HOME/.config/stylua`
-    fn search_config_locations(&self) -> Result<Option<Config>> {
-        // Look in `$XDG_CONFIG_HOME`
-        if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
-            let xdg_config_path = Path::new(&xdg_config);
-            if xdg_config_path.exists() {
-                debug!("config: looking in $XDG_CONFIG_HOME");
-
-                if let Some(config) = self.lookup_config_file_in_directory(xdg_config_path)? {
-                    return Ok(Some(config));
-                }
-
-                debug!("config: looking in $XDG_CONFIG_HOME/stylua");
-                let xdg_config_path = xdg_config_path.join("stylua");
-                if xdg_config_path.exists() {
-                    if let Some(config) = self.lookup_config_file_in_directory(&xdg_config_path)? {
-                        return Ok(Some(config));
-                    }
-                }
+    /// Looks for a configuration file in the global configuration directories, returning the first one found
+    fn search_config_locations(&self) -> Result<Option<(Config, PathBuf)>> {
+        for directory in &self.global_config_directories {
+            if let Some(config) = self.lookup_config_file_in_directory(directory)? {
+                return Ok(Some(config));
             }
         }
 
-        // Look in `$HOME/.config`
-        if let Ok(home) = std::env::var("HOME") {
-            let home_config_path = Path::new(&home).join(".config");
-
-            if home_config_path.exists() {
-                debug!("config: looking in $HOME/.config");
+        Ok(None)
+    }
+}
 
-                if let Some(config) = self.lookup_config_file_in_directory(&home_config_path)? {
-                    return Ok(Some(config));
-                }
+/// The directories searched for a user-level configuration file: `$XDG_CONFIG_HOME`, `$XDG_CONFIG_HOME/stylua`,
+/// `$HOME/.config` and `$HOME/.config/stylua`
+fn global_config_directories() -> Vec<PathBuf> {
+    let mut directories = Vec::new();
 
-                debug!("config: looking in $HOME/.config/stylua");
-                let home_config_path = home_config_path.join("stylua");
-                if home_config_path.exists() {
-                    if let Some(config) = self.lookup_config_file_in_directory(&home_config_path)? {
-                        return Ok(Some(config));
-                    }
-                }
-            }
-        }
+    if let Ok(xdg_config) = env::var("XDG_CONFIG_HOME") {
+        let xdg_config_path = PathBuf::from(xdg_config);
+        directories.push(xdg_config_path.clone());
+        directories.push(xdg_config_path.join("stylua"));
+    }
 
-        Ok(None)
+    if let Ok(home) = env::var("HOME") {
+        let home_config_path = Path::new(&home).join(".config");
+        directories.push(home_config_path.clone());
+        directories.push(home_config_path.join("stylua"));
     }
+
+    directories
 }
 
 /// Searches the directory for the configuration toml file (i.e. `stylua.toml` or `.stylua.toml`)
@@ -245,19 +402,6 @@ fn find_toml_file(directory: &Path) -> Option<PathBuf> {
     None
 }
 
-pub fn find_ignore_file_path(mut directory: PathBuf, recursive: bool) -> Option<PathBuf> {
-    debug!("config: looking for ignore file in {}", directory.display());
-    let file_path = directory.join(".styluaignore");
-    if file_path.is_file() {
-        debug!("config: resolved ignore file at {}", file_path.display());
-        Some(file_path)
-    } else if recursive && directory.pop() {
-        find_ignore_file_path(directory, recursive)
-    } else {
-        None
-    }
-}
-
 /// Handles any overrides provided by command line options
 fn load_overrides(config: Config, opt: &Opt) -> Config {
     let mut new_config = config;
@@ -289,9 +433,54 @@ fn load_overrides(config: Config, opt: &Opt) -> Config {
     if let Some(collapse_simple_statement) = opt.format_opts.collapse_simple_statement {
         new_config.collapse_simple_statement = collapse_simple_statement.into();
     }
+    if let Some(if_expression_style) = opt.format_opts.if_expression_style {
+        new_config.if_expression_style = if_expression_style.into();
+    }
+    if let Some(return_type_style) = opt.format_opts.return_type_style {
+        new_config.return_type_style = return_type_style.into();
+    }
+    if let Some(return_parentheses) = opt.format_opts.return_parentheses {
+        new_config.return_parentheses = return_parentheses.into();
+    }
+    if let Some(binary_chain_indent) = opt.format_opts.binary_chain_indent {
+        new_config.binary_chain_indent = binary_chain_indent.into();
+    }
+    if let Some(last_arg_callback) = opt.format_opts.last_arg_callback {
+        new_config.last_arg_callback = last_arg_callback.into();
+    }
+    if let Some(dispatch_table_style) = opt.format_opts.dispatch_table_style {
+        new_config.dispatch_table_style = dispatch_table_style.into();
+    }
+    if let Some(unary_operator_spacing) = opt.format_opts.unary_operator_spacing {
+        new_config.unary_operator_spacing = unary_operator_spacing.into();
+    }
+    if let Some(table_element_max_width) = opt.format_opts.table_element_max_width {
+        new_config.table_element_max_width = table_element_max_width;
+    }
+    if opt.format_opts.wrap_index_chains {
+        new_config.wrap_index_chains = true;
+    }
+    if let Some(nested_table_expansion) = opt.format_opts.nested_table_expansion {
+        new_config.nested_table_expansion = nested_table_expansion.into();
+    }
+    if let Some(assigned_function) = opt.format_opts.assigned_function {
+        new_config.assigned_function = assigned_function.into();
+    }
+    if let Some(single_table_argument) = opt.format_opts.single_table_argument {
+        new_config.single_table_argument = single_table_argument.into();
+    }
+    if let Some(leading_comment_newline_gap) = opt.format_opts.leading_comment_newline_gap {
+        new_config.leading_comment_newline_gap = leading_comment_newline_gap.into();
+    }
+    if let Some(parameter_comments) = opt.format_opts.parameter_comments {
+        new_config.parameter_comments = parameter_comments.into();
+    }
     if opt.format_opts.sort_requires {
         new_config.sort_requires = SortRequiresConfig { enabled: true }
     }
+    if opt.format_opts.normalize_elseif {
+        new_config.normalize_elseif = true;
+    }
 
     new_config
 }
@@ -299,6 +488,7 @@ fn load_overrides(config: Config, opt: &Opt) -> Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_fs::prelude::*;
     use clap::StructOpt;
     use stylua_lib::{CallParenType, IndentType, LineEndings, LuaVersion, QuoteStyle};
 
@@ -357,4 +547,83 @@ mod tests {
         let config = load_overrides(default_config, &override_opt);
         assert_eq!(config.call_parentheses, CallParenType::None);
     }
+
+    #[test]
+    fn test_configuration_is_cached_for_files_in_the_same_directory() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("stylua.toml")
+            .write_str("column_width = 80")
+            .unwrap();
+        cwd.child("src").create_dir_all().unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME", "--no-editorconfig"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let load_configuration = |config_resolver: &mut ConfigResolver, file_name: &str| {
+            config_resolver
+                .load_configuration_with_search_root(
+                    &cwd.child("src").child(file_name),
+                    Some(cwd.to_path_buf()),
+                    None,
+                )
+                .unwrap()
+        };
+
+        assert_eq!(
+            load_configuration(&mut config_resolver, "a.lua").column_width,
+            80
+        );
+
+        // The configuration file is removed, so it can only be found for the second file through the cache
+        fs::remove_file(cwd.child("stylua.toml")).unwrap();
+        assert_eq!(
+            load_configuration(&mut config_resolver, "b.lua").column_width,
+            80
+        );
+
+        config_resolver.invalidate_directory(cwd.path());
+        assert_eq!(
+            load_configuration(&mut config_resolver, "b.lua").column_width,
+            Config::default().column_width
+        );
+    }
+
+    #[test]
+    fn test_global_configuration_used_when_no_configuration_file_is_found() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("global/stylua/stylua.toml")
+            .write_str("column_width = 80")
+            .unwrap();
+        cwd.child("project/src").create_dir_all().unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME", "--no-editorconfig"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        config_resolver.global_config_directories = vec![
+            cwd.child("global").to_path_buf(),
+            cwd.child("global/stylua").to_path_buf(),
+        ];
+        let load_configuration = |config_resolver: &mut ConfigResolver| {
+            config_resolver
+                .load_configuration_with_search_root(
+                    &cwd.child("project/src/a.lua"),
+                    Some(cwd.child("project").to_path_buf()),
+                    None,
+                )
+                .unwrap()
+        };
+
+        assert_eq!(
+            load_configuration(&mut config_resolver).column_width,
+            Config::default().column_width
+        );
+
+        config_resolver.enable_global_configuration();
+        assert_eq!(load_configuration(&mut config_resolver).column_width, 80);
+
+        // Configuration in the project takes precedence over the global configuration
+        cwd.child("project/stylua.toml")
+            .write_str("column_width = 100")
+            .unwrap();
+        config_resolver.invalidate_directory(cwd.path());
+        assert_eq!(load_configuration(&mut config_resolver).column_width, 100);
+    }
 }
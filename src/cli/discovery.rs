@@ -0,0 +1,262 @@
+//! File discovery shared by the CLI and the language server, so that both make the same decisions about
+//! which files are ignored and which configuration is used to format them.
+
+use crate::config::ConfigResolver;
+use crate::opt::Opt;
+use anyhow::{Context, Result};
+use ignore::{gitignore::Gitignore, overrides::OverrideBuilder, Match, WalkBuilder};
+use log::*;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use stylua_lib::Config;
+
+lazy_static::lazy_static! {
+    /// The glob files are matched against when no `--glob` patterns are provided
+    pub static ref DEFAULT_GLOB: globset::GlobSet = {
+        let mut builder = globset::GlobSetBuilder::new();
+        builder.add(globset::Glob::new("**/*.lua").expect("cannot create default glob"));
+        #[cfg(feature = "luau")]
+        builder.add(globset::Glob::new("**/*.luau").expect("cannot create default luau glob"));
+        builder.build().expect("cannot build default globset")
+    };
+}
+
+/// Makes the path absolute, resolving `.` and `..` components without following symlinks, so that its ancestors are
+/// the directories it is actually within. The file walker also does not follow symlinks when matching ignore files.
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    let mut absolute_path = PathBuf::new();
+    for component in std::env::current_dir()?.join(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                absolute_path.pop();
+            }
+            component => absolute_path.push(component),
+        }
+    }
+    Ok(absolute_path)
+}
+
+/// The `.styluaignore` files found whilst discovering files, keyed by the directory they are in.
+/// Each file is only read once, as the same directories are checked for every file within them.
+#[derive(Default)]
+pub struct IgnoreFiles {
+    cache: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreFiles {
+    fn load(&self, directory: &Path) -> Result<Option<Arc<Gitignore>>> {
+        if let Some(ignore) = self.cache.lock().unwrap().get(directory) {
+            return Ok(ignore.clone());
+        }
+
+        let file_path = directory.join(".styluaignore");
+        let ignore = if file_path.is_file() {
+            debug!("discovery: resolved ignore file at {}", file_path.display());
+            let (ignore, err) = Gitignore::new(file_path);
+            if let Some(err) = err {
+                return Err(err).context("failed to parse ignore file");
+            }
+            Some(Arc::new(ignore))
+        } else {
+            None
+        };
+
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(directory.to_path_buf(), ignore.clone());
+        Ok(ignore)
+    }
+
+    /// Finds the `.styluaignore` pattern which ignores the provided path, if any, returning the pattern and the
+    /// file it was defined in.
+    ///
+    /// The `.styluaignore` files in every ancestor directory of the path are checked, with the closest file
+    /// taking precedence. If `include_parents` is set, the path is also ignored when any of its parent
+    /// directories are matched by a pattern.
+    pub fn find_match(
+        &self,
+        path: &Path,
+        include_parents: bool,
+    ) -> Result<Option<(String, PathBuf)>> {
+        let path = absolute_path(path)?;
+        let is_dir = path.is_dir();
+
+        for directory in path.ancestors().skip(1) {
+            let Some(ignore) = self.load(directory)? else {
+                continue;
+            };
+
+            let matched = if include_parents {
+                ignore.matched_path_or_any_parents(&path, is_dir)
+            } else {
+                ignore.matched(&path, is_dir)
+            };
+
+            match matched {
+                Match::Ignore(glob) => {
+                    let source = glob.from().unwrap_or_else(|| ignore.path());
+                    return Ok(Some((glob.original().to_string(), source.to_path_buf())));
+                }
+                Match::Whitelist(_) => return Ok(None),
+                Match::None => (),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether the provided path is ignored by a `.styluaignore` file
+    pub fn is_ignored(&self, path: &Path, include_parents: bool) -> Result<bool> {
+        Ok(self.find_match(path, include_parents)?.is_some())
+    }
+}
+
+/// Whether the provided path, or any of its parent directories, is ignored by a `.styluaignore` file.
+/// This is used for individual files, such as explicitly provided paths and documents opened in the language server.
+/// The CLI file walker uses the same rules whilst traversing directories.
+pub fn is_ignored(path: &Path) -> Result<bool> {
+    IgnoreFiles::default().is_ignored(path, true)
+}
+
+/// Finds the files within the directory which are formatted by default, in the same way as the CLI file walker
+/// when no `--glob` patterns are provided. Files and directories ignored by a `.styluaignore` file are skipped.
+pub fn find_files(directory: &Path) -> Vec<PathBuf> {
+    let ignore_files = Arc::new(IgnoreFiles::default());
+    WalkBuilder::new(directory)
+        .standard_filters(true)
+        .parents(true)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            !ignore_files
+                .is_ignored(entry.path(), false)
+                .unwrap_or(false)
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.into_path())
+        .filter(|path| DEFAULT_GLOB.is_match(path))
+        .collect()
+}
+
+/// Whether the provided path was explicitly provided to the tool
+pub fn is_explicitly_provided(opt: &Opt, path: &Path) -> bool {
+    opt.files.iter().any(|p| path == *p)
+}
+
+/// By default, files explicitly passed to the command line will be formatted regardless of whether
+/// they are present in .styluaignore / not glob matched. If `--respect-ignores` is provided,
+/// then we enforce .styluaignore / glob matching on explicitly passed paths.
+pub fn should_respect_ignores(opt: &Opt, path: &Path) -> bool {
+    !is_explicitly_provided(opt, path) || opt.respect_ignores
+}
+
+/// Resolves the configuration used to format the file at the provided path
+pub fn resolve_config(
+    config_resolver: &mut ConfigResolver,
+    path: &Path,
+    search_root: Option<PathBuf>,
+    language_id: Option<&str>,
+) -> Result<Config> {
+    config_resolver.load_configuration_with_search_root(path, search_root, language_id)
+}
+
+/// Finds the `.gitignore` pattern which ignores the provided path, if any, returning the pattern and the
+/// file it was defined in. Like the file walker, `.gitignore` files are only respected inside of a git repository.
+fn find_gitignore_match(path: &Path) -> Result<Option<(String, PathBuf)>> {
+    let path = absolute_path(path)?;
+    let mut gitignore_files = Vec::new();
+
+    for directory in path.ancestors().skip(1) {
+        let file_path = directory.join(".gitignore");
+        if file_path.is_file() {
+            gitignore_files.push(file_path);
+        }
+
+        if directory.join(".git").exists() {
+            // The closest .gitignore file takes precedence
+            for file_path in gitignore_files {
+                let (ignore, err) = Gitignore::new(&file_path);
+                if let Some(err) = err {
+                    return Err(err).context("failed to parse .gitignore file");
+                }
+
+                match ignore.matched_path_or_any_parents(&path, false) {
+                    Match::Ignore(glob) => {
+                        return Ok(Some((glob.original().to_string(), file_path)))
+                    }
+                    Match::Whitelist(_) => return Ok(None),
+                    Match::None => (),
+                }
+            }
+
+            return Ok(None);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Explains why the provided path would be skipped when searching for files to format, or returns `None` if
+/// it would be formatted. The path is treated as if it was found whilst traversing a directory, so ignores
+/// are always respected.
+pub fn explain_ignore(opt: &Opt, path: &Path) -> Result<Option<String>> {
+    let cwd = std::env::current_dir()?;
+
+    let ignore_match = match IgnoreFiles::default().find_match(path, true)? {
+        Some(ignore_match) => Some(ignore_match),
+        None => find_gitignore_match(path)?,
+    };
+
+    if let Some((pattern, source)) = ignore_match {
+        return Ok(Some(format!(
+            "ignored by pattern '{}' in {}",
+            pattern,
+            source.strip_prefix(&cwd).unwrap_or(&source).display()
+        )));
+    }
+
+    match opt.glob {
+        Some(ref globs) => {
+            let mut overrides = OverrideBuilder::new(&cwd);
+            for pattern in globs {
+                overrides.add(pattern)?;
+            }
+
+            if !overrides.build()?.matched(path, false).is_ignore() {
+                return Ok(None);
+            }
+
+            // Later patterns take precedence, so find the last negated pattern which ignores the path
+            for pattern in globs
+                .iter()
+                .rev()
+                .filter(|pattern| pattern.starts_with('!'))
+            {
+                let mut overrides = OverrideBuilder::new(&cwd);
+                overrides.add(pattern)?;
+                if overrides.build()?.matched(path, false).is_ignore() {
+                    return Ok(Some(format!(
+                        "ignored by pattern '{}' provided by --glob",
+                        pattern
+                    )));
+                }
+            }
+
+            Ok(Some(
+                "does not match any pattern provided by --glob".to_string(),
+            ))
+        }
+        None if !DEFAULT_GLOB.is_match(path) => Ok(Some(
+            "does not match the default glob (**/*.lua or **/*.luau)".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,103 @@
+//! Reading the contents of files to format.
+//! Large files are memory-mapped where the platform supports it, so that their contents are not copied into memory
+//! before formatting. Otherwise, the file is read into a `String` as normal.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    ops::Deref,
+    path::Path,
+};
+
+/// Files of at least this many bytes are memory-mapped, as mapping is slower than reading for small files
+#[cfg(any(unix, windows))]
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// The contents of a file, which dereferences to a `str` containing valid UTF-8
+pub enum FileContents {
+    Read(String),
+    #[cfg(any(unix, windows))]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for FileContents {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            FileContents::Read(contents) => contents,
+            // SAFETY: the mapped contents are checked to be valid UTF-8 when the file is read
+            #[cfg(any(unix, windows))]
+            FileContents::Mapped(mmap) => unsafe { std::str::from_utf8_unchecked(mmap) },
+        }
+    }
+}
+
+/// Reads the contents of the file at the given path, memory-mapping it if it is large enough.
+/// If the file cannot be mapped, it is read instead.
+pub fn read_file(path: &Path) -> io::Result<FileContents> {
+    let mut file = File::open(path)?;
+
+    #[cfg(any(unix, windows))]
+    if file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: the file could be modified by another process whilst it is mapped, which we cannot prevent.
+        // The mapping is only held whilst formatting, and must be dropped before the file is written to.
+        if let Ok(mmap) = unsafe { memmap2::Mmap::map(&file) } {
+            std::str::from_utf8(&mmap)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            return Ok(FileContents::Mapped(mmap));
+        }
+    }
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(FileContents::Read(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn test_read_small_file() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local x = 1\n").unwrap();
+
+        let contents = read_file(&cwd.child("foo.lua")).unwrap();
+        assert!(matches!(contents, FileContents::Read(_)));
+        assert_eq!(&*contents, "local x = 1\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_read_large_file_is_mapped() {
+        let code = "local x = 1\n".repeat(MMAP_THRESHOLD as usize / 10);
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str(&code).unwrap();
+
+        let contents = read_file(&cwd.child("foo.lua")).unwrap();
+        assert!(matches!(contents, FileContents::Mapped(_)));
+        assert_eq!(&*contents, code);
+
+        cwd.close().unwrap();
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_read_large_file_with_invalid_utf8() {
+        let mut bytes = "local x = 1\n"
+            .repeat(MMAP_THRESHOLD as usize / 10)
+            .into_bytes();
+        bytes.push(0xFF);
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_binary(&bytes).unwrap();
+
+        let error = read_file(&cwd.child("foo.lua")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        cwd.close().unwrap();
+    }
+}
@@ -1,30 +1,96 @@
-use std::{convert::TryInto, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    convert::TryInto,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering as AtomicOrdering},
+        Arc,
+    },
+};
 
+use anyhow::Context;
+use crossbeam_channel::Receiver;
+use full_moon::{
+    ast::{Ast, LastStmt, Stmt},
+    node::Node,
+    tokenizer::{Lexer, LexerResult, Token, TokenType},
+    visitors::Visitor,
+};
 use lsp_server::{Connection, ErrorCode, Message, Response};
 use lsp_textdocument::{FullTextDocument, TextDocuments};
 use lsp_types::{
-    request::{Formatting, RangeFormatting, Request},
-    DocumentFormattingParams, DocumentRangeFormattingParams, FormattingOptions, InitializeParams,
-    InitializeResult, OneOf, Range, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextEdit, Uri, WorkspaceFolder,
+    notification::{
+        Cancel, DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles,
+        DidCloseTextDocument, DidOpenTextDocument, LogMessage, Notification, Progress,
+        PublishDiagnostics, ShowMessage,
+    },
+    request::{
+        ApplyWorkspaceEdit, CodeActionRequest, DocumentDiagnosticRequest, ExecuteCommand,
+        Formatting, OnTypeFormatting, RangeFormatting, RegisterCapability, Request, Shutdown,
+        WillSaveWaitUntil,
+    },
+    ApplyWorkspaceEditParams, CancelParams, ClientCapabilities, CodeAction, CodeActionKind,
+    CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, Diagnostic,
+    DiagnosticOptions, DiagnosticServerCapabilities, DiagnosticSeverity,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DocumentDiagnosticReportResult, DocumentFormattingOptions, DocumentFormattingParams,
+    DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    ExecuteCommandOptions, ExecuteCommandParams, FileSystemWatcher, FormattingOptions,
+    FullDocumentDiagnosticReport, GlobPattern, InitializeParams, InitializeResult,
+    LogMessageParams, MessageType, NumberOrString, OneOf, Position, PositionEncodingKind,
+    ProgressParams, ProgressParamsValue, ProgressToken, PublishDiagnosticsParams, Range,
+    Registration, RegistrationParams, RelatedFullDocumentDiagnosticReport, RelativePattern,
+    ServerCapabilities, ServerInfo, ShowMessageParams, TextDocumentContentChangeEvent,
+    TextDocumentIdentifier, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextEdit, Uri, WillSaveTextDocumentParams, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressEnd, WorkDoneProgressOptions, WorkDoneProgressParams,
+    WorkDoneProgressReport, WorkspaceEdit, WorkspaceFolder,
 };
 use serde::{Deserialize, Serialize};
 use similar::{DiffOp, TextDiff};
-use stylua_lib::{format_code, IndentType, OutputVerification};
+use stylua_lib::{
+    format_ast, format_code, Config, IndentType, LineEndings, LuaVersion, OutputVerification,
+};
+use threadpool::ThreadPool;
+
+use crate::{
+    config::{ConfigResolver, CONFIG_FILE_NAME},
+    discovery, opt,
+};
 
-use crate::{config::ConfigResolver, opt, stylua_ignore};
+/// The byte offset of each character in the text, followed by the length of the text.
+/// Used to convert the character indices of a diff into byte offsets
+fn char_offsets(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(text.len()))
+        .collect()
+}
 
 fn diffop_to_textedit(
     op: DiffOp,
     document: &FullTextDocument,
+    contents_offsets: &[usize],
     formatted_contents: &str,
+    formatted_offsets: &[usize],
+    position_encoding: &PositionEncodingKind,
 ) -> Option<TextEdit> {
+    let position =
+        |index: usize| offset_to_position(document, contents_offsets[index], position_encoding);
     let range = |start: usize, len: usize| Range {
-        start: document.position_at(start.try_into().expect("usize fits into u32")),
-        end: document.position_at((start + len).try_into().expect("usize fits into u32")),
+        start: position(start),
+        end: position(start + len),
     };
 
-    let lookup = |start: usize, len: usize| formatted_contents[start..start + len].to_string();
+    let lookup = |start: usize, len: usize| {
+        formatted_contents[formatted_offsets[start]..formatted_offsets[start + len]].to_string()
+    };
 
     match op {
         DiffOp::Equal {
@@ -60,37 +126,392 @@ fn diffop_to_textedit(
     }
 }
 
+/// Computes the edits which change the old contents into the new contents, only keeping edits which intersect the
+/// byte range of the old contents if one is provided. The diff is by character, so that an edit never starts or ends
+/// within a multi-byte character. The line endings of the new contents are kept as they are
+fn compute_edits(
+    contents: &str,
+    formatted_contents: &str,
+    byte_range: Option<(usize, usize)>,
+    position_encoding: &PositionEncodingKind,
+) -> Vec<TextEdit> {
+    // Only used to convert between offsets and positions
+    let document = &FullTextDocument::new(String::new(), 0, contents.to_string());
+    let contents_offsets = char_offsets(contents);
+    let formatted_offsets = char_offsets(formatted_contents);
+    let is_within_range = |op: &DiffOp| {
+        byte_range.is_none_or(|(start, end)| {
+            let old_range = op.old_range();
+            contents_offsets[old_range.start] <= end && contents_offsets[old_range.end] >= start
+        })
+    };
+
+    TextDiff::from_chars(contents, formatted_contents)
+        .grouped_ops(0)
+        .into_iter()
+        .flatten()
+        .filter(is_within_range)
+        .filter_map(|op| {
+            diffop_to_textedit(
+                op,
+                document,
+                &contents_offsets,
+                formatted_contents,
+                &formatted_offsets,
+                position_encoding,
+            )
+        })
+        .collect()
+}
+
+/// A custom request for the configuration used to format a document, so that editors can show it to users.
+/// This is the configuration before any editor formatting options are applied, as these are only provided
+/// with each formatting request.
+enum ResolvedConfig {}
+
+impl Request for ResolvedConfig {
+    type Params = TextDocumentIdentifier;
+    type Result = ResolvedConfigResult;
+    const METHOD: &'static str = "stylua/resolvedConfig";
+}
+
+#[derive(Serialize, Deserialize)]
+struct ResolvedConfigResult {
+    config: Config,
+    /// Whether the editor's formatting options are used when formatting, unless `config` was found in a
+    /// configuration file which sets them
+    respect_editor_formatting_options: bool,
+}
+
+/// A custom request for the configuration used to format a document, along with the configuration file it was read
+/// from, to help debug why configuration is not being applied
+enum EffectiveConfig {}
+
+impl Request for EffectiveConfig {
+    type Params = TextDocumentIdentifier;
+    type Result = EffectiveConfigResult;
+    const METHOD: &'static str = "stylua/effectiveConfig";
+}
+
+#[derive(Serialize, Deserialize)]
+struct EffectiveConfigResult {
+    config: Config,
+    /// The configuration file which `config` was read from, or `None` if no configuration file was found and
+    /// `config` was resolved from the defaults and any `.editorconfig` properties
+    config_file: Option<PathBuf>,
+}
+
+/// A custom request to format the whole document, which also responds with where the cursor moves to once the edits
+/// are applied, so that editors can keep the cursor next to the same code
+enum FormatWithCursor {}
+
+impl Request for FormatWithCursor {
+    type Params = FormatWithCursorParams;
+    type Result = Option<FormatWithCursorResult>;
+    const METHOD: &'static str = "stylua/formatWithCursor";
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FormatWithCursorParams {
+    text_document: TextDocumentIdentifier,
+    /// The position of the cursor
+    position: Position,
+    options: FormattingOptions,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FormatWithCursorResult {
+    edits: Vec<TextEdit>,
+    /// The position of the cursor once the edits are applied
+    position: Position,
+}
+
+/// A request to format several ranges of a document at once, such as for multiple selections. This is part of
+/// LSP 3.18, which `lsp_types` does not yet support
+enum RangesFormatting {}
+
+impl Request for RangesFormatting {
+    type Params = DocumentRangesFormattingParams;
+    type Result = Option<Vec<TextEdit>>;
+    const METHOD: &'static str = "textDocument/rangesFormatting";
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentRangesFormattingParams {
+    text_document: TextDocumentIdentifier,
+    ranges: Vec<Range>,
+    options: FormattingOptions,
+    #[serde(flatten)]
+    work_done_progress_params: WorkDoneProgressParams,
+}
+
+/// Sorts the ranges, merging ranges which overlap or are adjacent, so that no statement is formatted twice
+fn coalesce_ranges(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.sort_by_key(|range| (range.start, range.end));
+    let mut coalesced: Vec<Range> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => coalesced.push(range),
+        }
+    }
+    coalesced
+}
+
+/// Sorts the edits from formatting several ranges, dropping any edit which overlaps an earlier one. Ranges are widened
+/// to whole statements, so the same edit can be made when formatting separate ranges
+fn merge_edits(mut edits: Vec<TextEdit>) -> Vec<TextEdit> {
+    edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+    let mut merged: Vec<TextEdit> = Vec::with_capacity(edits.len());
+    for edit in edits {
+        if merged
+            .last()
+            .is_none_or(|last| edit.range.start >= last.range.end && edit.range != last.range)
+        {
+            merged.push(edit);
+        }
+    }
+    merged
+}
+
+/// The command which formats every file within the workspace folders
+const FORMAT_WORKSPACE_COMMAND: &str = "stylua.formatWorkspace";
+
+#[derive(Serialize, Deserialize, Default)]
+struct FormatWorkspaceResult {
+    /// The files which were changed by formatting
+    formatted_files: Vec<Uri>,
+    /// The files which could not be formatted, such as those which fail to parse
+    failed_files: Vec<Uri>,
+}
+
+/// The file name used to resolve configuration for documents which are not saved to a file
+const UNSAVED_DOCUMENT_FILE_NAME: &str = "untitled.lua";
+/// The pattern of the configuration files watched for changes, relative to each workspace folder
+const WATCHED_CONFIG_FILES_PATTERN: &str = "**/{stylua.toml,.stylua.toml,.editorconfig}";
+
+/// The UTF-8 byte order mark, which some editors write at the start of a file. It is not valid Lua, so it is removed
+/// before parsing and added back to the formatted output
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Splits the code into its byte order mark, which is empty if there is none, and the code following it
+fn split_byte_order_mark(code: &str) -> (&str, &str) {
+    match code.strip_prefix(BYTE_ORDER_MARK) {
+        Some(rest) => code.split_at(code.len() - rest.len()),
+        None => ("", code),
+    }
+}
+
+/// Formats an AST in the same way as [`format_ast`], which can be replaced in tests to simulate formatter bugs
+type Formatter = fn(
+    Ast,
+    Config,
+    Option<stylua_lib::Range>,
+    OutputVerification,
+) -> Result<Ast, stylua_lib::Error>;
+
+/// A version of a document parsed with a particular syntax, kept so that the document is only parsed again once it
+/// changes
+struct ParsedDocument {
+    version: i32,
+    syntax: LuaVersion,
+    /// The byte offset of the parsed code within the document, which is the length of any byte order mark before it.
+    /// Positions within the AST are relative to this offset
+    offset: usize,
+    /// The parsed AST, which may have been reconstructed if there are errors
+    ast: Ast,
+    errors: Vec<full_moon::Error>,
+}
+
+impl ParsedDocument {
+    fn parse(document: &FullTextDocument, syntax: LuaVersion) -> ParsedDocument {
+        let (byte_order_mark, code) = split_byte_order_mark(document.get_content(None));
+        let result = full_moon::parse_fallible(code, syntax.into());
+        ParsedDocument {
+            version: document.version(),
+            syntax,
+            offset: byte_order_mark.len(),
+            errors: result.errors().to_vec(),
+            ast: result.into_ast(),
+        }
+    }
+}
+
+/// Parses an open document, reusing the result from the last time the same version was parsed with the same syntax
+#[allow(clippy::mutable_key_type)]
+fn parse_document<'a>(
+    parsed_documents: &'a mut HashMap<Uri, ParsedDocument>,
+    uri: &Uri,
+    document: &FullTextDocument,
+    syntax: LuaVersion,
+) -> &'a ParsedDocument {
+    let parsed_document = parsed_documents
+        .entry(uri.clone())
+        .or_insert_with(|| ParsedDocument::parse(document, syntax));
+    if parsed_document.version != document.version() || parsed_document.syntax != syntax {
+        *parsed_document = ParsedDocument::parse(document, syntax);
+    }
+    parsed_document
+}
+
 struct LanguageServer<'a> {
     documents: TextDocuments,
     workspace_folders: Vec<WorkspaceFolder>,
     root_uri: Option<Uri>,
-    search_parent_directories: bool,
     respect_editor_formatting_options: bool,
+    /// Whether formatting requests should publish a diagnostic for unformatted documents, rather than returning edits
+    diagnostics_only: bool,
+    /// Whether formatting the whole document should verify the output, warning the client if verification fails
+    verify_output: bool,
+    /// Whether formatted documents keep the line ending used most often in the document, in place of the configured
+    /// line endings
+    detect_line_endings: bool,
+    formatter: Formatter,
+    /// The LSP methods which are allowed to format documents. If not set, all methods are allowed
+    format_triggers: Option<Vec<String>>,
     config_resolver: &'a mut ConfigResolver<'a>,
+    /// The edits required to format each document which has been flagged as unformatted, used to fix it through a code action
+    unformatted_documents: HashMap<Uri, Vec<TextEdit>>,
+    /// Notifications to send to the client once the current message has been handled
+    pending_notifications: Vec<lsp_server::Notification>,
+    /// Messages to send to the client before responding to the current request, such as progress notifications
+    pending_messages: Vec<Message>,
+    /// The number of requests which have been sent to the client, used to create the ID of the next request
+    sent_request_count: u32,
+    /// The encoding of the characters of positions sent to and from the client
+    position_encoding: PositionEncodingKind,
+    /// The documents which syntax error diagnostics have been published for, which are cleared once the errors are fixed
+    documents_with_syntax_errors: HashSet<Uri>,
+    /// The version of each document which was last found to be formatted, along with the formatting options used,
+    /// so that formatting it again is skipped until the document changes
+    formatted_document_versions: HashMap<Uri, (i32, FormattingOptions)>,
+    /// The documents with changes which have been received but not yet applied. Formatting these documents, or
+    /// publishing their diagnostics, is skipped as the result would be out of date once the changes are applied
+    documents_with_queued_changes: HashSet<Uri>,
+    /// The requests which have been cancelled by a notification that has been received but not yet handled
+    cancelled_requests: HashSet<lsp_server::RequestId>,
+    /// The AST of each open document, which is reused until the document changes
+    parsed_documents: HashMap<Uri, ParsedDocument>,
+    /// The messages from the client, which are received while formatting so that a format can be abandoned if the
+    /// document changes before it completes
+    receiver: Option<Receiver<Message>>,
+    /// The single thread which formats while messages are received. A format which is abandoned keeps running until
+    /// it completes, so later formats wait for it rather than piling up on threads of their own
+    format_pool: ThreadPool,
+    /// Messages received while handling the current message, which are handled after those already received
+    received_messages: VecDeque<Message>,
+    /// The configuration errors which have been shown to the user, so that each is only shown once rather than every
+    /// time a document is formatted. Cleared when configuration changes
+    shown_configuration_errors: HashSet<String>,
 }
 
 enum FormattingError {
     StyLuaError,
+    /// The document does not parse
+    SyntaxError,
     NotLuaDocument,
     DocumentNotFound,
     FileIsIgnored,
+    /// A position of the range is past the end of its line, or within a character
+    InvalidRange,
+    /// The document changed while it was being formatted
+    ContentModified,
 }
 
 impl LanguageServer<'_> {
     fn new<'a>(
         workspace_folders: Vec<WorkspaceFolder>,
         root_uri: Option<Uri>,
-        search_parent_directories: bool,
-        respect_editor_formatting_options: bool,
+        position_encoding: PositionEncodingKind,
         config_resolver: &'a mut ConfigResolver<'a>,
     ) -> LanguageServer<'a> {
         LanguageServer {
             documents: TextDocuments::new(),
             workspace_folders,
             root_uri,
-            search_parent_directories,
-            respect_editor_formatting_options,
+            respect_editor_formatting_options: false,
+            diagnostics_only: false,
+            verify_output: false,
+            detect_line_endings: false,
+            formatter: format_ast,
+            format_triggers: None,
             config_resolver,
+            unformatted_documents: HashMap::new(),
+            pending_notifications: Vec::new(),
+            pending_messages: Vec::new(),
+            sent_request_count: 0,
+            position_encoding,
+            documents_with_syntax_errors: HashSet::new(),
+            formatted_document_versions: HashMap::new(),
+            documents_with_queued_changes: HashSet::new(),
+            cancelled_requests: HashSet::new(),
+            parsed_documents: HashMap::new(),
+            receiver: None,
+            format_pool: ThreadPool::with_name("stylua-format".to_string(), 1),
+            received_messages: VecDeque::new(),
+            shown_configuration_errors: HashSet::new(),
+        }
+    }
+
+    /// Applies the settings provided by the client through the initialization options or a
+    /// `workspace/didChangeConfiguration` notification. Only the settings which are provided are changed
+    fn apply_settings(&mut self, settings: InitializationOptions) {
+        if let Some(respect_editor_formatting_options) = settings.respect_editor_formatting_options
+        {
+            self.respect_editor_formatting_options = respect_editor_formatting_options;
+        }
+        if let Some(diagnostics_only) = settings.diagnostics_only {
+            self.diagnostics_only = diagnostics_only;
+        }
+        if let Some(verify_output) = settings.verify_output {
+            self.verify_output = verify_output;
+        }
+        if let Some(detect_line_endings) = settings.detect_line_endings {
+            self.detect_line_endings = detect_line_endings;
+        }
+        if let Some(format_triggers) = settings.format_triggers {
+            self.format_triggers = Some(format_triggers);
+        }
+
+        if let Some(config) = settings.config {
+            self.config_resolver.set_default_configuration(config);
+        }
+        for (language_id, config) in settings.language_config {
+            self.config_resolver
+                .set_language_default_configuration(language_id, config);
+        }
+        if let Some(config_path) = settings.config_path {
+            let config_path = match &self.root_uri {
+                Some(root_uri) => Path::new(root_uri.path().as_str()).join(config_path),
+                None => config_path,
+            };
+            if let Err(err) = self
+                .config_resolver
+                .set_forced_configuration_path(&config_path)
+            {
+                log_message(
+                    &mut self.pending_notifications,
+                    MessageType::ERROR,
+                    format!(
+                        "could not load configuration from {}: {:#}",
+                        config_path.display(),
+                        err
+                    ),
+                );
+            }
+        }
+
+        // Documents may now be formatted differently
+        self.formatted_document_versions.clear();
+        self.shown_configuration_errors.clear();
+    }
+
+    fn is_format_trigger_enabled(&self, method: &str) -> bool {
+        match &self.format_triggers {
+            Some(format_triggers) => format_triggers.iter().any(|trigger| trigger == method),
+            None => true,
         }
     }
 
@@ -118,765 +539,6335 @@ impl LanguageServer<'_> {
             Some(workspace) => workspace.path().as_str().into(),
             None => match &self.root_uri {
                 Some(root_uri) => root_uri.path().as_str().into(),
-                None => std::env::current_dir().expect("Could not find current directory"),
+                // Configuration is then only found from the defaults, rather than failing to format
+                None => std::env::current_dir().unwrap_or_default(),
             },
         }
     }
 
+    /// The path used to resolve configuration for a document. Documents which are not saved to a file,
+    /// such as those with an `untitled:` URI, are treated as a file in the root used to search for configuration
+    fn document_path(&self, uri: &Uri) -> PathBuf {
+        if is_file_uri(uri) {
+            uri.path().as_str().into()
+        } else {
+            self.find_config_root(uri).join(UNSAVED_DOCUMENT_FILE_NAME)
+        }
+    }
+
+    /// Whether the document is ignored by a `.styluaignore` file. Documents which are not saved to a file are never ignored
+    fn is_document_ignored(&self, uri: &Uri) -> bool {
+        is_file_uri(uri) && discovery::is_ignored(uri.path().as_str().as_ref()).unwrap_or(false)
+    }
+
+    fn handle_resolved_config(
+        &mut self,
+        params: TextDocumentIdentifier,
+    ) -> anyhow::Result<ResolvedConfigResult> {
+        let language_id = self
+            .documents
+            .get_document(&params.uri)
+            .map(FullTextDocument::language_id);
+        let search_root = Some(self.find_config_root(&params.uri));
+        let path = self.document_path(&params.uri);
+        let mut config =
+            discovery::resolve_config(self.config_resolver, &path, search_root, language_id)?;
+        config.syntax = document_syntax(config.syntax, &path, language_id);
+
+        Ok(ResolvedConfigResult {
+            config,
+            respect_editor_formatting_options: self.respect_editor_formatting_options,
+        })
+    }
+
+    fn handle_effective_config(
+        &mut self,
+        params: TextDocumentIdentifier,
+    ) -> anyhow::Result<EffectiveConfigResult> {
+        let path = self.document_path(&params.uri);
+        let search_root = self.find_config_root(&params.uri);
+        let config = self.handle_resolved_config(params)?.config;
+        let config_file = self
+            .config_resolver
+            .find_configuration_file(&path, Some(search_root))?;
+
+        Ok(EffectiveConfigResult {
+            config,
+            config_file,
+        })
+    }
+
+    /// Formats the document, or only the range within it. The editor's formatting options are not available
+    /// when formatting for a code action, in which case `formatting_options` is `None`
     fn handle_formatting(
         &mut self,
         uri: &Uri,
         range: Option<Range>,
         formatting_options: Option<&FormattingOptions>,
     ) -> Result<Vec<TextEdit>, FormattingError> {
-        let Some(document) = self.documents.get_document(uri) else {
-            return Err(FormattingError::DocumentNotFound);
+        // Documents which have not been opened are read from disk, and so have no version to cache the result against
+        let unopened_document;
+        let (document, is_open) = match self.documents.get_document(uri) {
+            Some(document) => (document, true),
+            None => {
+                unopened_document = read_unopened_document(uri)?;
+                (&unopened_document, false)
+            }
         };
 
-        let range = range.map(|lsp_range| {
-            stylua_lib::Range::from_values(
-                Some(document.offset_at(lsp_range.start).try_into().unwrap()),
-                Some(document.offset_at(lsp_range.end).try_into().unwrap()),
-            )
-        });
-
         if document.language_id() != "lua" && document.language_id() != "luau" {
             return Err(FormattingError::NotLuaDocument);
         }
 
-        let search_root = Some(self.find_config_root(uri));
-        let path = uri.path().as_str().as_ref();
+        if self.is_document_ignored(uri) {
+            return Err(FormattingError::FileIsIgnored);
+        }
 
-        if stylua_ignore::path_is_stylua_ignored(
-            path,
-            self.search_parent_directories,
-            search_root.clone(),
-        )
-        .unwrap_or(false)
+        let version = formatting_options
+            .filter(|_| is_open)
+            .map(|options| (document.version(), options.clone()));
+        if range.is_none()
+            && version.is_some()
+            && self.formatted_document_versions.get(uri) == version.as_ref()
         {
-            return Err(FormattingError::FileIsIgnored);
+            return Ok(Vec::new());
         }
 
+        let range = match range {
+            Some(lsp_range) => {
+                let (Some(start), Some(end)) = (
+                    position_to_offset(document, lsp_range.start, &self.position_encoding),
+                    position_to_offset(document, lsp_range.end, &self.position_encoding),
+                ) else {
+                    return Err(FormattingError::InvalidRange);
+                };
+                Some((start, end))
+            }
+            None => None,
+        };
+
         let contents = document.get_content(None);
 
-        let mut config = self
+        // The editor's formatting options are used in place of the defaults, so that configuration from a
+        // `stylua.toml` or `.editorconfig` file takes precedence over them
+        let mut default_configuration = self
             .config_resolver
-            .load_configuration_with_search_root(path, search_root)
-            .unwrap_or_default();
-
-        if let Some(formatting_options) = formatting_options {
-            config.indent_width = formatting_options
-                .tab_size
-                .try_into()
-                .expect("u32 fits into usize");
-            config.indent_type = if formatting_options.insert_spaces {
-                IndentType::Spaces
+            .default_configuration(Some(document.language_id()));
+        let editor_formatting_options =
+            formatting_options.filter(|_| self.respect_editor_formatting_options);
+        if let Some(formatting_options) = editor_formatting_options {
+            if formatting_options.insert_spaces {
+                default_configuration.indent_type = IndentType::Spaces;
+                default_configuration.indent_width = formatting_options
+                    .tab_size
+                    .try_into()
+                    .expect("u32 fits into usize");
             } else {
-                IndentType::Tabs
-            };
+                // When indenting with tabs, `indent_width` is only a heuristic for the width of a tab
+                // used when wrapping lines, so we keep the configured value
+                default_configuration.indent_type = IndentType::Tabs;
+            }
         }
 
-        let Ok(formatted_contents) = format_code(contents, config, range, OutputVerification::None)
-        else {
-            return Err(FormattingError::StyLuaError);
+        let search_root = Some(self.find_config_root(uri));
+        let path = self.document_path(uri);
+        let mut config = match self.config_resolver.load_configuration_with_default(
+            &path,
+            search_root,
+            default_configuration,
+        ) {
+            Ok(config) => config,
+            Err(err) => {
+                let message = format!(
+                    "could not load configuration for '{}', formatting with the defaults: {:#}",
+                    uri.as_str(),
+                    err
+                );
+                if self.shown_configuration_errors.insert(format!("{:#}", err)) {
+                    self.pending_notifications
+                        .push(lsp_server::Notification::new(
+                            ShowMessage::METHOD.to_string(),
+                            ShowMessageParams {
+                                typ: MessageType::ERROR,
+                                message: message.clone(),
+                            },
+                        ));
+                }
+                log_message(&mut self.pending_notifications, MessageType::ERROR, message);
+                Config::default()
+            }
         };
+        config.syntax = document_syntax(config.syntax, &path, Some(document.language_id()));
 
-        let operations =
-            TextDiff::from_chars(contents.as_bytes(), formatted_contents.as_bytes()).grouped_ops(0);
-        let edits = operations
-            .into_iter()
-            .flat_map(|operations| {
-                operations
-                    .into_iter()
-                    .filter_map(|op| diffop_to_textedit(op, document, &formatted_contents))
-            })
-            .collect();
-        Ok(edits)
-    }
+        // Documents which have not been opened may change on disk, so they are parsed every time
+        let unopened_parsed_document;
+        let parsed_document = if is_open {
+            parse_document(&mut self.parsed_documents, uri, document, config.syntax)
+        } else {
+            unopened_parsed_document = ParsedDocument::parse(document, config.syntax);
+            &unopened_parsed_document
+        };
 
-    fn handle_request(&mut self, request: lsp_server::Request) -> Response {
-        match request.method.as_str() {
-            Formatting::METHOD => {
-                match serde_json::from_value::<DocumentFormattingParams>(request.params) {
-                    Ok(params) => {
-                        match self.handle_formatting(
-                            &params.text_document.uri,
-                            None,
-                            self.respect_editor_formatting_options
-                                .then_some(&params.options),
-                        ) {
-                            Ok(edits) => Response::new_ok(request.id, edits),
-                            Err(FormattingError::StyLuaError)
-                            | Err(FormattingError::NotLuaDocument)
-                            | Err(FormattingError::FileIsIgnored) => {
-                                Response::new_ok(request.id, serde_json::Value::Null)
-                            }
-                            Err(FormattingError::DocumentNotFound) => Response::new_err(
-                                request.id,
-                                ErrorCode::RequestFailed as i32,
-                                format!(
-                                    "no document found for '{}'",
-                                    params.text_document.uri.as_str()
-                                ),
-                            ),
-                        }
-                    }
-                    Err(err) => Response::new_err(
-                        request.id,
-                        lsp_server::ErrorCode::RequestFailed as i32,
-                        err.to_string(),
-                    ),
+        // Only statements entirely within the range are formatted, so a range which starts or ends partway through a
+        // statement is widened to include the whole statement
+        let offset = parsed_document.offset;
+        let byte_range = range.map(|(start, end)| {
+            let (start, end) = expand_to_statements(
+                &parsed_document.ast,
+                start.saturating_sub(offset),
+                end.saturating_sub(offset),
+            );
+            (start + offset, end + offset)
+        });
+        let range = byte_range.map(|(start, end)| {
+            stylua_lib::Range::from_values(Some(start - offset), Some(end - offset))
+        });
+
+        // An empty document is left empty, unless the editor asks for a final newline when formatting the whole document
+        if contents.is_empty() {
+            if range.is_none()
+                && editor_formatting_options.and_then(|options| options.insert_final_newline)
+                    == Some(true)
+            {
+                return Ok(vec![TextEdit {
+                    range: Range::default(),
+                    new_text: match config.line_endings {
+                        LineEndings::Unix => "\n".to_string(),
+                        LineEndings::Windows => "\r\n".to_string(),
+                    },
+                }]);
+            }
+            return Ok(Vec::new());
+        }
+
+        // Formatting a range only formats the statements within it, so the output is reparsed in case the
+        // formatted statements are not valid in the context of the rest of the document
+        let verify_output = match range {
+            Some(_) => OutputVerification::Full,
+            None if self.verify_output => OutputVerification::Full,
+            None => OutputVerification::None,
+        };
+        if !parsed_document.errors.is_empty() {
+            return Err(FormattingError::SyntaxError);
+        }
+        let formatter = self.formatter;
+        let receiver = self.receiver.clone();
+        let format_pool = &self.format_pool;
+        let received_messages = &mut self.received_messages;
+        let mut format = |range, verify_output| {
+            let ast = parsed_document.ast.clone();
+            let format =
+                move || formatter(ast, config, range, verify_output).map(|ast| ast.to_string());
+            match &receiver {
+                Some(receiver) => {
+                    format_while_receiving(uri, receiver, format_pool, received_messages, format)
                 }
+                // A panic in the formatter would otherwise stop the server
+                None => panic::catch_unwind(AssertUnwindSafe(format))
+                    .map_err(|_| FormattingError::StyLuaError),
             }
-            RangeFormatting::METHOD => {
-                match serde_json::from_value::<DocumentRangeFormattingParams>(request.params) {
-                    Ok(params) => {
-                        match self.handle_formatting(
-                            &params.text_document.uri,
-                            Some(params.range),
-                            self.respect_editor_formatting_options
-                                .then_some(&params.options),
-                        ) {
-                            Ok(edits) => Response::new_ok(request.id, edits),
-                            Err(FormattingError::StyLuaError)
-                            | Err(FormattingError::NotLuaDocument)
-                            | Err(FormattingError::FileIsIgnored) => {
-                                Response::new_ok(request.id, serde_json::Value::Null)
-                            }
-                            Err(FormattingError::DocumentNotFound) => Response::new_err(
-                                request.id,
-                                ErrorCode::RequestFailed as i32,
-                                format!(
-                                    "no document found for '{}'",
-                                    params.text_document.uri.as_str()
-                                ),
+        };
+        let formatted_contents = match format(range, verify_output)? {
+            Ok(formatted_contents) => formatted_contents,
+            Err(stylua_lib::Error::VerificationAstError(_)) if range.is_some() => {
+                log::warn!(
+                    "range formatting of '{}' produced invalid code, formatting the whole document instead",
+                    uri.as_str()
+                );
+                format(None, OutputVerification::None)?.map_err(|_| FormattingError::StyLuaError)?
+            }
+            // A verification failure is a bug in the formatter, which is reported to the user while still
+            // returning the unverified output
+            Err(
+                error @ (stylua_lib::Error::VerificationAstError(_)
+                | stylua_lib::Error::VerificationAstDifference),
+            ) if self.verify_output => {
+                self.pending_notifications
+                    .push(lsp_server::Notification::new(
+                        ShowMessage::METHOD.to_string(),
+                        ShowMessageParams {
+                            typ: MessageType::WARNING,
+                            message: format!(
+                                "formatting '{}' failed verification: {}",
+                                uri.as_str(),
+                                error
                             ),
-                        }
-                    }
-                    Err(err) => Response::new_err(
-                        request.id,
-                        lsp_server::ErrorCode::RequestFailed as i32,
-                        err.to_string(),
-                    ),
-                }
+                        },
+                    ));
+                format(range, OutputVerification::None)?
+                    .map_err(|_| FormattingError::StyLuaError)?
             }
-            _ => Response::new_err(
-                request.id,
-                lsp_server::ErrorCode::MethodNotFound as i32,
-                format!("server does not support method '{}'", request.method),
+            Err(_) => return Err(FormattingError::StyLuaError),
+        };
+
+        // Only the whole document is post-processed, as the editor's options would otherwise apply to lines
+        // outside of the range
+        let formatted_contents = match editor_formatting_options {
+            Some(formatting_options) if range.is_none() => apply_editor_formatting_options(
+                formatted_contents,
+                config.syntax,
+                formatting_options,
             ),
+            _ => formatted_contents,
+        };
+        let formatted_contents =
+            match detect_line_ending(contents).filter(|_| self.detect_line_endings) {
+                Some(line_ending) => replace_line_endings(&formatted_contents, line_ending),
+                None => formatted_contents,
+            };
+        // The byte order mark is kept, so that it is not removed by an edit
+        let formatted_contents = contents[..offset].to_string() + &formatted_contents;
+
+        // Some editors reject range formatting responses with edits outside of the range, such as those from
+        // formatting the whole document when the formatted range is not valid
+        let edits = compute_edits(
+            contents,
+            &formatted_contents,
+            byte_range,
+            &self.position_encoding,
+        );
+
+        if let (None, Some(version), true) = (range, version, edits.is_empty()) {
+            self.formatted_document_versions
+                .insert(uri.clone(), version);
         }
+        Ok(edits)
     }
 
-    fn handle_notification(&mut self, notification: lsp_server::Notification) {
-        self.documents
-            .listen(notification.method.as_str(), &notification.params);
+    /// Formats the document for a request from the user, such as `textDocument/formatting`, telling them why the
+    /// document was not formatted through `window/logMessage`. Formatting for code actions and diagnostics, which
+    /// editors request automatically, is not logged
+    fn handle_requested_formatting(
+        &mut self,
+        uri: &Uri,
+        range: Option<Range>,
+        formatting_options: Option<&FormattingOptions>,
+    ) -> Result<Vec<TextEdit>, FormattingError> {
+        let result = self.handle_formatting(uri, range, formatting_options);
+        let message = match &result {
+            Err(FormattingError::SyntaxError) => Some((
+                MessageType::WARNING,
+                format!(
+                    "could not format '{}', as it contains syntax errors",
+                    uri.as_str()
+                ),
+            )),
+            Err(FormattingError::StyLuaError) => Some((
+                MessageType::ERROR,
+                format!("formatting '{}' failed", uri.as_str()),
+            )),
+            Err(FormattingError::FileIsIgnored) => Some((
+                MessageType::INFO,
+                format!("skipping '{}', as it is ignored", uri.as_str()),
+            )),
+            _ => None,
+        };
+        if let Some((typ, message)) = message {
+            log_message(&mut self.pending_notifications, typ, message);
+        }
+        result
     }
-}
 
-#[derive(Serialize, Deserialize, Default)]
-#[serde(default)]
-struct InitializationOptions {
-    respect_editor_formatting_options: Option<bool>,
-}
+    /// Formats each of the ranges within the document, responding with the edits for all of them
+    fn handle_ranges_formatting(
+        &mut self,
+        uri: &Uri,
+        ranges: Vec<Range>,
+        formatting_options: &FormattingOptions,
+    ) -> Result<Vec<TextEdit>, FormattingError> {
+        let mut edits = Vec::new();
+        for range in coalesce_ranges(ranges) {
+            edits.extend(self.handle_requested_formatting(
+                uri,
+                Some(range),
+                Some(formatting_options),
+            )?);
+        }
+        Ok(merge_edits(edits))
+    }
 
-fn main_loop<'a>(
-    connection: Connection,
-    search_parent_directories: bool,
-    config_resolver: &'a mut ConfigResolver<'a>,
-) -> anyhow::Result<()> {
-    let initialize_result = InitializeResult {
-        capabilities: ServerCapabilities {
-            document_range_formatting_provider: Some(OneOf::Left(true)),
-            document_formatting_provider: Some(OneOf::Left(true)),
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                TextDocumentSyncKind::INCREMENTAL,
-            )),
-            ..Default::default()
-        },
-        server_info: Some(ServerInfo {
-            name: env!("CARGO_PKG_NAME").to_string(),
-            version: Some(env!("CARGO_PKG_VERSION").to_string()),
-        }),
-    };
+    /// Formats the innermost statement containing the last character typed before the cursor. No edits are returned
+    /// if the document does not parse, such as when a block is not yet closed, or if the edits would move the cursor
+    /// onto a different line
+    fn handle_on_type_formatting(
+        &mut self,
+        params: &DocumentOnTypeFormattingParams,
+    ) -> Result<Vec<TextEdit>, FormattingError> {
+        // Unformatted documents are only reported when formatting the whole document
+        if self.diagnostics_only {
+            return Ok(Vec::new());
+        }
 
-    let (id, initialize_params) = connection.initialize_start()?;
+        let uri = &params.text_document_position.text_document.uri;
+        let cursor = params.text_document_position.position;
+        let Some(document) = self.documents.get_document(uri) else {
+            return Err(FormattingError::DocumentNotFound);
+        };
 
-    let initialize_params = serde_json::from_value::<InitializeParams>(initialize_params)?;
-    let respect_editor_formatting_options = initialize_params
-        .initialization_options
-        .and_then(|opt| serde_json::from_value::<InitializationOptions>(opt).ok())
-        .and_then(|opt| opt.respect_editor_formatting_options)
-        .unwrap_or_default();
+        if document.language_id() != "lua" && document.language_id() != "luau" {
+            return Err(FormattingError::NotLuaDocument);
+        }
 
-    connection.initialize_finish(id, serde_json::to_value(initialize_result)?)?;
+        let Some(cursor_offset) = position_to_offset(document, cursor, &self.position_encoding)
+        else {
+            return Err(FormattingError::InvalidRange);
+        };
 
-    let mut language_server = LanguageServer::new(
-        initialize_params.workspace_folders.unwrap_or_default(),
-        #[allow(deprecated)]
-        initialize_params.root_uri,
-        search_parent_directories,
-        respect_editor_formatting_options,
-        config_resolver,
-    );
+        let contents = document.get_content(None);
+        // When a new line is typed, the statement ending on the previous line is formatted
+        let typed_offset = match contents[..cursor_offset].trim_end().len() {
+            0 => return Ok(Vec::new()),
+            end => end - 1,
+        };
 
-    for msg in &connection.receiver {
-        match msg {
-            Message::Request(req) => {
-                if connection.handle_shutdown(&req)? {
-                    break;
-                }
+        let path = self.document_path(uri);
+        let syntax = discovery::resolve_config(
+            self.config_resolver,
+            &path,
+            Some(self.find_config_root(uri)),
+            Some(document.language_id()),
+        )
+        .unwrap_or_default()
+        .syntax;
+        let syntax = document_syntax(syntax, &path, Some(document.language_id()));
 
-                let response = language_server.handle_request(req);
-                connection.sender.send(Message::Response(response))?
-            }
-            Message::Response(_) => {}
-            Message::Notification(notification) => {
-                language_server.handle_notification(notification)
-            }
+        let parsed_document = parse_document(&mut self.parsed_documents, uri, document, syntax);
+        if !parsed_document.errors.is_empty() {
+            return Ok(Vec::new());
         }
-    }
-    Ok(())
-}
 
-pub fn run(opt: opt::Opt) -> anyhow::Result<()> {
-    // Load the configuration
-    let opt_for_config_resolver = opt.clone();
-    let mut config_resolver = ConfigResolver::new(&opt_for_config_resolver)?;
+        let mut finder = EnclosingStatementFinder {
+            offset: typed_offset.saturating_sub(parsed_document.offset),
+            range: None,
+        };
+        finder.visit_ast(&parsed_document.ast);
+        let Some((start, end)) = finder.range else {
+            return Ok(Vec::new());
+        };
+        let (start, end) = (start + parsed_document.offset, end + parsed_document.offset);
 
-    let (connection, io_threads) = Connection::stdio();
+        let position =
+            |offset: usize| offset_to_position(document, offset, &self.position_encoding);
+        let range = Range::new(position(start), position(end));
+        let edits = self.handle_formatting(uri, Some(range), Some(&params.options))?;
 
-    main_loop(
-        connection,
-        opt.search_parent_directories,
-        &mut config_resolver,
-    )?;
+        let mut moved_lines = 0;
+        for edit in &edits {
+            if edit.range.start < cursor && cursor < edit.range.end {
+                return Ok(Vec::new());
+            }
 
-    io_threads.join()?;
+            if edit.range.end <= cursor {
+                let removed_lines = edit.range.end.line - edit.range.start.line;
+                let added_lines = edit.new_text.matches('\n').count();
+                moved_lines += added_lines as i64 - i64::from(removed_lines);
+            }
+        }
 
-    Ok(())
-}
+        if moved_lines != 0 {
+            return Ok(Vec::new());
+        }
 
-#[cfg(test)]
-mod tests {
-    use std::cmp::Ordering;
-    use std::convert::TryInto;
-    use std::path::Path;
-    use std::str::FromStr;
+        Ok(edits)
+    }
 
-    use clap::Parser;
-    use crossbeam_channel::Receiver;
+    /// Formats every file within the workspace folders, applying the changes through a `workspace/applyEdit` request.
+    /// Open documents are formatted using their contents in the editor. Files which fail to format, such as those
+    /// which do not parse, are skipped and returned in the result
+    fn handle_format_workspace(
+        &mut self,
+        work_done_token: Option<ProgressToken>,
+    ) -> FormatWorkspaceResult {
+        let roots: Vec<PathBuf> = match self.workspace_folders.as_slice() {
+            [] => self
+                .root_uri
+                .iter()
+                .map(|root_uri| root_uri.path().as_str().into())
+                .collect(),
+            workspace_folders => workspace_folders
+                .iter()
+                .map(|workspace_folder| workspace_folder.uri.path().as_str().into())
+                .collect(),
+        };
+        let files: Vec<(&Path, PathBuf)> = roots
+            .iter()
+            .flat_map(|root| {
+                discovery::find_files(root)
+                    .into_iter()
+                    .map(move |path| (root.as_path(), path))
+            })
+            .collect();
 
-    use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
-    use lsp_types::{
-        notification::{DidOpenTextDocument, Exit, Initialized, Notification as NotificationType},
-        request::{Formatting, Initialize, RangeFormatting, Request as RequestType, Shutdown},
-        DidOpenTextDocumentParams, DocumentFormattingParams, DocumentRangeFormattingParams,
-        FormattingOptions, InitializeParams, OneOf, Position, Range, ServerCapabilities,
-        ServerInfo, TextDocumentIdentifier, TextDocumentItem, TextDocumentSyncCapability,
-        TextDocumentSyncKind, TextEdit, Uri, WorkDoneProgressParams,
-    };
-    use serde::de::DeserializeOwned;
-    use serde_json::to_value;
+        self.report_progress(
+            &work_done_token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: "Formatting workspace".to_string(),
+                percentage: Some(0),
+                ..Default::default()
+            }),
+        );
 
-    use crate::{
-        config::ConfigResolver,
-        lsp::{main_loop, InitializationOptions},
-        opt::Opt,
-    };
+        let mut result = FormatWorkspaceResult::default();
+        // `Uri` caches its parsed components, but its hash never changes
+        #[allow(clippy::mutable_key_type)]
+        let mut changes = HashMap::new();
+        for (index, (root, path)) in files.iter().enumerate() {
+            self.report_progress(
+                &work_done_token,
+                WorkDoneProgress::Report(WorkDoneProgressReport {
+                    message: Some(path.display().to_string()),
+                    percentage: Some((index * 100 / files.len()).try_into().unwrap()),
+                    ..Default::default()
+                }),
+            );
 
-    use assert_fs::prelude::*;
+            let open_document = self
+                .documents
+                .documents()
+                .iter()
+                .find(|(uri, _)| Path::new(uri.path().as_str()) == path);
+            let (uri, contents, language_id) = match open_document {
+                Some((uri, document)) => (
+                    uri.clone(),
+                    document.get_content(None).to_string(),
+                    Some(document.language_id().to_string()),
+                ),
+                None => {
+                    let Some(uri) = path_to_uri(path) else {
+                        log::warn!("could not create a URI for {}", path.display());
+                        continue;
+                    };
+                    match fs::read_to_string(path) {
+                        Ok(contents) => (uri, contents, None),
+                        Err(err) => {
+                            log::warn!("could not read {}: {}", path.display(), err);
+                            result.failed_files.push(uri);
+                            continue;
+                        }
+                    }
+                }
+            };
 
-    macro_rules! construct_tree {
-        ({ $($file_name:literal:$file_contents:literal,)* }) => {{
-            let cwd = assert_fs::TempDir::new().unwrap();
+            let mut config = discovery::resolve_config(
+                self.config_resolver,
+                path,
+                Some(root.to_path_buf()),
+                language_id.as_deref(),
+            )
+            .unwrap_or_default();
+            config.syntax = document_syntax(config.syntax, path, language_id.as_deref());
 
-            $(
-                cwd.child($file_name).write_str($file_contents).unwrap();
-            )*
+            let (byte_order_mark, code) = split_byte_order_mark(&contents);
+            // A panic in the formatter only fails this file, rather than stopping the server
+            let formatted_contents =
+                panic::catch_unwind(|| format_code(code, config, None, OutputVerification::None))
+                    .map_err(|_| "the formatter panicked".to_string())
+                    .and_then(|result| result.map_err(|err| err.to_string()))
+                    .map(|formatted_contents| byte_order_mark.to_string() + &formatted_contents);
+            match formatted_contents {
+                Ok(formatted_contents) if formatted_contents != contents => {
+                    let document = FullTextDocument::new("lua".to_string(), 0, contents);
+                    let end = from_document_position(
+                        &document,
+                        document.position_at(document.content_len()),
+                        &self.position_encoding,
+                    );
+                    changes.insert(
+                        uri.clone(),
+                        vec![TextEdit::new(
+                            Range::new(Position::new(0, 0), end),
+                            formatted_contents,
+                        )],
+                    );
+                    result.formatted_files.push(uri);
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    log::warn!("could not format {}: {}", path.display(), err);
+                    result.failed_files.push(uri);
+                }
+            }
+        }
 
-            cwd
-        }};
-        ({ $($file_name:literal:$file_contents:expr,)* }) => {{
-            let cwd = assert_fs::TempDir::new().unwrap();
+        self.report_progress(
+            &work_done_token,
+            WorkDoneProgress::End(WorkDoneProgressEnd {
+                message: Some(format!(
+                    "formatted {} files, {} failed",
+                    result.formatted_files.len(),
+                    result.failed_files.len()
+                )),
+            }),
+        );
 
-            $(
-                cwd.child($file_name).write_str($file_contents).unwrap();
-            )*
+        if !changes.is_empty() {
+            self.sent_request_count += 1;
+            self.pending_messages
+                .push(Message::Request(lsp_server::Request::new(
+                    lsp_server::RequestId::from(format!(
+                        "{}/{}",
+                        ApplyWorkspaceEdit::METHOD,
+                        self.sent_request_count
+                    )),
+                    ApplyWorkspaceEdit::METHOD.to_string(),
+                    ApplyWorkspaceEditParams {
+                        label: Some("Format workspace".to_string()),
+                        edit: WorkspaceEdit::new(changes),
+                    },
+                )));
+        }
 
-            cwd
-        }};
+        result
     }
 
-    macro_rules! lsp_test {
-        ([$( $arguments:expr ),*], [$( $messages:expr ),*], [$( $tests:expr ),*]) => {
-            let opt = Opt::parse_from(vec!["BINARY_NAME", "--lsp", $($arguments)*]);
-            let mut config_resolver = ConfigResolver::new(&opt).unwrap();
-
-            let (server, client) = Connection::memory();
-            $(
-                client.sender.send($messages).unwrap();
-            )*
+    /// Reports the progress of a request, if the client provided a token to report progress with
+    fn report_progress(&mut self, token: &Option<ProgressToken>, progress: WorkDoneProgress) {
+        if let Some(token) = token {
+            self.pending_messages
+                .push(Message::Notification(lsp_server::Notification::new(
+                    Progress::METHOD.to_string(),
+                    ProgressParams {
+                        token: token.clone(),
+                        value: ProgressParamsValue::WorkDone(progress),
+                    },
+                )));
+        }
+    }
 
-            main_loop(server, false, &mut config_resolver).unwrap();
+    /// Responds to a formatting request with the edits to apply. If only diagnostics should be provided, no edits are
+    /// returned, and instead a diagnostic is published if the document is not formatted
+    fn formatting_response(
+        &mut self,
+        id: lsp_server::RequestId,
+        uri: Uri,
+        edits: Vec<TextEdit>,
+    ) -> Response {
+        if !self.diagnostics_only {
+            return Response::new_ok(id, edits);
+        }
 
-            $(
-                $tests(&client.receiver);
-            )*
+        let diagnostics = match (edits.first(), edits.last()) {
+            (Some(first), Some(last)) => vec![Diagnostic {
+                range: Range::new(first.range.start, last.range.end),
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some(env!("CARGO_PKG_NAME").to_string()),
+                message: "document is not formatted".to_string(),
+                ..Default::default()
+            }],
+            _ => Vec::new(),
         };
-    }
 
-    fn initialize(id: i32, root_path: Option<&Path>) -> Message {
-        Message::Request(Request {
-            id: RequestId::from(id),
-            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
-            params: to_value(InitializeParams {
-                #[allow(deprecated)]
-                root_uri: root_path.map(|path| Uri::from_str(path.to_str().unwrap()).unwrap()),
-                ..Default::default()
-            })
-            .unwrap(),
-        })
+        if edits.is_empty() {
+            self.unformatted_documents.remove(&uri);
+        } else {
+            self.unformatted_documents.insert(uri.clone(), edits);
+        }
+
+        self.pending_notifications
+            .push(lsp_server::Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                PublishDiagnosticsParams::new(uri, diagnostics, None),
+            ));
+
+        Response::new_ok(id, Vec::<TextEdit>::new())
     }
 
-    fn initialize_with_options(id: i32, options: InitializationOptions) -> Message {
-        Message::Request(Request {
-            id: RequestId::from(id),
-            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
-            params: to_value(InitializeParams {
-                initialization_options: Some(to_value(options).unwrap()),
+    /// Provides code actions to format the document, and the selected range if one is provided, which are omitted
+    /// when there is nothing to format. If only diagnostics should be provided, the action instead formats a document
+    /// which has been flagged as unformatted
+    fn handle_code_action(&mut self, params: CodeActionParams) -> Vec<CodeActionOrCommand> {
+        let uri = params.text_document.uri;
+        if self.diagnostics_only {
+            let Some(edits) = self.unformatted_documents.get(&uri) else {
+                return Vec::new();
+            };
+
+            return vec![CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Format document".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(params.context.diagnostics),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(uri, edits.clone())]))),
+                is_preferred: Some(true),
                 ..Default::default()
-            })
-            .unwrap(),
-        })
-    }
+            })];
+        }
 
-    fn initialized() -> Message {
-        Message::Notification(Notification {
-            method: Initialized::METHOD.to_string(),
-            params: serde_json::Value::Null,
-        })
-    }
+        if !self.is_format_trigger_enabled(CodeActionRequest::METHOD) {
+            return Vec::new();
+        }
 
-    fn open_text_document(uri: Uri, text: String) -> Message {
-        Message::Notification(Notification {
-            method: DidOpenTextDocument::METHOD.to_string(),
-            params: to_value(DidOpenTextDocumentParams {
-                text_document: TextDocumentItem {
-                    uri,
-                    language_id: "lua".to_string(),
-                    version: 0,
-                    text,
-                },
-            })
-            .unwrap(),
-        })
-    }
+        let mut ranges = vec![("Format with StyLua", None)];
+        if params.range.start != params.range.end {
+            ranges.push(("Format selection with StyLua", Some(params.range)));
+        }
 
-    fn format_document(id: i32, uri: Uri, options: FormattingOptions) -> Message {
-        Message::Request(Request {
-            id: RequestId::from(id),
-            method: Formatting::METHOD.to_string(),
-            params: to_value(DocumentFormattingParams {
-                text_document: TextDocumentIdentifier { uri },
-                options,
-                work_done_progress_params: WorkDoneProgressParams::default(),
+        ranges
+            .into_iter()
+            .filter_map(|(title, range)| {
+                let edits = self.handle_formatting(&uri, range, None).ok()?;
+                if edits.is_empty() {
+                    return None;
+                }
+
+                Some(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.to_string(),
+                    kind: Some(CodeActionKind::SOURCE),
+                    edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+                    ..Default::default()
+                }))
             })
-            .unwrap(),
-        })
+            .collect()
     }
 
-    fn shutdown(id: i32) -> Message {
-        Message::Request(Request {
-            id: RequestId::from(id),
-            method: Shutdown::METHOD.to_string(),
-            params: serde_json::Value::Null,
-        })
-    }
+    /// Records the changed documents and cancelled requests in the messages which have been received but not yet handled
+    fn update_queued_messages(&mut self, queue: &VecDeque<Message>) {
+        self.documents_with_queued_changes.clear();
+        self.cancelled_requests.clear();
 
-    fn exit() -> Message {
-        Message::Notification(Notification {
-            method: Exit::METHOD.to_string(),
-            params: serde_json::Value::Null,
-        })
-    }
+        for message in queue {
+            let Message::Notification(notification) = message else {
+                continue;
+            };
 
-    fn expect_server_initialized(receiver: &Receiver<Message>, response_id: i32) {
-        match receiver.recv().unwrap() {
-            Message::Response(Response {
-                id,
-                result: Some(result),
-                error: None,
-            }) if id == RequestId::from(response_id)
-                && result
-                    == serde_json::json!({
-                    "capabilities": ServerCapabilities {
-                        document_range_formatting_provider: Some(OneOf::Left(true)),
-                        document_formatting_provider: Some(OneOf::Left(true)),
-                        text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                            TextDocumentSyncKind::INCREMENTAL,
-                        )),
-                        ..Default::default()
-                    },
-                    "serverInfo": Some(ServerInfo {
-                        name: env!("CARGO_PKG_NAME").to_string(),
-                        version: Some(env!("CARGO_PKG_VERSION").to_string()),
-                    }),
-                    }) => {}
-            _ => panic!("assertion failed"),
+            match notification.method.as_str() {
+                DidChangeTextDocument::METHOD => {
+                    if let Some(uri) = changed_document(notification) {
+                        self.documents_with_queued_changes.insert(uri);
+                    }
+                }
+                Cancel::METHOD => {
+                    if let Ok(params) =
+                        serde_json::from_value::<CancelParams>(notification.params.clone())
+                    {
+                        self.cancelled_requests.insert(match params.id {
+                            NumberOrString::Number(id) => id.into(),
+                            NumberOrString::String(id) => id.into(),
+                        });
+                    }
+                }
+                _ => (),
+            }
         }
     }
 
-    fn expect_response<T: DeserializeOwned>(receiver: &Receiver<Message>, response_id: i32) -> T {
-        match receiver.recv().unwrap() {
-            Message::Response(Response {
-                id,
-                result: Some(result),
-                error: None,
-            }) if id == RequestId::from(response_id) => {
-                serde_json::from_value::<T>(result).unwrap()
-            }
-            _ => panic!("assertion failed"),
+    /// Responds with an error to a request which has been cancelled, or whose document will have changed by the time
+    /// the client receives the response
+    fn stale_request_response(&self, request: &lsp_server::Request) -> Option<Response> {
+        if self.cancelled_requests.contains(&request.id) {
+            return Some(Response::new_err(
+                request.id.clone(),
+                ErrorCode::RequestCanceled as i32,
+                "request was cancelled".to_string(),
+            ));
         }
-    }
 
-    fn expect_server_shutdown(receiver: &Receiver<Message>, response_id: i32) {
-        match receiver.recv().unwrap() {
-            Message::Response(Response {
-                id,
-                result: Some(result),
-                error: None,
-            }) if id == RequestId::from(response_id) && result == serde_json::Value::Null => {}
-            _ => panic!("assertion failed"),
+        if !matches!(
+            request.method.as_str(),
+            Formatting::METHOD
+                | RangeFormatting::METHOD
+                | RangesFormatting::METHOD
+                | OnTypeFormatting::METHOD
+                | WillSaveWaitUntil::METHOD
+                | FormatWithCursor::METHOD
+                | CodeActionRequest::METHOD
+        ) {
+            return None;
         }
-    }
 
-    #[test]
-    fn test_lsp_initialize() {
-        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
-        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let uri = serde_json::from_value::<TextDocumentIdentifier>(
+            request.params.get("textDocument")?.clone(),
+        )
+        .ok()?
+        .uri;
+        self.documents_with_queued_changes
+            .contains(&uri)
+            .then(|| content_modified_response(request.id.clone(), &uri))
+    }
 
-        let (server, client) = Connection::memory();
-        client.sender.send(initialize(1, None)).unwrap();
-        client.sender.send(initialized()).unwrap();
-        client.sender.send(shutdown(2)).unwrap();
-        client.sender.send(exit()).unwrap();
+    fn handle_request(&mut self, request: lsp_server::Request) -> Response {
+        if let Some(response) = self.stale_request_response(&request) {
+            return response;
+        }
 
-        main_loop(server, false, &mut config_resolver).unwrap();
+        if matches!(
+            request.method.as_str(),
+            Formatting::METHOD
+                | RangeFormatting::METHOD
+                | RangesFormatting::METHOD
+                | OnTypeFormatting::METHOD
+                | WillSaveWaitUntil::METHOD
+                | FormatWithCursor::METHOD
+        ) && !self.is_format_trigger_enabled(&request.method)
+        {
+            return Response::new_ok(request.id, serde_json::Value::Null);
+        }
 
-        expect_server_initialized(&client.receiver, 1);
-        expect_server_shutdown(&client.receiver, 2);
-        assert!(client.receiver.is_empty());
-    }
+        match request.method.as_str() {
+            Formatting::METHOD => {
+                match serde_json::from_value::<DocumentFormattingParams>(request.params) {
+                    Ok(params) => {
+                        let work_done_token = params.work_done_progress_params.work_done_token;
+                        self.report_progress(
+                            &work_done_token,
+                            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                                title: "Formatting document".to_string(),
+                                ..Default::default()
+                            }),
+                        );
+                        let result = self.handle_requested_formatting(
+                            &params.text_document.uri,
+                            None,
+                            Some(&params.options),
+                        );
+                        self.report_progress(
+                            &work_done_token,
+                            WorkDoneProgress::End(WorkDoneProgressEnd::default()),
+                        );
 
-    fn apply_text_edits_to(text: &str, mut edits: Vec<TextEdit>) -> String {
-        edits.sort_by(|a, b| match a.range.start.line.cmp(&b.range.start.line) {
-            Ordering::Equal => a
-                .range
-                .start
-                .character
-                .cmp(&b.range.start.character)
-                .reverse(),
-            order => order.reverse(),
-        });
-        let mut text = text.to_string();
-        for edit in edits {
-            let start = text
-                .lines()
-                .take(edit.range.start.line.try_into().unwrap())
-                .map(|line| line.len() + '\n'.len_utf8())
-                .sum::<usize>()
-                + <u32 as TryInto<usize>>::try_into(edit.range.start.character).unwrap();
-            let end = text
-                .lines()
-                .take(edit.range.end.line.try_into().unwrap())
-                .map(|line| line.len() + '\n'.len_utf8())
-                .sum::<usize>()
-                + <u32 as TryInto<usize>>::try_into(edit.range.end.character).unwrap();
-            text.replace_range(start..end, &edit.new_text);
+                        match result {
+                            Ok(edits) => self.formatting_response(
+                                request.id,
+                                params.text_document.uri,
+                                edits,
+                            ),
+                            Err(FormattingError::InvalidRange) => Response::new_err(
+                                request.id,
+                                ErrorCode::InternalError as i32,
+                                "no range was requested, but it was invalid".to_string(),
+                            ),
+                            Err(FormattingError::ContentModified) => {
+                                content_modified_response(request.id, &params.text_document.uri)
+                            }
+                            Err(FormattingError::StyLuaError)
+                            | Err(FormattingError::SyntaxError)
+                            | Err(FormattingError::NotLuaDocument)
+                            | Err(FormattingError::FileIsIgnored)
+                            | Err(FormattingError::DocumentNotFound) => {
+                                Response::new_ok(request.id, serde_json::Value::Null)
+                            }
+                        }
+                    }
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            RangeFormatting::METHOD => {
+                match serde_json::from_value::<DocumentRangeFormattingParams>(request.params) {
+                    Ok(params) => {
+                        match self.handle_requested_formatting(
+                            &params.text_document.uri,
+                            Some(params.range),
+                            Some(&params.options),
+                        ) {
+                            Ok(edits) => self.formatting_response(
+                                request.id,
+                                params.text_document.uri,
+                                edits,
+                            ),
+                            Err(FormattingError::InvalidRange) => Response::new_err(
+                                request.id,
+                                ErrorCode::InvalidParams as i32,
+                                format!("range {:?} is not within the document", params.range),
+                            ),
+                            Err(FormattingError::ContentModified) => {
+                                content_modified_response(request.id, &params.text_document.uri)
+                            }
+                            Err(FormattingError::StyLuaError)
+                            | Err(FormattingError::SyntaxError)
+                            | Err(FormattingError::NotLuaDocument)
+                            | Err(FormattingError::FileIsIgnored)
+                            | Err(FormattingError::DocumentNotFound) => {
+                                Response::new_ok(request.id, serde_json::Value::Null)
+                            }
+                        }
+                    }
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            RangesFormatting::METHOD => {
+                match serde_json::from_value::<DocumentRangesFormattingParams>(request.params) {
+                    Ok(params) => {
+                        match self.handle_ranges_formatting(
+                            &params.text_document.uri,
+                            params.ranges.clone(),
+                            &params.options,
+                        ) {
+                            Ok(edits) => self.formatting_response(
+                                request.id,
+                                params.text_document.uri,
+                                edits,
+                            ),
+                            Err(FormattingError::InvalidRange) => Response::new_err(
+                                request.id,
+                                ErrorCode::InvalidParams as i32,
+                                format!(
+                                    "ranges {:?} are not all within the document",
+                                    params.ranges
+                                ),
+                            ),
+                            Err(FormattingError::ContentModified) => {
+                                content_modified_response(request.id, &params.text_document.uri)
+                            }
+                            Err(FormattingError::StyLuaError)
+                            | Err(FormattingError::SyntaxError)
+                            | Err(FormattingError::NotLuaDocument)
+                            | Err(FormattingError::FileIsIgnored)
+                            | Err(FormattingError::DocumentNotFound) => {
+                                Response::new_ok(request.id, serde_json::Value::Null)
+                            }
+                        }
+                    }
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            // The editor's formatting options are not sent before saving, so the document is formatted in the same
+            // way as for a code action
+            WillSaveWaitUntil::METHOD => {
+                match serde_json::from_value::<WillSaveTextDocumentParams>(request.params) {
+                    Ok(params) => {
+                        match self.handle_requested_formatting(
+                            &params.text_document.uri,
+                            None,
+                            None,
+                        ) {
+                            Ok(edits) => self.formatting_response(
+                                request.id,
+                                params.text_document.uri,
+                                edits,
+                            ),
+                            Err(FormattingError::InvalidRange) => Response::new_err(
+                                request.id,
+                                ErrorCode::InternalError as i32,
+                                "no range was requested, but it was invalid".to_string(),
+                            ),
+                            Err(FormattingError::ContentModified) => {
+                                content_modified_response(request.id, &params.text_document.uri)
+                            }
+                            Err(FormattingError::StyLuaError)
+                            | Err(FormattingError::SyntaxError)
+                            | Err(FormattingError::NotLuaDocument)
+                            | Err(FormattingError::FileIsIgnored)
+                            | Err(FormattingError::DocumentNotFound) => {
+                                Response::new_ok(request.id, serde_json::Value::Null)
+                            }
+                        }
+                    }
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            OnTypeFormatting::METHOD => {
+                match serde_json::from_value::<DocumentOnTypeFormattingParams>(request.params) {
+                    Ok(params) => match self.handle_on_type_formatting(&params) {
+                        Ok(edits) => Response::new_ok(request.id, edits),
+                        Err(FormattingError::InvalidRange) => Response::new_err(
+                            request.id,
+                            ErrorCode::InvalidParams as i32,
+                            format!(
+                                "position {:?} is not within the document",
+                                params.text_document_position.position
+                            ),
+                        ),
+                        Err(FormattingError::StyLuaError)
+                        | Err(FormattingError::SyntaxError)
+                        | Err(FormattingError::NotLuaDocument)
+                        | Err(FormattingError::FileIsIgnored) => {
+                            Response::new_ok(request.id, serde_json::Value::Null)
+                        }
+                        Err(FormattingError::DocumentNotFound) => Response::new_err(
+                            request.id,
+                            ErrorCode::RequestFailed as i32,
+                            format!(
+                                "no document found for '{}'",
+                                params.text_document_position.text_document.uri.as_str()
+                            ),
+                        ),
+                        Err(FormattingError::ContentModified) => content_modified_response(
+                            request.id,
+                            &params.text_document_position.text_document.uri,
+                        ),
+                    },
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            ExecuteCommand::METHOD => {
+                match serde_json::from_value::<ExecuteCommandParams>(request.params) {
+                    Ok(params) if params.command == FORMAT_WORKSPACE_COMMAND => {
+                        let result = self.handle_format_workspace(
+                            params.work_done_progress_params.work_done_token,
+                        );
+                        Response::new_ok(request.id, result)
+                    }
+                    Ok(params) => Response::new_err(
+                        request.id,
+                        ErrorCode::InvalidParams as i32,
+                        format!("unknown command '{}'", params.command),
+                    ),
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            // Only diagnostics are provided in place of edits, so the cursor does not move
+            FormatWithCursor::METHOD => {
+                match serde_json::from_value::<FormatWithCursorParams>(request.params) {
+                    Ok(params) => match self.handle_requested_formatting(
+                        &params.text_document.uri,
+                        None,
+                        Some(&params.options),
+                    ) {
+                        Ok(edits) => {
+                            let edits = if self.diagnostics_only {
+                                Vec::new()
+                            } else {
+                                edits
+                            };
+                            let position =
+                                map_position(params.position, &edits, &self.position_encoding);
+                            Response::new_ok(request.id, FormatWithCursorResult { edits, position })
+                        }
+                        Err(FormattingError::InvalidRange) => Response::new_err(
+                            request.id,
+                            ErrorCode::InternalError as i32,
+                            "no range was requested, but it was invalid".to_string(),
+                        ),
+                        Err(FormattingError::ContentModified) => {
+                            content_modified_response(request.id, &params.text_document.uri)
+                        }
+                        Err(FormattingError::StyLuaError)
+                        | Err(FormattingError::SyntaxError)
+                        | Err(FormattingError::NotLuaDocument)
+                        | Err(FormattingError::FileIsIgnored)
+                        | Err(FormattingError::DocumentNotFound) => {
+                            Response::new_ok(request.id, serde_json::Value::Null)
+                        }
+                    },
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            ResolvedConfig::METHOD => {
+                match serde_json::from_value::<TextDocumentIdentifier>(request.params) {
+                    Ok(params) => match self.handle_resolved_config(params) {
+                        Ok(result) => Response::new_ok(request.id, result),
+                        Err(err) => Response::new_err(
+                            request.id,
+                            ErrorCode::RequestFailed as i32,
+                            format!("{:#}", err),
+                        ),
+                    },
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            EffectiveConfig::METHOD => {
+                match serde_json::from_value::<TextDocumentIdentifier>(request.params) {
+                    Ok(params) => match self.handle_effective_config(params) {
+                        Ok(result) => Response::new_ok(request.id, result),
+                        Err(err) => Response::new_err(
+                            request.id,
+                            ErrorCode::RequestFailed as i32,
+                            format!("{:#}", err),
+                        ),
+                    },
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            DocumentDiagnosticRequest::METHOD => {
+                match serde_json::from_value::<DocumentDiagnosticParams>(request.params) {
+                    Ok(params) => {
+                        let uri = params.text_document.uri.clone();
+                        match self.handle_document_diagnostic(params) {
+                            Ok(report) => Response::new_ok(request.id, report),
+                            Err(_) => Response::new_err(
+                                request.id,
+                                ErrorCode::RequestFailed as i32,
+                                format!("no document found for '{}'", uri.as_str()),
+                            ),
+                        }
+                    }
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            CodeActionRequest::METHOD => {
+                match serde_json::from_value::<CodeActionParams>(request.params) {
+                    Ok(params) => Response::new_ok(request.id, self.handle_code_action(params)),
+                    Err(err) => Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::RequestFailed as i32,
+                        err.to_string(),
+                    ),
+                }
+            }
+            _ => Response::new_err(
+                request.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("server does not support method '{}'", request.method),
+            ),
         }
-        text
+    }
+
+    fn handle_notification(&mut self, notification: lsp_server::Notification) {
+        if notification.method == DidChangeTextDocument::METHOD {
+            if let Ok(params) =
+                serde_json::from_value::<DidChangeTextDocumentParams>(notification.params.clone())
+            {
+                let uri = params.text_document.uri.clone();
+                self.handle_did_change(params);
+                // Diagnostics are only published once the last of a burst of changes has been applied
+                if !self.documents_with_queued_changes.contains(&uri) {
+                    self.publish_syntax_diagnostics(uri);
+                }
+                return;
+            }
+        }
+
+        self.documents
+            .listen(notification.method.as_str(), &notification.params);
+
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                serde_json::from_value::<DidOpenTextDocumentParams>(notification.params)
+                    .map(|params| params.text_document.uri)
+            }
+            DidCloseTextDocument::METHOD => serde_json::from_value::<DidCloseTextDocumentParams>(
+                notification.params,
+            )
+            .map(|params| {
+                self.formatted_document_versions
+                    .remove(&params.text_document.uri);
+                self.parsed_documents.remove(&params.text_document.uri);
+                params.text_document.uri
+            }),
+            DidChangeWatchedFiles::METHOD => {
+                if let Ok(params) =
+                    serde_json::from_value::<DidChangeWatchedFilesParams>(notification.params)
+                {
+                    self.handle_did_change_watched_files(params);
+                }
+                return;
+            }
+            DidChangeConfiguration::METHOD => {
+                // The settings use the same schema as the initialization options
+                match serde_json::from_value::<DidChangeConfigurationParams>(notification.params)
+                    .and_then(|params| serde_json::from_value(params.settings))
+                {
+                    Ok(settings) => self.apply_settings(settings),
+                    Err(err) => log_message(
+                        &mut self.pending_notifications,
+                        MessageType::WARNING,
+                        format!("could not parse changed settings: {}", err),
+                    ),
+                }
+                return;
+            }
+            _ => return,
+        };
+        if let Ok(uri) = uri {
+            self.publish_syntax_diagnostics(uri);
+        }
+    }
+
+    /// Invalidates any cached configuration affected by the changed configuration files, so that it is resolved again
+    /// when a document is next formatted. Only configuration within the workspace folder containing the file is
+    /// invalidated
+    fn handle_did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) {
+        for change in params.changes {
+            let path = PathBuf::from(change.uri.path().as_str());
+            let is_config_file = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| {
+                    CONFIG_FILE_NAME.contains(&file_name) || file_name == ".editorconfig"
+                });
+            if !is_config_file {
+                continue;
+            }
+
+            let root = self.find_config_root(&change.uri);
+            let Some(directory) = path
+                .parent()
+                .filter(|directory| directory.starts_with(&root))
+            else {
+                continue;
+            };
+
+            log::info!(
+                "configuration file {} changed, reloading configuration",
+                path.display()
+            );
+            self.config_resolver.invalidate_directory(directory);
+            self.formatted_document_versions.clear();
+            self.shown_configuration_errors.clear();
+        }
+    }
+
+    /// Publishes a diagnostic for each syntax error in the document. Diagnostics are only published for documents
+    /// which fail to parse, or which previously failed to parse, so that the errors are cleared once fixed
+    fn publish_syntax_diagnostics(&mut self, uri: Uri) {
+        let diagnostics = self.syntax_diagnostics(&uri);
+
+        if diagnostics.is_empty() {
+            if !self.documents_with_syntax_errors.remove(&uri) {
+                return;
+            }
+        } else {
+            self.documents_with_syntax_errors.insert(uri.clone());
+        }
+
+        self.pending_notifications
+            .push(lsp_server::Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                PublishDiagnosticsParams::new(uri, diagnostics, None),
+            ));
+    }
+
+    /// Responds to a pull diagnostics request with the syntax errors in the document. If the document parses, but is
+    /// not formatted, a diagnostic is instead provided for the first line which would be changed by formatting
+    fn handle_document_diagnostic(
+        &mut self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult, FormattingError> {
+        let uri = params.text_document.uri;
+        if self.documents.get_document(&uri).is_none() {
+            return Err(FormattingError::DocumentNotFound);
+        }
+
+        let mut items = self.syntax_diagnostics(&uri);
+        if items.is_empty() {
+            if let Some(edit) = self
+                .handle_formatting(&uri, None, None)
+                .ok()
+                .and_then(|edits| edits.into_iter().next())
+            {
+                let line = edit.range.start.line;
+                items.push(Diagnostic {
+                    range: Range::new(Position::new(line, 0), Position::new(line + 1, 0)),
+                    severity: Some(DiagnosticSeverity::INFORMATION),
+                    source: Some(env!("CARGO_PKG_NAME").to_string()),
+                    message: "document is not formatted".to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(DocumentDiagnosticReportResult::Report(
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: None,
+                    items,
+                },
+            }),
+        ))
+    }
+
+    /// Parses the document, returning a diagnostic for each syntax error. Documents which are not Lua, or which are
+    /// ignored by a `.styluaignore` file, have no diagnostics
+    fn syntax_diagnostics(&mut self, uri: &Uri) -> Vec<Diagnostic> {
+        match self.documents.get_document(uri) {
+            Some(document)
+                if (document.language_id() == "lua" || document.language_id() == "luau")
+                    && !self.is_document_ignored(uri) =>
+            {
+                let path = self.document_path(uri);
+                let syntax = discovery::resolve_config(
+                    self.config_resolver,
+                    &path,
+                    Some(self.find_config_root(uri)),
+                    Some(document.language_id()),
+                )
+                .unwrap_or_default()
+                .syntax;
+                let syntax = document_syntax(syntax, &path, Some(document.language_id()));
+
+                let parsed_document =
+                    parse_document(&mut self.parsed_documents, uri, document, syntax);
+                let (offset, errors) = (parsed_document.offset, parsed_document.errors.clone());
+                let position = |position: full_moon::tokenizer::Position| {
+                    offset_to_position(document, offset + position.bytes(), &self.position_encoding)
+                };
+
+                errors
+                    .iter()
+                    .map(|error| {
+                        let (start, end) = error.range();
+                        Diagnostic {
+                            range: Range::new(position(start), position(end)),
+                            severity: Some(DiagnosticSeverity::ERROR),
+                            source: Some(env!("CARGO_PKG_NAME").to_string()),
+                            message: error.error_message().to_string(),
+                            ..Default::default()
+                        }
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Applies the changes to the document one at a time, so that the range of each change can be
+    /// clamped against the contents of the document at the point it is applied
+    fn handle_did_change(&mut self, params: DidChangeTextDocumentParams) {
+        let uri = &params.text_document.uri;
+
+        // Changes are made to the document as of the previous version, so changes which arrive out of order would
+        // be applied to the wrong contents
+        if let Some(document) = self.documents.get_document(uri) {
+            if params.text_document.version <= document.version() {
+                log::warn!(
+                    "skipping changes to '{}' for version {}, as version {} has already been applied",
+                    uri.as_str(),
+                    params.text_document.version,
+                    document.version()
+                );
+                return;
+            }
+        }
+
+        let Some(document) = self.documents.get_document(uri) else {
+            log::warn!("ignoring changes to unknown document '{}'", uri.as_str());
+            return;
+        };
+
+        // Each change is made to the document as left by the changes before it, so the changes are applied to a copy
+        // of the document, which replaces it once every change has been applied. If a change cannot be applied, e.g.
+        // if the client sent it for an out-of-date version of the document, every change is skipped, as the changes
+        // after it would be made to the wrong contents
+        let mut changed_document = FullTextDocument::new(
+            document.language_id().to_string(),
+            document.version(),
+            document.get_content(None).to_string(),
+        );
+        for mut change in params.content_changes {
+            if let Some(range) = change.range.as_mut() {
+                if range.start.line >= changed_document.line_count()
+                    || range.end.line >= changed_document.line_count()
+                {
+                    log::warn!(
+                        "skipping changes to '{}' with range {:?} past the end of the document",
+                        uri.as_str(),
+                        range
+                    );
+                    return;
+                }
+
+                for position in [&mut range.start, &mut range.end] {
+                    *position = clamp_to_line_end(
+                        &changed_document,
+                        to_document_position(&changed_document, *position, &self.position_encoding),
+                    );
+                }
+
+                if range.start > range.end {
+                    log::warn!(
+                        "skipping changes to '{}' with range {:?} ending before it starts",
+                        uri.as_str(),
+                        range
+                    );
+                    return;
+                }
+            }
+
+            changed_document.update(&[change], params.text_document.version);
+        }
+
+        // Any edits to fix the document, or its AST, are out of date once it has changed
+        self.unformatted_documents.remove(uri);
+        self.parsed_documents.remove(uri);
+
+        let params = DidChangeTextDocumentParams {
+            text_document: params.text_document,
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: changed_document.get_content(None).to_string(),
+            }],
+        };
+        match serde_json::to_value(params) {
+            Ok(params) => {
+                self.documents
+                    .listen(DidChangeTextDocument::METHOD, &params);
+            }
+            Err(err) => log_message(
+                &mut self.pending_notifications,
+                MessageType::ERROR,
+                format!("failed to apply changes to a document: {err}"),
+            ),
+        }
+    }
+}
+
+/// Clamps the character of a position to the length of its line, excluding the line ending.
+/// Positions past the end of a line, or between the `\r` and `\n` of a CRLF line ending, refer to the end of
+/// the line, and should not remove or split the line ending.
+fn clamp_to_line_end(document: &FullTextDocument, position: Position) -> Position {
+    if position.line >= document.line_count() {
+        return position;
+    }
+
+    let line_length = line_content(document, position.line).encode_utf16().count();
+
+    Position::new(
+        position.line,
+        position.character.min(line_length.try_into().unwrap()),
+    )
+}
+
+/// The contents of a line of the document, excluding the line ending
+fn line_content(document: &FullTextDocument, line: u32) -> &str {
+    let line_start: usize = document
+        .offset_at(Position::new(line, 0))
+        .try_into()
+        .unwrap();
+    document.get_content(None)[line_start..]
+        .split(['\r', '\n'])
+        .next()
+        .unwrap_or_default()
+}
+
+/// Converts a position sent by the client into the UTF-16 position used by the document.
+/// When positions are encoded in UTF-8, a character within a multi-byte character refers to the start of it
+fn to_document_position(
+    document: &FullTextDocument,
+    position: Position,
+    position_encoding: &PositionEncodingKind,
+) -> Position {
+    if *position_encoding != PositionEncodingKind::UTF8 || position.line >= document.line_count() {
+        return position;
+    }
+
+    let line = line_content(document, position.line);
+    let character: usize = position.character.try_into().unwrap();
+    let mut offset = character.min(line.len());
+    while !line.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    Position::new(
+        position.line,
+        line[..offset].encode_utf16().count().try_into().unwrap(),
+    )
+}
+
+/// Converts a position sent by the client into a byte offset into the document, where the character is a number of
+/// code units in the negotiated encoding. Returns `None` if the position is past the end of its line, or within a character
+fn position_to_offset(
+    document: &FullTextDocument,
+    position: Position,
+    position_encoding: &PositionEncodingKind,
+) -> Option<usize> {
+    if position.line >= document.line_count() {
+        return None;
+    }
+
+    let line_start: usize = document
+        .offset_at(Position::new(position.line, 0))
+        .try_into()
+        .unwrap();
+    let line = line_content(document, position.line);
+    let character: usize = position.character.try_into().unwrap();
+
+    let mut code_units = 0;
+    for (offset, char) in line.char_indices() {
+        match code_units.cmp(&character) {
+            Ordering::Equal => return Some(line_start + offset),
+            Ordering::Greater => return None,
+            Ordering::Less => (),
+        }
+
+        code_units += if *position_encoding == PositionEncodingKind::UTF8 {
+            char.len_utf8()
+        } else {
+            char.len_utf16()
+        };
+    }
+
+    (code_units == character).then_some(line_start + line.len())
+}
+
+/// Moves the position along with the text around it as the edits are applied. The edits must be in order and not
+/// overlap, as they are when computed from a diff. Text inserted at the position is placed before it, and a position
+/// within text which is removed or replaced is placed at the start of its replacement
+fn map_position(
+    position: Position,
+    edits: &[TextEdit],
+    position_encoding: &PositionEncodingKind,
+) -> Position {
+    let text_len = |text: &str| -> u32 {
+        if *position_encoding == PositionEncodingKind::UTF8 {
+            text.len().try_into().unwrap()
+        } else {
+            text.encode_utf16().count().try_into().unwrap()
+        }
+    };
+
+    // The number of lines added before the end of the last edit applied, and the line and character that the end of
+    // the edit moved from and to. Later positions on the same line move by the same number of characters
+    let mut added_lines: i64 = 0;
+    let mut last_edit_end: Option<(Position, u32)> = None;
+    let moved = |position: Position, added_lines: i64, last_edit_end: Option<(Position, u32)>| {
+        let line = (i64::from(position.line) + added_lines).try_into().unwrap();
+        match last_edit_end {
+            Some((old_end, new_character)) if old_end.line == position.line => {
+                Position::new(line, new_character + position.character - old_end.character)
+            }
+            _ => Position::new(line, position.character),
+        }
+    };
+
+    for edit in edits {
+        let start = moved(edit.range.start, added_lines, last_edit_end);
+        if edit.range.start > position {
+            break;
+        }
+        if edit.range.end > position {
+            return start;
+        }
+
+        let new_lines: u32 = edit.new_text.matches('\n').count().try_into().unwrap();
+        let new_end = match edit.new_text.rsplit_once('\n') {
+            Some((_, last_line)) => Position::new(start.line + new_lines, text_len(last_line)),
+            None => Position::new(start.line, start.character + text_len(&edit.new_text)),
+        };
+        added_lines = i64::from(new_end.line) - i64::from(edit.range.end.line);
+        last_edit_end = Some((edit.range.end, new_end.character));
+    }
+
+    moved(position, added_lines, last_edit_end)
+}
+
+/// Converts a UTF-16 position in the document into the encoding used by the client
+fn from_document_position(
+    document: &FullTextDocument,
+    position: Position,
+    position_encoding: &PositionEncodingKind,
+) -> Position {
+    if *position_encoding != PositionEncodingKind::UTF8 || position.line >= document.line_count() {
+        return position;
+    }
+
+    let offset = document.offset_at(position) - document.offset_at(Position::new(position.line, 0));
+    Position::new(position.line, offset)
+}
+
+/// Converts a byte offset into the document into a position in the negotiated encoding.
+/// The document only supports offsets which fit in a `u32`, so larger offsets refer to the end of the document
+fn offset_to_position(
+    document: &FullTextDocument,
+    offset: usize,
+    position_encoding: &PositionEncodingKind,
+) -> Position {
+    let offset: u32 = offset.try_into().unwrap_or(u32::MAX);
+    from_document_position(document, document.position_at(offset), position_encoding)
+}
+
+/// Logs the message, and also sends it to the client through `window/logMessage`, so that it is shown in the editor
+/// without configuring a logger for the server
+fn log_message(
+    pending_notifications: &mut Vec<lsp_server::Notification>,
+    typ: MessageType,
+    message: String,
+) {
+    match typ {
+        MessageType::ERROR => log::error!("{}", message),
+        MessageType::WARNING => log::warn!("{}", message),
+        MessageType::INFO => log::info!("{}", message),
+        _ => log::debug!("{}", message),
+    }
+
+    pending_notifications.push(lsp_server::Notification::new(
+        LogMessage::METHOD.to_string(),
+        LogMessageParams { typ, message },
+    ));
+}
+
+/// The document changed by a `textDocument/didChange` notification
+fn changed_document(notification: &lsp_server::Notification) -> Option<Uri> {
+    if notification.method != DidChangeTextDocument::METHOD {
+        return None;
+    }
+
+    serde_json::from_value::<DidChangeTextDocumentParams>(notification.params.clone())
+        .ok()
+        .map(|params| params.text_document.uri)
+}
+
+/// Responds with an error to a request whose document has changed, so its edits would no longer apply
+fn content_modified_response(id: lsp_server::RequestId, uri: &Uri) -> Response {
+    Response::new_err(
+        id,
+        ErrorCode::ContentModified as i32,
+        format!("'{}' has changed since the request was sent", uri.as_str()),
+    )
+}
+
+/// Formats on the pool's thread while receiving messages from the client, so that a format which takes a long time
+/// is abandoned as soon as a change to the document is received. The received messages are kept to be handled
+/// afterwards. No more messages are received after a shutdown request, as it is followed by the exit notification.
+/// A format which is abandoned before the pool starts it is skipped
+fn format_while_receiving(
+    uri: &Uri,
+    receiver: &Receiver<Message>,
+    format_pool: &ThreadPool,
+    received_messages: &mut VecDeque<Message>,
+    format: impl FnOnce() -> Result<String, stylua_lib::Error> + Send + 'static,
+) -> Result<Result<String, stylua_lib::Error>, FormattingError> {
+    let (sender, result) = crossbeam_channel::bounded(1);
+    let abandoned = Arc::new(AtomicBool::new(false));
+    {
+        let abandoned = abandoned.clone();
+        format_pool.execute(move || {
+            if !abandoned.load(AtomicOrdering::SeqCst) {
+                let _ = sender.send(format());
+            }
+        });
+    }
+
+    let never = crossbeam_channel::never();
+    let mut messages = receiver;
+    loop {
+        crossbeam_channel::select! {
+            recv(result) -> formatted => {
+                // The formatter panicked
+                return formatted.map_err(|_| FormattingError::StyLuaError);
+            }
+            recv(messages) -> message => {
+                let Ok(message) = message else {
+                    messages = &never;
+                    continue;
+                };
+
+                if matches!(&message, Message::Request(request) if request.method == Shutdown::METHOD) {
+                    messages = &never;
+                }
+                let document_changed = matches!(
+                    &message,
+                    Message::Notification(notification) if changed_document(notification).as_ref() == Some(uri)
+                );
+                received_messages.push_back(message);
+                if document_changed {
+                    abandoned.store(true, AtomicOrdering::SeqCst);
+                    return Err(FormattingError::ContentModified);
+                }
+            }
+        }
+    }
+}
+
+/// The line ending used most often in the code, or `None` if it has no line endings. Ties are broken in favour of
+/// `\n`, then `\r\n`
+fn detect_line_ending(code: &str) -> Option<&'static str> {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut bytes = code.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\n' => lf += 1,
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                crlf += 1;
+            }
+            b'\r' => cr += 1,
+            _ => (),
+        }
+    }
+
+    [(lf, "\n"), (crlf, "\r\n"), (cr, "\r")]
+        .iter()
+        .copied()
+        .filter(|(count, _)| *count > 0)
+        .rev()
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, line_ending)| line_ending)
+}
+
+/// Replaces every `\n`, `\r\n` and lone `\r` line ending in the text with the line ending
+fn replace_line_endings(text: &str, line_ending: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    match line_ending {
+        "\n" => text,
+        _ => text.replace('\n', line_ending),
+    }
+}
+
+/// Reads a document which has not been opened from disk, so that it can still be formatted. The language ID is
+/// chosen from the file extension
+fn read_unopened_document(uri: &Uri) -> Result<FullTextDocument, FormattingError> {
+    if !is_file_uri(uri) {
+        return Err(FormattingError::DocumentNotFound);
+    }
+
+    let path = Path::new(uri.path().as_str());
+    let contents = fs::read_to_string(path).map_err(|_| FormattingError::DocumentNotFound)?;
+    let language_id = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("luau") => "luau",
+        Some("lua") => "lua",
+        _ => "",
+    };
+
+    Ok(FullTextDocument::new(language_id.to_string(), 0, contents))
+}
+
+/// The syntax used to parse a document. Luau documents, identified by their language ID or otherwise their file
+/// extension, are parsed as Luau unless another syntax has been configured, as parsing every syntax at once
+/// misreads Luau syntax such as nested generics
+#[cfg(feature = "luau")]
+fn document_syntax(syntax: LuaVersion, path: &Path, language_id: Option<&str>) -> LuaVersion {
+    let is_luau = match language_id {
+        Some(language_id) => language_id == "luau",
+        None => path
+            .extension()
+            .is_some_and(|extension| extension == "luau"),
+    };
+
+    match syntax {
+        LuaVersion::All if is_luau => LuaVersion::Luau,
+        syntax => syntax,
+    }
+}
+
+#[cfg(not(feature = "luau"))]
+fn document_syntax(syntax: LuaVersion, _path: &Path, _language_id: Option<&str>) -> LuaVersion {
+    syntax
+}
+
+/// Whether the URI refers to a local file, rather than an unsaved document or a document on a remote file system.
+/// URIs without a scheme are treated as paths to local files
+fn is_file_uri(uri: &Uri) -> bool {
+    uri.scheme()
+        .is_none_or(|scheme| scheme.as_str().eq_ignore_ascii_case("file"))
+}
+
+/// Creates a `file:` URI for the path, percent-encoding any characters which are not allowed in a URI path
+fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path.to_str()?.replace('\\', "/");
+    let mut uri = String::from("file://");
+    // Windows paths start with a drive letter, rather than a `/`
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte.into())
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    Uri::from_str(&uri).ok()
+}
+
+/// Removes spaces and tabs from the end of the line, keeping the `\r` of a CRLF line ending
+fn trim_line_end(line: &str) -> String {
+    match line.strip_suffix('\r') {
+        Some(line) => line.trim_end_matches([' ', '\t']).to_string() + "\r",
+        None => line.trim_end_matches([' ', '\t']).to_string(),
+    }
+}
+
+/// Removes trailing whitespace from lines which formatting leaves alone, such as those within `-- stylua: ignore`
+/// regions. Whitespace within strings and multi-line comments is kept, as removing it would change their contents
+fn trim_trailing_whitespace(code: &str, syntax: LuaVersion) -> String {
+    let LexerResult::Ok(tokens) = Lexer::new(code, syntax.into()).collect() else {
+        return code.to_string();
+    };
+
+    let mut output = String::with_capacity(code.len());
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token.token_type() {
+            TokenType::Whitespace { characters } => {
+                let mut lines = characters.split('\n').peekable();
+                while let Some(line) = lines.next() {
+                    match lines.peek() {
+                        Some(_) => {
+                            output.push_str(&trim_line_end(line));
+                            output.push('\n');
+                        }
+                        // The whitespace before the next token is kept, unless it is at the end of the code
+                        None => match tokens.peek().map(|token| token.token_type()) {
+                            Some(TokenType::Eof) | None => output.push_str(&trim_line_end(line)),
+                            Some(_) => output.push_str(line),
+                        },
+                    }
+                }
+            }
+            TokenType::SingleLineComment { comment } => output.push_str(
+                &Token::new(TokenType::SingleLineComment {
+                    comment: trim_line_end(comment).into(),
+                })
+                .to_string(),
+            ),
+            _ => output.push_str(&token.to_string()),
+        }
+    }
+
+    output
+}
+
+/// Applies the editor's options for whitespace at the end of lines and of the document to the formatted output.
+/// The output already has a single final line ending, so `trim_final_newlines` is always satisfied
+fn apply_editor_formatting_options(
+    formatted_contents: String,
+    syntax: LuaVersion,
+    formatting_options: &FormattingOptions,
+) -> String {
+    let mut formatted_contents = match formatting_options.trim_trailing_whitespace {
+        Some(true) => trim_trailing_whitespace(&formatted_contents, syntax),
+        _ => formatted_contents,
+    };
+
+    if formatting_options.insert_final_newline == Some(false) {
+        formatted_contents.truncate(formatted_contents.trim_end_matches(['\r', '\n']).len());
+    }
+
+    formatted_contents
+}
+
+/// Finds the byte range of the innermost statement containing the offset
+struct EnclosingStatementFinder {
+    offset: usize,
+    range: Option<(usize, usize)>,
+}
+
+impl EnclosingStatementFinder {
+    fn visit_statement(&mut self, node: &impl Node) {
+        if let Some((start, end)) = node.range() {
+            // Statements are visited before the statements nested within them
+            if (start.bytes()..end.bytes()).contains(&self.offset) {
+                self.range = Some((start.bytes(), end.bytes()));
+            }
+        }
+    }
+}
+
+impl Visitor for EnclosingStatementFinder {
+    fn visit_stmt(&mut self, node: &Stmt) {
+        self.visit_statement(node);
+    }
+
+    fn visit_last_stmt(&mut self, node: &LastStmt) {
+        self.visit_statement(node);
+    }
+}
+
+/// Widens the byte range so that it starts and ends at the boundaries of the innermost statements containing
+/// its start and end. Offsets which are not within a statement, such as those between statements, are unchanged
+fn expand_to_statements(ast: &Ast, start: usize, end: usize) -> (usize, usize) {
+    let enclosing_statement = |offset: usize| {
+        let mut finder = EnclosingStatementFinder {
+            offset,
+            range: None,
+        };
+        finder.visit_ast(ast);
+        finder.range
+    };
+
+    // The end of the range is exclusive, so the statement containing the last selected character is used
+    let start =
+        enclosing_statement(start).map_or(start, |(statement_start, _)| statement_start.min(start));
+    let end = enclosing_statement(end.saturating_sub(1).max(start))
+        .map_or(end, |(_, statement_end)| statement_end.max(end));
+    (start, end)
+}
+
+/// Chooses the encoding of positions, preferring UTF-8 when the client supports it, as documents are stored
+/// in UTF-8. Otherwise, UTF-16 is used, which every client must support
+fn negotiate_position_encoding(capabilities: &ClientCapabilities) -> PositionEncodingKind {
+    match capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref())
+    {
+        Some(encodings) if encodings.contains(&PositionEncodingKind::UTF8) => {
+            PositionEncodingKind::UTF8
+        }
+        _ => PositionEncodingKind::UTF16,
+    }
+}
+
+/// Creates the request registering a watcher for changes to configuration files, if the client supports it.
+/// Files are watched within each workspace folder when the client supports relative patterns, or otherwise everywhere
+fn watch_config_files_request(
+    capabilities: &ClientCapabilities,
+    workspace_folders: &[WorkspaceFolder],
+) -> Option<lsp_server::Request> {
+    let watched_files_capabilities = capabilities
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.did_change_watched_files.as_ref())?;
+    if watched_files_capabilities.dynamic_registration != Some(true) {
+        return None;
+    }
+
+    let watchers = if watched_files_capabilities.relative_pattern_support == Some(true)
+        && !workspace_folders.is_empty()
+    {
+        workspace_folders
+            .iter()
+            .map(|workspace_folder| FileSystemWatcher {
+                glob_pattern: GlobPattern::Relative(RelativePattern {
+                    base_uri: OneOf::Left(workspace_folder.clone()),
+                    pattern: WATCHED_CONFIG_FILES_PATTERN.to_string(),
+                }),
+                kind: None,
+            })
+            .collect()
+    } else {
+        vec![FileSystemWatcher {
+            glob_pattern: GlobPattern::String(WATCHED_CONFIG_FILES_PATTERN.to_string()),
+            kind: None,
+        }]
+    };
+
+    let registration = Registration {
+        id: DidChangeWatchedFiles::METHOD.to_string(),
+        method: DidChangeWatchedFiles::METHOD.to_string(),
+        register_options: Some(
+            serde_json::to_value(DidChangeWatchedFilesRegistrationOptions { watchers })
+                .expect("registration options are serializable"),
+        ),
+    };
+
+    Some(lsp_server::Request::new(
+        lsp_server::RequestId::from(RegisterCapability::METHOD.to_string()),
+        RegisterCapability::METHOD.to_string(),
+        RegistrationParams {
+            registrations: vec![registration],
+        },
+    ))
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct InitializationOptions {
+    respect_editor_formatting_options: Option<bool>,
+    /// Configuration to use in place of the built-in defaults when no configuration file is found
+    config: Option<Config>,
+    /// A configuration file to use for every document, in place of any configuration files found for them.
+    /// Relative paths are resolved from the workspace root
+    config_path: Option<PathBuf>,
+    /// The LSP methods which are allowed to format documents, e.g. `["textDocument/formatting"]`.
+    /// If not provided, formatting is performed for all supported methods
+    format_triggers: Option<Vec<String>>,
+    /// Configuration to use in place of `config` for documents of a particular language ID, when no configuration file is found
+    language_config: HashMap<String, Config>,
+    /// Whether formatting requests should return no edits, and instead publish a diagnostic if the document is not formatted.
+    /// A code action is provided to format the document
+    diagnostics_only: Option<bool>,
+    /// Whether formatting the whole document should reparse the output to check that the code is unchanged, showing a
+    /// warning if it is not. The formatted output is still returned
+    verify_output: Option<bool>,
+    /// Whether formatted documents should keep the line ending used most often in the document, which may be a lone
+    /// `\r`, in place of the configured `line_endings`
+    detect_line_endings: Option<bool>,
+}
+
+fn main_loop<'a>(
+    connection: Connection,
+    config_resolver: &'a mut ConfigResolver<'a>,
+) -> anyhow::Result<()> {
+    let mut initialize_result = InitializeResult {
+        capabilities: ServerCapabilities {
+            document_formatting_provider: Some(OneOf::Right(DocumentFormattingOptions {
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: Some(true),
+                },
+            })),
+            document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: "\n".to_string(),
+                more_trigger_character: Some(vec!["d".to_string(), "}".to_string()]),
+            }),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![FORMAT_WORKSPACE_COMMAND.to_string()],
+                ..Default::default()
+            }),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                identifier: Some(env!("CARGO_PKG_NAME").to_string()),
+                ..Default::default()
+            })),
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    will_save_wait_until: Some(true),
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        },
+        server_info: Some(ServerInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }),
+    };
+
+    let (id, initialize_params) = connection.initialize_start()?;
+
+    let initialize_params = serde_json::from_value::<InitializeParams>(initialize_params)?;
+    let initialization_options = initialize_params
+        .initialization_options
+        .map(serde_json::from_value::<InitializationOptions>)
+        .transpose();
+
+    let position_encoding = negotiate_position_encoding(&initialize_params.capabilities);
+    initialize_result.capabilities.position_encoding = Some(position_encoding.clone());
+
+    // Editors run the server for every project, so personal configuration is used for projects without their own
+    config_resolver.enable_global_configuration();
+
+    // `lsp_types` does not yet support `rangesSupport`, so it is added to the serialized capabilities
+    let mut initialize_result = serde_json::to_value(initialize_result)?;
+    initialize_result["capabilities"]["documentRangeFormattingProvider"] =
+        serde_json::json!({ "rangesSupport": true });
+
+    connection.initialize_finish(id, initialize_result)?;
+
+    let workspace_folders = initialize_params.workspace_folders.unwrap_or_default();
+    if let Some(request) =
+        watch_config_files_request(&initialize_params.capabilities, &workspace_folders)
+    {
+        connection.sender.send(Message::Request(request))?;
+    }
+
+    let mut language_server = LanguageServer::new(
+        workspace_folders,
+        #[allow(deprecated)]
+        initialize_params.root_uri,
+        position_encoding,
+        config_resolver,
+    );
+    // If the initialization options cannot be parsed, such as when one is mistyped, none of them can be used
+    match initialization_options {
+        Ok(initialization_options) => {
+            language_server.apply_settings(initialization_options.unwrap_or_default())
+        }
+        Err(err) => log_message(
+            &mut language_server.pending_notifications,
+            MessageType::WARNING,
+            format!("could not parse initialization options: {}", err),
+        ),
+    }
+    for notification in language_server.pending_notifications.drain(..) {
+        connection
+            .sender
+            .send(Message::Notification(notification))?;
+    }
+
+    // Every message which has been received is queued before the next message is handled, so that work made redundant
+    // by a later message, such as formatting a document which has since changed, can be skipped
+    let mut queue = VecDeque::new();
+    loop {
+        // Messages are not queued past a shutdown request, as the exit notification following it is received when
+        // the request is handled
+        let shutdown_queued = |queue: &VecDeque<Message>| matches!(queue.back(), Some(Message::Request(request)) if request.method == Shutdown::METHOD);
+        while !shutdown_queued(&queue) {
+            match connection.receiver.try_recv() {
+                Ok(msg) => queue.push_back(msg),
+                Err(_) => break,
+            }
+        }
+        let msg = match queue.pop_front() {
+            Some(msg) => msg,
+            None => match connection.receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+        };
+        language_server.update_queued_messages(&queue);
+        language_server.receiver = (!shutdown_queued(&queue)).then(|| connection.receiver.clone());
+
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+
+                let response = language_server.handle_request(req);
+                for message in language_server.pending_messages.drain(..) {
+                    connection.sender.send(message)?;
+                }
+                connection.sender.send(Message::Response(response))?;
+
+                for notification in language_server.pending_notifications.drain(..) {
+                    connection
+                        .sender
+                        .send(Message::Notification(notification))?;
+                }
+            }
+            Message::Response(_) => {}
+            Message::Notification(notification) => {
+                language_server.handle_notification(notification);
+
+                for notification in language_server.pending_notifications.drain(..) {
+                    connection
+                        .sender
+                        .send(Message::Notification(notification))?;
+                }
+            }
+        }
+        queue.extend(language_server.received_messages.drain(..));
+    }
+    Ok(())
+}
+
+pub fn run(opt: opt::Opt) -> anyhow::Result<()> {
+    // Load the configuration
+    let opt_for_config_resolver = opt.clone();
+    let mut config_resolver = ConfigResolver::new(&opt_for_config_resolver)?;
+
+    let (connection, io_threads) = match &opt.lsp_listen {
+        Some(address) => {
+            log::info!("listening for a language client on {}", address);
+            Connection::listen(address)
+                .with_context(|| format!("could not listen on {}", address))?
+        }
+        None => Connection::stdio(),
+    };
+
+    main_loop(connection, &mut config_resolver)?;
+
+    io_threads.join()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+    use std::path::Path;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    use clap::Parser;
+    use crossbeam_channel::Receiver;
+    use lsp_textdocument::FullTextDocument;
+    use proptest::{collection::vec, prop_assert_eq, proptest};
+
+    use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+    use lsp_types::{
+        notification::{
+            Cancel, DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles,
+            DidOpenTextDocument, Exit, Initialized, LogMessage, Notification as NotificationType,
+            Progress, PublishDiagnostics, ShowMessage,
+        },
+        request::{
+            ApplyWorkspaceEdit, CodeActionRequest, DocumentDiagnosticRequest, ExecuteCommand,
+            Formatting, Initialize, OnTypeFormatting, RangeFormatting, RegisterCapability,
+            Request as RequestType, Shutdown, WillSaveWaitUntil,
+        },
+        ApplyWorkspaceEditParams, CancelParams, ClientCapabilities, CodeAction, CodeActionKind,
+        CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, Diagnostic,
+        DiagnosticOptions, DiagnosticServerCapabilities, DiagnosticSeverity,
+        DidChangeConfigurationParams, DidChangeTextDocumentParams,
+        DidChangeWatchedFilesClientCapabilities, DidChangeWatchedFilesParams,
+        DidChangeWatchedFilesRegistrationOptions, DidOpenTextDocumentParams,
+        DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportResult,
+        DocumentFormattingOptions, DocumentFormattingParams, DocumentOnTypeFormattingOptions,
+        DocumentOnTypeFormattingParams, DocumentRangeFormattingParams, ExecuteCommandOptions,
+        ExecuteCommandParams, FileChangeType, FileEvent, FileSystemWatcher, FormattingOptions,
+        GeneralClientCapabilities, GlobPattern, InitializeParams, InitializeResult,
+        LogMessageParams, MessageType, NumberOrString, OneOf, Position, PositionEncodingKind,
+        ProgressParams, ProgressParamsValue, ProgressToken, PublishDiagnosticsParams, Range,
+        RegistrationParams, RelativePattern, ServerCapabilities, ServerInfo, ShowMessageParams,
+        TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, TextDocumentSaveReason, TextDocumentSyncCapability,
+        TextDocumentSyncKind, TextDocumentSyncOptions, TextEdit, Uri,
+        VersionedTextDocumentIdentifier, WillSaveTextDocumentParams, WorkDoneProgress,
+        WorkDoneProgressOptions, WorkDoneProgressParams, WorkspaceClientCapabilities,
+        WorkspaceEdit, WorkspaceFolder,
+    };
+    use serde::de::DeserializeOwned;
+    use serde_json::to_value;
+    use stylua_lib::{
+        format_ast, Config, IndentType, LuaVersion, OutputVerification, QuoteStyle,
+        SortRequiresConfig,
+    };
+
+    use crate::{
+        config::ConfigResolver,
+        lsp::{
+            compute_edits, main_loop, path_to_uri, position_to_offset, run, EffectiveConfig,
+            EffectiveConfigResult, FormatWithCursor, FormatWithCursorParams,
+            FormatWithCursorResult, FormatWorkspaceResult, FormattingError, InitializationOptions,
+            LanguageServer, RangesFormatting, ResolvedConfig, ResolvedConfigResult,
+        },
+        opt::Opt,
+    };
+
+    use assert_fs::prelude::*;
+
+    macro_rules! construct_tree {
+        ({ $($file_name:literal:$file_contents:literal,)* }) => {{
+            let cwd = assert_fs::TempDir::new().unwrap();
+
+            $(
+                cwd.child($file_name).write_str($file_contents).unwrap();
+            )*
+
+            cwd
+        }};
+        ({ $($file_name:literal:$file_contents:expr,)* }) => {{
+            let cwd = assert_fs::TempDir::new().unwrap();
+
+            $(
+                cwd.child($file_name).write_str($file_contents).unwrap();
+            )*
+
+            cwd
+        }};
+    }
+
+    macro_rules! lsp_test {
+        ([$( $arguments:expr ),*], [$( $messages:expr ),*], [$( $tests:expr ),*]) => {
+            let opt = Opt::parse_from(vec!["BINARY_NAME", "--lsp" $(, $arguments)*]);
+            let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+            let (server, client) = Connection::memory();
+            $(
+                client.sender.send($messages).unwrap();
+            )*
+
+            main_loop(server, &mut config_resolver).unwrap();
+
+            $(
+                $tests(&client.receiver);
+            )*
+        };
+    }
+
+    fn initialize(id: i32, root_path: Option<&Path>) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+            params: to_value(InitializeParams {
+                #[allow(deprecated)]
+                root_uri: root_path.map(|path| Uri::from_str(path.to_str().unwrap()).unwrap()),
+                ..Default::default()
+            })
+            .unwrap(),
+        })
+    }
+
+    fn initialize_with_options(id: i32, options: InitializationOptions) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+            params: to_value(InitializeParams {
+                initialization_options: Some(to_value(options).unwrap()),
+                ..Default::default()
+            })
+            .unwrap(),
+        })
+    }
+
+    fn initialize_with_root_and_options(
+        id: i32,
+        root_path: &Path,
+        options: InitializationOptions,
+    ) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+            params: to_value(InitializeParams {
+                #[allow(deprecated)]
+                root_uri: Some(Uri::from_str(root_path.to_str().unwrap()).unwrap()),
+                initialization_options: Some(to_value(options).unwrap()),
+                ..Default::default()
+            })
+            .unwrap(),
+        })
+    }
+
+    fn initialize_with_position_encodings(
+        id: i32,
+        position_encodings: Vec<PositionEncodingKind>,
+    ) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+            params: to_value(InitializeParams {
+                capabilities: ClientCapabilities {
+                    general: Some(GeneralClientCapabilities {
+                        position_encodings: Some(position_encodings),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap(),
+        })
+    }
+
+    fn initialized() -> Message {
+        Message::Notification(Notification {
+            method: Initialized::METHOD.to_string(),
+            params: serde_json::Value::Null,
+        })
+    }
+
+    fn change_configuration(settings: InitializationOptions) -> Message {
+        Message::Notification(Notification {
+            method: DidChangeConfiguration::METHOD.to_string(),
+            params: to_value(DidChangeConfigurationParams {
+                settings: to_value(settings).unwrap(),
+            })
+            .unwrap(),
+        })
+    }
+
+    fn open_text_document(uri: Uri, text: String) -> Message {
+        open_text_document_with_language_id(uri, "lua", text)
+    }
+
+    fn open_text_document_with_language_id(uri: Uri, language_id: &str, text: String) -> Message {
+        Message::Notification(Notification {
+            method: DidOpenTextDocument::METHOD.to_string(),
+            params: to_value(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri,
+                    language_id: language_id.to_string(),
+                    version: 0,
+                    text,
+                },
+            })
+            .unwrap(),
+        })
+    }
+
+    fn change_text_document(
+        uri: Uri,
+        version: i32,
+        range: Range,
+        text: String,
+    ) -> lsp_server::Notification {
+        Notification {
+            method: DidChangeTextDocument::METHOD.to_string(),
+            params: to_value(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier { uri, version },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(range),
+                    range_length: None,
+                    text,
+                }],
+            })
+            .unwrap(),
+        }
+    }
+
+    fn format_document(id: i32, uri: Uri, options: FormattingOptions) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: Formatting::METHOD.to_string(),
+            params: to_value(DocumentFormattingParams {
+                text_document: TextDocumentIdentifier { uri },
+                options,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .unwrap(),
+        })
+    }
+
+    fn shutdown(id: i32) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: Shutdown::METHOD.to_string(),
+            params: serde_json::Value::Null,
+        })
+    }
+
+    fn exit() -> Message {
+        Message::Notification(Notification {
+            method: Exit::METHOD.to_string(),
+            params: serde_json::Value::Null,
+        })
+    }
+
+    fn expect_server_initialized(receiver: &Receiver<Message>, response_id: i32) {
+        let mut expected = serde_json::json!({
+                    "capabilities": ServerCapabilities {
+                        document_formatting_provider: Some(OneOf::Right(DocumentFormattingOptions {
+                            work_done_progress_options: WorkDoneProgressOptions {
+                                work_done_progress: Some(true),
+                            },
+                        })),
+                        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                            first_trigger_character: "\n".to_string(),
+                            more_trigger_character: Some(vec!["d".to_string(), "}".to_string()]),
+                        }),
+                        execute_command_provider: Some(ExecuteCommandOptions {
+                            commands: vec!["stylua.formatWorkspace".to_string()],
+                            ..Default::default()
+                        }),
+                        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                            DiagnosticOptions {
+                                identifier: Some("stylua".to_string()),
+                                ..Default::default()
+                            }
+                        )),
+                        text_document_sync: Some(TextDocumentSyncCapability::Options(
+                            TextDocumentSyncOptions {
+                                open_close: Some(true),
+                                change: Some(TextDocumentSyncKind::INCREMENTAL),
+                                will_save_wait_until: Some(true),
+                                ..Default::default()
+                            },
+                        )),
+                        position_encoding: Some(PositionEncodingKind::UTF16),
+                        ..Default::default()
+                    },
+                    "serverInfo": Some(ServerInfo {
+                        name: env!("CARGO_PKG_NAME").to_string(),
+                        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    }),
+        });
+        expected["capabilities"]["documentRangeFormattingProvider"] =
+            serde_json::json!({ "rangesSupport": true });
+
+        match receiver.recv().unwrap() {
+            Message::Response(Response {
+                id,
+                result: Some(result),
+                error: None,
+            }) if id == RequestId::from(response_id) && result == expected => {}
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    fn expect_response<T: DeserializeOwned>(receiver: &Receiver<Message>, response_id: i32) -> T {
+        match receiver.recv().unwrap() {
+            Message::Response(Response {
+                id,
+                result: Some(result),
+                error: None,
+            }) if id == RequestId::from(response_id) => {
+                serde_json::from_value::<T>(result).unwrap()
+            }
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    fn expect_log_message(receiver: &Receiver<Message>, typ: MessageType) -> String {
+        match receiver.recv().unwrap() {
+            Message::Notification(Notification { method, params })
+                if method == LogMessage::METHOD =>
+            {
+                let params: LogMessageParams = serde_json::from_value(params).unwrap();
+                assert_eq!(params.typ, typ);
+                params.message
+            }
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    fn expect_show_message(receiver: &Receiver<Message>, typ: MessageType) -> String {
+        match receiver.recv().unwrap() {
+            Message::Notification(Notification { method, params })
+                if method == ShowMessage::METHOD =>
+            {
+                let params: ShowMessageParams = serde_json::from_value(params).unwrap();
+                assert_eq!(params.typ, typ);
+                params.message
+            }
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    fn expect_server_shutdown(receiver: &Receiver<Message>, response_id: i32) {
+        match receiver.recv().unwrap() {
+            Message::Response(Response {
+                id,
+                result: Some(result),
+                error: None,
+            }) if id == RequestId::from(response_id) && result == serde_json::Value::Null => {}
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    #[test]
+    fn test_lsp_initialize() {
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client.sender.send(shutdown(2)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+        expect_server_shutdown(&client.receiver, 2);
+        assert!(client.receiver.is_empty());
+    }
+
+    fn apply_text_edits_to(text: &str, mut edits: Vec<TextEdit>) -> String {
+        edits.sort_by(|a, b| match a.range.start.line.cmp(&b.range.start.line) {
+            Ordering::Equal => a
+                .range
+                .start
+                .character
+                .cmp(&b.range.start.character)
+                .reverse(),
+            order => order.reverse(),
+        });
+        let mut text = text.to_string();
+        for edit in edits {
+            let start = text
+                .lines()
+                .take(edit.range.start.line.try_into().unwrap())
+                .map(|line| line.len() + '\n'.len_utf8())
+                .sum::<usize>()
+                + <u32 as TryInto<usize>>::try_into(edit.range.start.character).unwrap();
+            let end = text
+                .lines()
+                .take(edit.range.end.line.try_into().unwrap())
+                .map(|line| line.len() + '\n'.len_utf8())
+                .sum::<usize>()
+                + <u32 as TryInto<usize>>::try_into(edit.range.end.character).unwrap();
+            text.replace_range(start..end, &edit.new_text);
+        }
+        text
+    }
+
+    proptest! {
+        #[test]
+        fn test_compute_edits_changes_document_into_formatted_contents(
+            lines in vec("[a-z é😀]{0,8}", 0..10),
+            changes in vec((0..10usize, 0..3u8, "[a-z é😀]{0,8}"), 0..10),
+        ) {
+            let mut formatted_lines = lines.clone();
+            for (index, kind, line) in changes {
+                let index = index.min(formatted_lines.len());
+                match kind {
+                    0 => formatted_lines.insert(index, line),
+                    _ if index == formatted_lines.len() => (),
+                    1 => {
+                        formatted_lines.remove(index);
+                    }
+                    _ => formatted_lines[index] = line,
+                }
+            }
+            let contents = lines.join("\n");
+            let formatted_contents = formatted_lines.join("\n");
+
+            // Characters are bytes, as they are when edits are applied
+            let edits = compute_edits(
+                &contents,
+                &formatted_contents,
+                None,
+                &PositionEncodingKind::UTF8,
+            );
+            prop_assert_eq!(apply_text_edits_to(&contents, edits), formatted_contents);
+        }
+    }
+
+    #[test]
+    fn test_lsp_detects_line_endings() {
+        let cr_uri = Uri::from_str("file:///home/documents/cr.lua").unwrap();
+        let cr_contents = "local  x = 1\rlocal y  = 2\r";
+        let mixed_uri = Uri::from_str("file:///home/documents/mixed.lua").unwrap();
+        let mixed_contents = "local  x = 1\r\nlocal y  = 2\nlocal z = 3\r\n";
+
+        // The positions of the edits are separated by lone `\r` line endings, which the document understands
+        let apply_edits = |contents: &str, mut edits: Vec<TextEdit>| {
+            edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+            let changes: Vec<_> = edits
+                .into_iter()
+                .map(|edit| TextDocumentContentChangeEvent {
+                    range: Some(edit.range),
+                    range_length: None,
+                    text: edit.new_text,
+                })
+                .collect();
+            let mut document = FullTextDocument::new("lua".to_string(), 0, contents.to_string());
+            document.update(&changes, 1);
+            document.get_content(None).to_string()
+        };
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        detect_line_endings: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(cr_uri.clone(), cr_contents.to_string()),
+                open_text_document(mixed_uri.clone(), mixed_contents.to_string()),
+                format_document(2, cr_uri, FormattingOptions::default()),
+                format_document(3, mixed_uri, FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert_eq!(
+                        apply_edits(cr_contents, edits),
+                        "local x = 1\rlocal y = 2\r"
+                    );
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    assert_eq!(
+                        apply_edits(mixed_contents, edits),
+                        "local x = 1\r\nlocal y = 2\r\nlocal z = 3\r\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_ranged_change_to_crlf_document() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\r\nlocal  y  =  2\r\nlocal  z  =  3\r\n";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            1,
+            Range::new(Position::new(1, 7), Position::new(1, 8)),
+            "value".to_string(),
+        ));
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            2,
+            Range::new(Position::new(1, 18), Position::new(2, 0)),
+            "\r\n-- comment\r\n".to_string(),
+        ));
+        // Positions past the end of the line, or within the CRLF line ending, refer to the end of the line
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            3,
+            Range::new(Position::new(0, 14), Position::new(0, 100)),
+            " -- x".to_string(),
+        ));
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            4,
+            Range::new(Position::new(3, 15), Position::new(3, 15)),
+            " -- z".to_string(),
+        ));
+
+        assert_eq!(
+            language_server
+                .documents
+                .get_document_content(&uri, None)
+                .unwrap(),
+            "local  x  =  1 -- x\r\nlocal  value  =  2\r\n-- comment\r\nlocal  z  =  3 -- z\r\n"
+        );
+    }
+
+    #[test]
+    fn test_lsp_out_of_order_changes_are_skipped() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) =
+            open_text_document(uri.clone(), "local x = 1\n".to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            2,
+            Range::new(Position::new(0, 6), Position::new(0, 7)),
+            "value".to_string(),
+        ));
+        // The change for version 1 is made to the original contents, so applying it after version 2 would
+        // replace part of `value`
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            1,
+            Range::new(Position::new(0, 6), Position::new(0, 7)),
+            "y".to_string(),
+        ));
+        language_server.handle_notification(Notification {
+            method: DidChangeTextDocument::METHOD.to_string(),
+            params: to_value(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "local z = 1\n".to_string(),
+                }],
+            })
+            .unwrap(),
+        });
+
+        assert_eq!(
+            language_server
+                .documents
+                .get_document_content(&uri, None)
+                .unwrap(),
+            "local value = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_lsp_batched_changes_are_applied_in_order() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) =
+            open_text_document(uri.clone(), "local x = 1\n".to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let change = |version: i32, changes: Vec<(Range, &str)>| Notification {
+            method: DidChangeTextDocument::METHOD.to_string(),
+            params: to_value(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version,
+                },
+                content_changes: changes
+                    .into_iter()
+                    .map(|(range, text)| TextDocumentContentChangeEvent {
+                        range: Some(range),
+                        range_length: None,
+                        text: text.to_string(),
+                    })
+                    .collect(),
+            })
+            .unwrap(),
+        };
+
+        // The second change is to a line added by the first
+        language_server.handle_notification(change(
+            1,
+            vec![
+                (
+                    Range::new(Position::new(1, 0), Position::new(1, 0)),
+                    "local y = 2\n",
+                ),
+                (Range::new(Position::new(1, 6), Position::new(1, 7)), "z"),
+            ],
+        ));
+        assert_eq!(
+            language_server.documents.get_document_content(&uri, None),
+            Some("local x = 1\nlocal z = 2\n")
+        );
+
+        // No changes are made when a change cannot be applied, as later changes would be made to the wrong contents
+        language_server.handle_notification(change(
+            2,
+            vec![
+                (Range::new(Position::new(0, 6), Position::new(0, 7)), "a"),
+                (Range::new(Position::new(5, 0), Position::new(5, 0)), "b"),
+                (Range::new(Position::new(1, 6), Position::new(1, 7)), "c"),
+            ],
+        ));
+        assert_eq!(
+            language_server.documents.get_document_content(&uri, None),
+            Some("local x = 1\nlocal z = 2\n")
+        );
+        assert_eq!(
+            language_server
+                .documents
+                .get_document(&uri)
+                .map(FullTextDocument::version),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_lsp_formatting_is_skipped_for_documents_with_queued_changes() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let replace_contents = |version: i32, text: &str| {
+            Message::Notification(Notification {
+                method: DidChangeTextDocument::METHOD.to_string(),
+                params: to_value(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version,
+                    },
+                    content_changes: vec![TextDocumentContentChangeEvent {
+                        range: None,
+                        range_length: None,
+                        text: text.to_string(),
+                    }],
+                })
+                .unwrap(),
+            })
+        };
+        let expect_content_modified =
+            |receiver: &Receiver<Message>, response_id: i32| match receiver.recv().unwrap() {
+                Message::Response(Response {
+                    id,
+                    result: None,
+                    error: Some(error),
+                }) if id == RequestId::from(response_id) => {
+                    assert_eq!(error.code, ErrorCode::ContentModified as i32);
+                }
+                _ => panic!("assertion failed"),
+            };
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), "local x = 1\n".to_string()),
+                // The syntax errors in the first two changes are not published, as they are fixed by the last change
+                replace_contents(1, "local  a  =  ("),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                replace_contents(2, "local  b  =  ("),
+                format_document(3, uri.clone(), FormattingOptions::default()),
+                replace_contents(3, "local  c  =  3"),
+                format_document(4, uri.clone(), FormattingOptions::default()),
+                shutdown(5),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| expect_content_modified(receiver, 2),
+                |receiver| expect_content_modified(receiver, 3),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 4);
+                    let formatted = apply_text_edits_to("local  c  =  3", edits);
+                    assert_eq!(formatted, "local c = 3\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_cancelled_request_is_not_handled() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), "local  x  =  1".to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                Message::Notification(Notification {
+                    method: Cancel::METHOD.to_string(),
+                    params: to_value(CancelParams {
+                        id: NumberOrString::Number(2),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Response(Response {
+                        id,
+                        result: None,
+                        error: Some(error),
+                    }) if id == RequestId::from(2) => {
+                        assert_eq!(error.code, ErrorCode::RequestCanceled as i32);
+                    }
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    fn document_diagnostic(id: i32, uri: Uri) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: DocumentDiagnosticRequest::METHOD.to_string(),
+            params: to_value(DocumentDiagnosticParams {
+                text_document: TextDocumentIdentifier { uri },
+                identifier: None,
+                previous_result_id: None,
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap(),
+        })
+    }
+
+    fn expect_diagnostic_report(receiver: &Receiver<Message>, response_id: i32) -> Vec<Diagnostic> {
+        match expect_response(receiver, response_id) {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                report.full_document_diagnostic_report.items
+            }
+            _ => panic!("assertion failed"),
+        }
+    }
+
+    #[test]
+    fn test_lsp_document_diagnostic() {
+        let formatted_uri = Uri::from_str("file:///home/documents/formatted.lua").unwrap();
+        let unformatted_uri = Uri::from_str("file:///home/documents/unformatted.lua").unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(formatted_uri.clone(), "local x = 1\n".to_string()),
+                open_text_document(
+                    unformatted_uri.clone(),
+                    "local x = 1\nlocal  y  =  2\n".to_string()
+                ),
+                document_diagnostic(2, formatted_uri),
+                document_diagnostic(3, unformatted_uri),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let diagnostics = expect_diagnostic_report(receiver, 2);
+                    assert!(diagnostics.is_empty());
+                },
+                |receiver| {
+                    let diagnostics = expect_diagnostic_report(receiver, 3);
+                    let [diagnostic] = diagnostics.as_slice() else {
+                        panic!("assertion failed")
+                    };
+                    assert_eq!(
+                        diagnostic.range,
+                        Range::new(Position::new(1, 0), Position::new(2, 0))
+                    );
+                    assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::INFORMATION));
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_document_diagnostic_syntax_error() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), "local  x  =".to_string()),
+                document_diagnostic(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                // The syntax error is also published when the document is opened
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Notification(notification)
+                        if notification.method == PublishDiagnostics::METHOD => {}
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| {
+                    let diagnostics = expect_diagnostic_report(receiver, 2);
+                    assert!(!diagnostics.is_empty());
+                    assert!(diagnostics
+                        .iter()
+                        .all(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR)));
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_change_outside_of_document_is_skipped() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Notification(change_text_document(
+                    uri.clone(),
+                    1,
+                    Range::new(Position::new(10, 0), Position::new(12, 0)),
+                    "local y = 2".to_string(),
+                )),
+                Message::Notification(change_text_document(
+                    uri.clone(),
+                    2,
+                    Range::new(Position::new(0, 10), Position::new(0, 5)),
+                    "-- comment".to_string(),
+                )),
+                Message::Notification(change_text_document(
+                    uri.clone(),
+                    3,
+                    Range::new(Position::new(0, 14), Position::new(0, 14)),
+                    " -- x".to_string(),
+                )),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to("local  x  =  1 -- x\n", edits);
+                    assert_eq!(formatted, "local x = 1 -- x\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_document_formatting_adds_final_line_ending() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = 1\nlocal y = 2";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert_eq!(
+                        edits,
+                        [TextEdit {
+                            range: Range::new(Position::new(1, 11), Position::new(1, 11)),
+                            new_text: "\n".to_string()
+                        }]
+                    );
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\nlocal y = 2\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_document_formatting_collapses_final_line_endings() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = 1\nlocal y = 2\n\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert_eq!(
+                        edits,
+                        [TextEdit {
+                            range: Range::new(Position::new(2, 0), Position::new(3, 0)),
+                            new_text: "".to_string()
+                        }]
+                    );
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\nlocal y = 2\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    fn format_on_type(id: i32, uri: Uri, position: Position, ch: &str) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: OnTypeFormatting::METHOD.to_string(),
+            params: to_value(DocumentOnTypeFormattingParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    position,
+                },
+                ch: ch.to_string(),
+                options: FormattingOptions::default(),
+            })
+            .unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_lsp_on_type_formatting() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nif x then\nprint(x)\nend\nlocal  t  =  {1,2}\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                // Typing the `d` of `end` formats the `if` statement
+                format_on_type(2, uri.clone(), Position::new(3, 3), "d"),
+                format_on_type(3, uri.clone(), Position::new(4, 18), "}"),
+                // A new line formats the statement on the previous line
+                format_on_type(4, uri.clone(), Position::new(1, 0), "\n"),
+                shutdown(5),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert_eq!(
+                        apply_text_edits_to(contents, edits),
+                        "local  x  =  1\nif x then\n\tprint(x)\nend\nlocal  t  =  {1,2}\n"
+                    );
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    assert_eq!(
+                        apply_text_edits_to(contents, edits),
+                        "local  x  =  1\nif x then\nprint(x)\nend\nlocal t = { 1, 2 }\n"
+                    );
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 4);
+                    assert_eq!(
+                        apply_text_edits_to(contents, edits),
+                        "local x = 1\nif x then\nprint(x)\nend\nlocal  t  =  {1,2}\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_on_type_formatting_skips_incomplete_block() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nif x then\nprint(x)\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_on_type(2, uri.clone(), Position::new(3, 0), "\n"),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                // The syntax error is reported once the document is opened
+                |receiver: &Receiver<Message>| assert!(matches!(
+                    receiver.recv().unwrap(),
+                    Message::Notification(notification)
+                        if notification.method == PublishDiagnostics::METHOD
+                )),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert!(edits.is_empty());
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_on_type_formatting_does_not_move_cursor_to_another_line() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        // Formatting the statement would expand it over multiple lines, moving the cursor at its end
+        let contents = "if x then print(x) end";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_on_type(2, uri.clone(), Position::new(0, 22), "d"),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert!(edits.is_empty());
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_formatted_document_is_not_formatted_again_until_changed() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = \"hello\"\n";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let formatting_options = FormattingOptions::default();
+        assert!(matches!(
+            language_server.handle_formatting(&uri, None, Some(&formatting_options)),
+            Ok(edits) if edits.is_empty()
+        ));
+
+        // The document would now be formatted differently, so no edits means it was not formatted again
+        language_server
+            .config_resolver
+            .set_default_configuration(Config {
+                quote_style: QuoteStyle::ForceSingle,
+                ..Config::default()
+            });
+        assert!(matches!(
+            language_server.handle_formatting(&uri, None, Some(&formatting_options)),
+            Ok(edits) if edits.is_empty()
+        ));
+
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            1,
+            Range::new(Position::new(1, 0), Position::new(1, 0)),
+            String::new(),
+        ));
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(apply_text_edits_to(contents, edits), "local x = 'hello'\n");
+    }
+
+    #[test]
+    fn test_lsp_formatter_panic_fails_formatting() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        language_server.formatter = |_, _, _, _| panic!("formatter bug");
+        let formatting_options = FormattingOptions::default();
+        assert!(matches!(
+            language_server.handle_formatting(&uri, None, Some(&formatting_options)),
+            Err(FormattingError::StyLuaError)
+        ));
+
+        // The server can still format documents afterwards
+        language_server.formatter = format_ast;
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(apply_text_edits_to(contents, edits), "local x = 1\n");
+    }
+
+    #[test]
+    fn test_lsp_document_is_only_parsed_again_once_changed() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let formatting_options = FormattingOptions::default();
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(apply_text_edits_to(contents, edits), "local x = 1\n");
+
+        // Replace the cached AST, so formatting the other code shows that the document was not parsed again
+        language_server.parsed_documents.get_mut(&uri).unwrap().ast =
+            full_moon::parse_fallible("local  z  =  3", LuaVersion::Lua51.into()).into_ast();
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(apply_text_edits_to(contents, edits), "local z = 3\n");
+
+        language_server.handle_notification(change_text_document(
+            uri.clone(),
+            1,
+            Range::new(Position::new(0, 13), Position::new(0, 14)),
+            "2".to_string(),
+        ));
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(
+            apply_text_edits_to("local  x  =  2", edits),
+            "local x = 2\n"
+        );
+    }
+
+    #[test]
+    fn test_lsp_formatting_is_abandoned_when_document_changes() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+        // Formatting takes as long as it would for a large file
+        language_server.formatter = |ast, config, range, verify_output| {
+            std::thread::sleep(std::time::Duration::from_secs(10));
+            format_ast(ast, config, range, verify_output)
+        };
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        // The change is received once formatting has started
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        language_server.receiver = Some(receiver);
+        sender
+            .send(Message::Notification(change_text_document(
+                uri.clone(),
+                1,
+                Range::new(Position::new(0, 13), Position::new(0, 14)),
+                "2".to_string(),
+            )))
+            .unwrap();
+
+        let start = std::time::Instant::now();
+        assert!(matches!(
+            language_server.handle_formatting(&uri, None, Some(&FormattingOptions::default())),
+            Err(FormattingError::ContentModified)
+        ));
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        // The change is handled after the formatting request
+        let Some(Message::Notification(change)) = language_server.received_messages.pop_front()
+        else {
+            panic!("expected the change to be received");
+        };
+        assert!(language_server.received_messages.is_empty());
+        language_server.handle_notification(change);
+        assert_eq!(
+            language_server.documents.get_document_content(&uri, None),
+            Some("local  x  =  2")
+        );
+    }
+
+    #[test]
+    fn test_lsp_abandoned_formats_are_not_started() {
+        static FORMAT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+        language_server.formatter = |ast, config, range, verify_output| {
+            FORMAT_COUNT.fetch_add(1, AtomicOrdering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            format_ast(ast, config, range, verify_output)
+        };
+
+        let Message::Notification(open) =
+            open_text_document(uri.clone(), "local  x  =  1".to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let change = |version| {
+            Message::Notification(change_text_document(
+                uri.clone(),
+                version,
+                Range::new(Position::new(0, 13), Position::new(0, 14)),
+                "2".to_string(),
+            ))
+        };
+
+        // The first format is abandoned once it has started, and the later formats are abandoned while it is
+        // still running
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        language_server.receiver = Some(receiver);
+        let first_change = change(1);
+        let first_change_sender = sender.clone();
+        std::thread::spawn(move || {
+            while FORMAT_COUNT.load(AtomicOrdering::SeqCst) == 0 {
+                std::thread::yield_now();
+            }
+            first_change_sender.send(first_change).unwrap();
+        });
+        for version in 1..=3 {
+            if version > 1 {
+                sender.send(change(version)).unwrap();
+            }
+            assert!(matches!(
+                language_server.handle_formatting(&uri, None, Some(&FormattingOptions::default())),
+                Err(FormattingError::ContentModified)
+            ));
+        }
+
+        language_server.format_pool.join();
+        assert_eq!(FORMAT_COUNT.load(AtomicOrdering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_lsp_formatting_keeps_byte_order_mark() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "\u{feff}local  x  =  1\nlocal  y  =  2\n";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            // Characters are bytes, as they are when edits are applied
+            PositionEncodingKind::UTF8,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let formatting_options = FormattingOptions::default();
+        let Ok(edits) = language_server.handle_formatting(&uri, None, Some(&formatting_options))
+        else {
+            panic!("formatting failed");
+        };
+        // The byte order mark is the first 3 bytes of the first line
+        assert!(edits
+            .iter()
+            .all(|edit| edit.range.start >= Position::new(0, 3)));
+        assert_eq!(
+            apply_text_edits_to(contents, edits),
+            "\u{feff}local x = 1\nlocal y = 2\n"
+        );
+
+        let Ok(edits) = language_server.handle_formatting(
+            &uri,
+            Some(Range::new(Position::new(1, 0), Position::new(1, 14))),
+            Some(&formatting_options),
+        ) else {
+            panic!("formatting failed");
+        };
+        assert_eq!(
+            apply_text_edits_to(contents, edits),
+            "\u{feff}local  x  =  1\nlocal y = 2\n"
+        );
+    }
+
+    #[test]
+    fn test_lsp_warns_when_output_verification_fails() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+        language_server.verify_output = true;
+        language_server.formatter = |ast, config, range, verify_output| match verify_output {
+            OutputVerification::None => format_ast(ast, config, range, verify_output),
+            _ => Err(stylua_lib::Error::VerificationAstDifference),
+        };
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+        language_server.pending_notifications.clear();
+
+        let Ok(edits) =
+            language_server.handle_formatting(&uri, None, Some(&FormattingOptions::default()))
+        else {
+            panic!("formatting failed");
+        };
+        assert_eq!(apply_text_edits_to(contents, edits), "local x = 1\n");
+
+        let [notification] = language_server.pending_notifications.as_slice() else {
+            panic!("expected a single notification");
+        };
+        assert_eq!(notification.method, ShowMessage::METHOD);
+        let params: ShowMessageParams =
+            serde_json::from_value(notification.params.clone()).unwrap();
+        assert_eq!(params.typ, MessageType::WARNING);
+        assert!(params.message.contains("failed verification"));
+    }
+
+    #[test]
+    fn test_lsp_will_save_wait_until_formats_document() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client
+            .sender
+            .send(open_text_document(uri.clone(), contents.to_string()))
+            .unwrap();
+        client
+            .sender
+            .send(format_document(
+                2,
+                uri.clone(),
+                FormattingOptions::default(),
+            ))
+            .unwrap();
+        client
+            .sender
+            .send(Message::Request(Request {
+                id: RequestId::from(3),
+                method: WillSaveWaitUntil::METHOD.to_string(),
+                params: to_value(WillSaveTextDocumentParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    reason: TextDocumentSaveReason::MANUAL,
+                })
+                .unwrap(),
+            }))
+            .unwrap();
+        client.sender.send(shutdown(4)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        let formatting_edits: Vec<TextEdit> = expect_response(&client.receiver, 2);
+        let will_save_edits: Vec<TextEdit> = expect_response(&client.receiver, 3);
+        assert_eq!(will_save_edits, formatting_edits);
+        assert_eq!(
+            apply_text_edits_to(contents, will_save_edits),
+            "local x = 1\n"
+        );
+
+        expect_server_shutdown(&client.receiver, 4);
+        assert!(client.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_lsp_format_with_cursor() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "local  x  =  1\n\n\n\nprint(   y)\n";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let mut format_with_cursor = |position: Position| {
+            let response = language_server.handle_request(Request {
+                id: RequestId::from(1),
+                method: FormatWithCursor::METHOD.to_string(),
+                params: to_value(FormatWithCursorParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                    position,
+                    options: FormattingOptions::default(),
+                })
+                .unwrap(),
+            });
+            let result: FormatWithCursorResult =
+                serde_json::from_value(response.result.unwrap()).unwrap();
+            assert_eq!(
+                apply_text_edits_to(contents, result.edits),
+                "local x = 1\n\nprint(y)\n"
+            );
+            result.position
+        };
+
+        // Before any changes
+        assert_eq!(format_with_cursor(Position::new(0, 0)), Position::new(0, 0));
+        // After changes on the same line, and removed lines
+        assert_eq!(format_with_cursor(Position::new(0, 7)), Position::new(0, 6));
+        assert_eq!(
+            format_with_cursor(Position::new(4, 10)),
+            Position::new(2, 7)
+        );
+        // Within removed whitespace
+        assert_eq!(format_with_cursor(Position::new(4, 7)), Position::new(2, 6));
+        // Within removed lines
+        assert_eq!(format_with_cursor(Position::new(3, 0)), Position::new(2, 0));
+    }
+
+    #[test]
+    fn test_lsp_document_formatting() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client
+            .sender
+            .send(open_text_document(uri.clone(), contents.to_string()))
+            .unwrap();
+        client
+            .sender
+            .send(format_document(
+                2,
+                uri.clone(),
+                FormattingOptions::default(),
+            ))
+            .unwrap();
+        client.sender.send(shutdown(3)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        let edits: Vec<TextEdit> = expect_response(&client.receiver, 2);
+        assert_eq!(
+            edits,
+            [
+                TextEdit {
+                    range: Range::new(Position::new(0, 6), Position::new(0, 7)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(0, 9), Position::new(0, 10)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(0, 11), Position::new(0, 12)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(0, 14), Position::new(0, 14)),
+                    new_text: "\n".to_string()
+                },
+            ]
+        );
+        let formatted = apply_text_edits_to(contents, edits);
+        assert_eq!(formatted, "local x = 1\n");
+
+        expect_server_shutdown(&client.receiver, 3);
+        assert!(client.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_lsp_ranges_formatting() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nlocal  y  =  2\nlocal  z  =  3\nlocal  w  =  4\n";
+        let ranges_formatting = |id: i32, ranges: Vec<Range>| {
+            Message::Request(Request {
+                id: RequestId::from(id),
+                method: RangesFormatting::METHOD.to_string(),
+                params: serde_json::json!({
+                    "textDocument": { "uri": uri },
+                    "ranges": ranges,
+                    "options": FormattingOptions::default(),
+                }),
+            })
+        };
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                ranges_formatting(
+                    2,
+                    vec![
+                        Range::new(Position::new(2, 0), Position::new(2, 14)),
+                        Range::new(Position::new(0, 0), Position::new(0, 14)),
+                    ]
+                ),
+                // Overlapping ranges are formatted once
+                ranges_formatting(
+                    3,
+                    vec![
+                        Range::new(Position::new(1, 0), Position::new(1, 10)),
+                        Range::new(Position::new(1, 5), Position::new(1, 14)),
+                    ]
+                ),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert!(edits
+                        .windows(2)
+                        .all(|edits| edits[0].range.end <= edits[1].range.start));
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local x = 1\nlocal  y  =  2\nlocal z = 3\nlocal  w  =  4\n"
+                    );
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local  x  =  1\nlocal y = 2\nlocal  z  =  3\nlocal  w  =  4\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_formatting() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nlocal    y   =   2";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client
+            .sender
+            .send(open_text_document(uri.clone(), contents.to_string()))
+            .unwrap();
+        client
+            .sender
+            .send(Message::Request(Request {
+                id: RequestId::from(2),
+                method: RangeFormatting::METHOD.to_string(),
+                params: to_value(DocumentRangeFormattingParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    range: Range::new(Position::new(1, 0), Position::new(1, 18)),
+                    options: FormattingOptions::default(),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .unwrap(),
+            }))
+            .unwrap();
+        client.sender.send(shutdown(3)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        let edits: Vec<TextEdit> = expect_response(&client.receiver, 2);
+        assert_eq!(
+            edits,
+            [
+                TextEdit {
+                    range: Range::new(Position::new(1, 6), Position::new(1, 9)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(1, 11), Position::new(1, 13)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(1, 15), Position::new(1, 17)),
+                    new_text: "".to_string()
+                },
+                TextEdit {
+                    range: Range::new(Position::new(1, 18), Position::new(1, 18)),
+                    new_text: "\n".to_string()
+                },
+            ]
+        );
+        let formatted = apply_text_edits_to(contents, edits);
+        assert_eq!(formatted, "local  x  =  1\nlocal y = 2\n");
+
+        expect_server_shutdown(&client.receiver, 3);
+        assert!(client.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_bisecting_block() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  a  =  1\nif  x  then\n  local  b  =  2\n    local  c  =  3\nend\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        // The range ends within the body of the if statement
+                        range: Range::new(Position::new(0, 0), Position::new(2, 16)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local a = 1\nif  x  then\n\tlocal b = 2\n    local  c  =  3\nend\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_only_returns_edits_within_range() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nprint(  a,   b  )\nlocal  y  =  2\n";
+        let range = Range::new(Position::new(1, 0), Position::new(1, 17));
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let mut language_server = LanguageServer::new(
+            vec![],
+            None,
+            PositionEncodingKind::UTF16,
+            &mut config_resolver,
+        );
+        // Formatting changes the code outside of the range
+        language_server.formatter =
+            |ast, config, _, verify_output| format_ast(ast, config, None, verify_output);
+
+        let Message::Notification(open) = open_text_document(uri.clone(), contents.to_string())
+        else {
+            unreachable!()
+        };
+        language_server.handle_notification(open);
+
+        let Ok(edits) = language_server.handle_formatting(
+            &uri,
+            Some(range),
+            Some(&FormattingOptions::default()),
+        ) else {
+            panic!("formatting failed");
+        };
+        assert!(edits
+            .iter()
+            .all(|edit| edit.range.start >= range.start && edit.range.end <= range.end));
+        assert_eq!(
+            apply_text_edits_to(contents, edits),
+            "local  x  =  1\nprint(a, b)\nlocal  y  =  2\n"
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_expands_to_enclosing_statement() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nprint(  a,   b  )\nlocal  y  =  2\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        // The range only selects the arguments of the function call
+                        range: Range::new(Position::new(1, 8), Position::new(1, 14)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local  x  =  1\nprint(a, b)\nlocal  y  =  2\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_expands_to_statements_at_both_ends() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1\nlocal  y  =  2\nlocal  z  =  3\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        // The range starts and ends partway through statements
+                        range: Range::new(Position::new(0, 8), Position::new(1, 3)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\nlocal y = 2\nlocal  z  =  3\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_expands_to_innermost_statement() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  function  f()\n  return  call(  a  )\nend\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        // The range is within a statement nested in a function body
+                        range: Range::new(Position::new(1, 16), Position::new(1, 17)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local  function  f()\n\treturn call(a)\nend\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_ignore_formatting_for_non_lua_files() {
+        let uri = Uri::from_str("file:///home/documents/file.txt").unwrap();
+        let contents = "local x";
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client
+            .sender
+            .send(Message::Notification(Notification {
+                method: DidOpenTextDocument::METHOD.to_string(),
+                params: to_value(DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: "txt".to_string(),
+                        version: 0,
+                        text: contents.to_string(),
+                    },
+                })
+                .unwrap(),
+            }))
+            .unwrap();
+        client
+            .sender
+            .send(Message::Request(Request {
+                id: RequestId::from(2),
+                method: RangeFormatting::METHOD.to_string(),
+                params: to_value(DocumentRangeFormattingParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    range: Range::new(Position::new(1, 0), Position::new(1, 18)),
+                    options: FormattingOptions::default(),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .unwrap(),
+            }))
+            .unwrap();
+        client.sender.send(shutdown(3)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        let edits: serde_json::Value = expect_response(&client.receiver, 2);
+        assert_eq!(edits, serde_json::Value::Null);
+
+        expect_server_shutdown(&client.receiver, 3);
+        assert!(client.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_lsp_listens_over_tcp() {
+        // Find a free port for the server to listen on
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        let server = {
+            let address = address.clone();
+            std::thread::spawn(move || {
+                run(Opt::parse_from(vec![
+                    "BINARY_NAME",
+                    "--lsp",
+                    "--lsp-listen",
+                    &address,
+                ]))
+            })
+        };
+
+        // The server may not be listening yet
+        let (client, _io_threads) = (0..100)
+            .find_map(|_| {
+                Connection::connect(&address).ok().or_else(|| {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    None
+                })
+            })
+            .expect("could not connect to the server");
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client.sender.send(shutdown(2)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        // The null result of the shutdown response is read from the socket as no result
+        let Message::Response(response) = client.receiver.recv().unwrap() else {
+            panic!("expected a response to the shutdown request");
+        };
+        assert_eq!(response.id, RequestId::from(2));
+        assert!(response.error.is_none());
+
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_lsp_returns_no_edits_for_unknown_files() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+
+        let (server, client) = Connection::memory();
+        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialized()).unwrap();
+        client
+            .sender
+            .send(format_document(2, uri, FormattingOptions::default()))
+            .unwrap();
+        client.sender.send(shutdown(3)).unwrap();
+        client.sender.send(exit()).unwrap();
+
+        main_loop(server, &mut config_resolver).unwrap();
+
+        expect_server_initialized(&client.receiver, 1);
+
+        let edits: serde_json::Value = expect_response(&client.receiver, 2);
+        assert_eq!(edits, serde_json::Value::Null);
+
+        expect_server_shutdown(&client.receiver, 3);
+        assert!(client.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_lsp_respects_configuration_in_root_path() {
+        let contents = "local x = \"hello\"";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    fn change_watched_files(changes: Vec<FileEvent>) -> Message {
+        Message::Notification(Notification {
+            method: DidChangeWatchedFiles::METHOD.to_string(),
+            params: to_value(DidChangeWatchedFilesParams { changes }).unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_lsp_registers_config_file_watcher() {
+        let cwd = construct_tree!({});
+        let workspace_folder = WorkspaceFolder {
+            uri: Uri::from_str(cwd.path().to_str().unwrap()).unwrap(),
+            name: "workspace".to_string(),
+        };
+
+        lsp_test!(
+            [],
+            [
+                Message::Request(Request {
+                    id: RequestId::from(1),
+                    method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+                    params: to_value(InitializeParams {
+                        capabilities: ClientCapabilities {
+                            workspace: Some(WorkspaceClientCapabilities {
+                                did_change_watched_files: Some(
+                                    DidChangeWatchedFilesClientCapabilities {
+                                        dynamic_registration: Some(true),
+                                        relative_pattern_support: Some(true),
+                                    }
+                                ),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                        workspace_folders: Some(vec![workspace_folder.clone()]),
+                        ..Default::default()
+                    })
+                    .unwrap(),
+                }),
+                initialized(),
+                shutdown(2),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Request(request) => {
+                        assert_eq!(request.method, RegisterCapability::METHOD);
+                        let params: RegistrationParams =
+                            serde_json::from_value(request.params).unwrap();
+                        assert_eq!(params.registrations.len(), 1);
+                        assert_eq!(
+                            params.registrations[0].method,
+                            DidChangeWatchedFiles::METHOD
+                        );
+
+                        let options: DidChangeWatchedFilesRegistrationOptions =
+                            serde_json::from_value(
+                                params.registrations[0].register_options.clone().unwrap(),
+                            )
+                            .unwrap();
+                        assert_eq!(
+                            options.watchers,
+                            [FileSystemWatcher {
+                                glob_pattern: GlobPattern::Relative(RelativePattern {
+                                    base_uri: OneOf::Left(workspace_folder.clone()),
+                                    pattern: "**/{stylua.toml,.stylua.toml,.editorconfig}"
+                                        .to_string(),
+                                }),
+                                kind: None,
+                            }]
+                        );
+                    }
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| expect_server_shutdown(receiver, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_format_workspace_command() {
+        let cwd = construct_tree!({
+            ".styluaignore": "ignored/",
+            "bar.lua": "local x = 1\n",
+            "broken.lua": "local x =\n",
+            "foo.lua": "local   x = 1\n",
+            "ignored/baz.lua": "local   x = 1\n",
+        });
+
+        // The open document is formatted using its contents in the editor, rather than on disk
+        let foo_uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+        let foo_contents = "local   y = 2";
+        let broken_uri = path_to_uri(&cwd.child("broken.lua")).unwrap();
+        let work_done_token = ProgressToken::String("format-workspace".to_string());
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(foo_uri.clone(), foo_contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: ExecuteCommand::METHOD.to_string(),
+                    params: to_value(ExecuteCommandParams {
+                        command: "stylua.formatWorkspace".to_string(),
+                        arguments: vec![],
+                        work_done_progress_params: WorkDoneProgressParams {
+                            work_done_token: Some(work_done_token.clone()),
+                        },
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver: &Receiver<Message>| {
+                    let mut messages = Vec::new();
+                    for _ in 0..5 {
+                        match receiver.recv().unwrap() {
+                            Message::Notification(notification) => {
+                                assert_eq!(notification.method, Progress::METHOD);
+                                let params: ProgressParams =
+                                    serde_json::from_value(notification.params).unwrap();
+                                assert_eq!(params.token, work_done_token);
+                                let ProgressParamsValue::WorkDone(progress) = params.value;
+                                messages.push(match progress {
+                                    WorkDoneProgress::Begin(begin) => begin.title,
+                                    WorkDoneProgress::Report(report) => report.message.unwrap(),
+                                    WorkDoneProgress::End(end) => end.message.unwrap(),
+                                });
+                            }
+                            _ => panic!("assertion failed"),
+                        }
+                    }
+
+                    let file_name = |name: &str| cwd.child(name).display().to_string();
+                    assert_eq!(
+                        messages,
+                        [
+                            "Formatting workspace".to_string(),
+                            file_name("bar.lua"),
+                            file_name("broken.lua"),
+                            file_name("foo.lua"),
+                            "formatted 1 files, 1 failed".to_string(),
+                        ]
+                    );
+                },
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Request(request) => {
+                        assert_eq!(request.method, ApplyWorkspaceEdit::METHOD);
+                        let params: ApplyWorkspaceEditParams =
+                            serde_json::from_value(request.params).unwrap();
+                        assert_eq!(
+                            params.edit,
+                            WorkspaceEdit::new(HashMap::from([(
+                                foo_uri.clone(),
+                                vec![TextEdit::new(
+                                    Range::new(Position::new(0, 0), Position::new(0, 13)),
+                                    "local y = 2\n".to_string()
+                                )]
+                            )]))
+                        );
+                    }
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| {
+                    let result: FormatWorkspaceResult = expect_response(receiver, 2);
+                    assert_eq!(result.formatted_files, [foo_uri]);
+                    assert_eq!(result.failed_files, [broken_uri]);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_document_formatting_reports_progress() {
+        let uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let contents = "local  x  =  1";
+        let work_done_token = ProgressToken::Number(1);
+
+        let expect_progress = |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+            Message::Notification(notification) if notification.method == Progress::METHOD => {
+                let params: ProgressParams = serde_json::from_value(notification.params).unwrap();
+                assert_eq!(params.token, ProgressToken::Number(1));
+                let ProgressParamsValue::WorkDone(progress) = params.value;
+                progress
+            }
+            _ => panic!("assertion failed"),
+        };
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: Formatting::METHOD.to_string(),
+                    params: to_value(DocumentFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams {
+                            work_done_token: Some(work_done_token),
+                        },
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let WorkDoneProgress::Begin(begin) = expect_progress(receiver) else {
+                        panic!("assertion failed")
+                    };
+                    assert_eq!(begin.title, "Formatting document");
+                },
+                |receiver| {
+                    let WorkDoneProgress::End(_) = expect_progress(receiver) else {
+                        panic!("assertion failed")
+                    };
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_format_workspace_unknown_command() {
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: ExecuteCommand::METHOD.to_string(),
+                    params: to_value(ExecuteCommandParams {
+                        command: "stylua.unknown".to_string(),
+                        ..Default::default()
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Response(Response {
+                        id,
+                        result: None,
+                        error: Some(error),
+                    }) => {
+                        assert_eq!(id, RequestId::from(2));
+                        assert_eq!(error.code, ErrorCode::InvalidParams as i32);
+                    }
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_reloads_configuration_when_watched_file_changes() {
+        let contents = "if true then\nlocal x = 1\nend\n";
+        let cwd = construct_tree!({
+            "stylua.toml": "indent_type = 'Spaces'\nindent_width = 2",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+        let config_uri = Uri::from_str(cwd.child("stylua.toml").to_str().unwrap()).unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME", "--lsp"]);
+        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        let (server, client) = Connection::memory();
+
+        // The messages received from the server are checked once it has exited, so that a failed assertion
+        // does not leave the server waiting for further messages
+        let (sender, receiver) = crossbeam_channel::unbounded();
+
+        std::thread::scope(|scope| {
+            let config_resolver = &mut config_resolver;
+            let server_thread = scope.spawn(move || main_loop(server, config_resolver));
+
+            client.sender.send(initialize(1, Some(cwd.path()))).unwrap();
+            client.sender.send(initialized()).unwrap();
+            client
+                .sender
+                .send(open_text_document(uri.clone(), contents.to_string()))
+                .unwrap();
+            client
+                .sender
+                .send(format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions::default(),
+                ))
+                .unwrap();
+
+            // The configuration file is only changed once the document has been formatted using the original
+            for _ in 0..2 {
+                sender.send(client.receiver.recv().unwrap()).unwrap();
+            }
+
+            cwd.child("stylua.toml")
+                .write_str("indent_type = 'Spaces'\nindent_width = 8")
+                .unwrap();
+            client
+                .sender
+                .send(change_watched_files(vec![FileEvent::new(
+                    config_uri,
+                    FileChangeType::CHANGED,
+                )]))
+                .unwrap();
+            client
+                .sender
+                .send(format_document(
+                    3,
+                    uri.clone(),
+                    FormattingOptions::default(),
+                ))
+                .unwrap();
+            client.sender.send(shutdown(4)).unwrap();
+            client.sender.send(exit()).unwrap();
+
+            server_thread.join().unwrap().unwrap();
+        });
+
+        for message in client.receiver.try_iter() {
+            sender.send(message).unwrap();
+        }
+
+        expect_server_initialized(&receiver, 1);
+        let edits: Vec<TextEdit> = expect_response(&receiver, 2);
+        assert_eq!(
+            apply_text_edits_to(contents, edits),
+            "if true then\n  local x = 1\nend\n"
+        );
+        let edits: Vec<TextEdit> = expect_response(&receiver, 3);
+        assert_eq!(
+            apply_text_edits_to(contents, edits),
+            "if true then\n        local x = 1\nend\n"
+        );
+        expect_server_shutdown(&receiver, 4);
+        assert!(receiver.is_empty());
+    }
+
+    fn resolved_config(id: i32, uri: Uri) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: ResolvedConfig::METHOD.to_string(),
+            params: to_value(TextDocumentIdentifier { uri }).unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_lsp_resolved_config() {
+        let cwd = construct_tree!({
+            "stylua.toml": "column_width = 80",
+            "foo.lua": "",
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                resolved_config(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let result: ResolvedConfigResult = expect_response(receiver, 2);
+                    assert_eq!(result.config.column_width, 80);
+                    assert!(!result.respect_editor_formatting_options);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_resolved_config_for_unsaved_document() {
+        let cwd = construct_tree!({
+            "stylua.toml": "column_width = 80",
+        });
+
+        // An unsaved document has no path, so configuration is searched for from the root
+        let uri = Uri::from_str("untitled:Untitled-1").unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), "local x = 1".to_string()),
+                resolved_config(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let result: ResolvedConfigResult = expect_response(receiver, 2);
+                    assert_eq!(result.config.column_width, 80);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    fn effective_config(id: i32, uri: Uri) -> Message {
+        Message::Request(Request {
+            id: RequestId::from(id),
+            method: EffectiveConfig::METHOD.to_string(),
+            params: to_value(TextDocumentIdentifier { uri }).unwrap(),
+        })
+    }
+
+    #[cfg(feature = "luau")]
+    #[test]
+    fn test_lsp_formats_luau_document_with_luau_syntax() {
+        let contents = "local  x:  Array<Array<number>>  =  {}";
+        let cwd = construct_tree!({
+            "foo.luau": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.luau").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document_with_language_id(uri.clone(), "luau", contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                resolved_config(3, uri),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x: Array<Array<number>> = {}\n");
+                },
+                |receiver| {
+                    let result: ResolvedConfigResult = expect_response(receiver, 3);
+                    assert_eq!(result.config.syntax, stylua_lib::LuaVersion::Luau);
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[cfg(feature = "luau")]
+    #[test]
+    fn test_lsp_luau_document_uses_configured_syntax() {
+        let cwd = construct_tree!({
+            "stylua.toml": "syntax = \"Lua51\"",
+            "foo.luau": "",
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.luau").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document_with_language_id(uri.clone(), "luau", String::new()),
+                resolved_config(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let result: ResolvedConfigResult = expect_response(receiver, 2);
+                    assert_eq!(result.config.syntax, stylua_lib::LuaVersion::Lua51);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_effective_config() {
+        let cwd = construct_tree!({
+            "stylua.toml": "indent_width = 8",
+            "nested/.stylua.toml": "indent_width = 2",
+            "nested/foo.lua": "",
+        });
+
+        let uri = Uri::from_str(cwd.child("nested/foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                effective_config(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    // The result is `{ "config": { ... }, "config_file": "/path/to/stylua.toml" }`
+                    let result: serde_json::Value = expect_response(receiver, 2);
+                    assert_eq!(result["config"]["indent_width"], 2);
+                    assert_eq!(
+                        result["config_file"],
+                        cwd.child("nested/.stylua.toml").to_str().unwrap()
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_effective_config_without_config_file() {
+        let cwd = construct_tree!({
+            "foo.lua": "",
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            ["--no-editorconfig"],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                effective_config(2, uri),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let result: EffectiveConfigResult = expect_response(receiver, 2);
+                    assert_eq!(result.config.indent_width, Config::default().indent_width);
+                    assert_eq!(result.config_file, None);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_formats_unopened_document_from_disk() {
+        let contents = "local  x  =  1";
+        let cwd = construct_tree!({
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                format_document(2, uri, FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_formats_documents_which_are_not_local_files() {
+        let untitled_uri = Uri::from_str("untitled:Untitled-1").unwrap();
+        let remote_uri = Uri::from_str("vscode-vfs://github/owner/repo/file.lua").unwrap();
+        let contents = "local  x  =  1";
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(untitled_uri.clone(), contents.to_string()),
+                open_text_document(remote_uri.clone(), contents.to_string()),
+                format_document(2, untitled_uri, FormattingOptions::default()),
+                format_document(3, remote_uri, FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_cwd_configuration_respected_for_nested_file() {
+        let contents = "local x = \"hello\"";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "build/foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_configuration_is_not_used_outside_of_cwd() {
+        let contents = "local x = \"hello\"";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "build/foo.lua": contents,
+        });
+
+        let cwd = cwd.child("build");
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = \"hello\"\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_configuration_used_outside_of_cwd_when_search_parent_directories_is_enabled() {
+        let contents = "local x = \"hello\"";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "build/foo.lua": contents,
+        });
+
+        let cwd = cwd.child("build");
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            ["--search-parent-directories"],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_configuration_is_searched_next_to_file() {
+        let contents = "local x = \"hello\"";
+        let cwd = construct_tree!({
+            "build/stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "build/foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("build/foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_does_not_use_editor_formatting_options() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "do print(1) end";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: None,
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 2,
+                        insert_spaces: true,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "do\n\tprint(1)\nend\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_respects_editor_formatting_options_if_enabled() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "do print(1) end";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 2,
+                        insert_spaces: true,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "do\n  print(1)\nend\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[cfg(feature = "editorconfig")]
+    #[test]
+    fn test_lsp_configuration_file_takes_precedence_over_editorconfig() {
+        let contents = "do print(1) end";
+        let cwd = construct_tree!({
+            "stylua.toml": "indent_type = 'Spaces'\nindent_width = 2",
+            ".editorconfig": "root = true\n\n[*]\nindent_style = tab\nend_of_line = crlf\n",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_root_and_options(
+                    1,
+                    cwd.path(),
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 8,
+                        insert_spaces: true,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "do\n  print(1)\nend\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[cfg(feature = "editorconfig")]
+    #[test]
+    fn test_lsp_editorconfig_takes_precedence_over_editor_formatting_options() {
+        let contents = "do print(1) end";
+        let cwd = construct_tree!({
+            ".editorconfig": "root = true\n\n[*.lua]\nindent_style = space\nindent_size = 3\nend_of_line = crlf\n",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_root_and_options(
+                    1,
+                    cwd.path(),
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 8,
+                        insert_spaces: true,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "do\r\n   print(1)\r\nend\r\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[cfg(feature = "editorconfig")]
+    #[test]
+    fn test_lsp_editor_formatting_options_used_when_editorconfig_does_not_set_indentation() {
+        let contents = "do print(1) end";
+        let cwd = construct_tree!({
+            ".editorconfig": "root = true\n\n[*]\nend_of_line = crlf\n",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_root_and_options(
+                    1,
+                    cwd.path(),
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 2,
+                        insert_spaces: true,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "do\r\n  print(1)\r\nend\r\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_editor_trim_trailing_whitespace_option() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        // Formatting leaves the ignored statement, and the contents of the string, alone
+        let contents = "-- stylua: ignore\nlocal  x  =  1  \nlocal y = [[\nfoo  \n]] \n";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        trim_trailing_whitespace: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "-- stylua: ignore\nlocal  x  =  1\nlocal y = [[\nfoo  \n]]\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_editor_insert_final_newline_option() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = 1\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        insert_final_newline: Some(false),
+                        ..Default::default()
+                    }
+                ),
+                format_document(
+                    3,
+                    uri.clone(),
+                    FormattingOptions {
+                        insert_final_newline: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1");
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    assert!(edits.is_empty());
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_editor_trim_final_newlines_option() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "-- stylua: ignore start\nlocal  x  =  1\n\n\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        trim_final_newlines: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "-- stylua: ignore start\nlocal  x  =  1\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_editor_tab_size_not_used_when_indenting_with_tabs() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "do\n\tlocal x = call(aaaa, bbbb)\nend\n";
+
+        lsp_test!(
+            ["--column-width", "32", "--indent-width", "4"],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        tab_size: 8,
+                        insert_spaces: false,
+                        ..Default::default()
+                    }
+                ),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, contents);
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_uses_configuration_from_initialization_options() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = \"hello\"";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        config: Some(Config {
+                            quote_style: QuoteStyle::AutoPreferSingle,
+                            ..Config::default()
+                        }),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_logs_invalid_initialization_options() {
+        lsp_test!(
+            [],
+            [
+                Message::Request(Request {
+                    id: RequestId::from(1),
+                    method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+                    params: to_value(InitializeParams {
+                        initialization_options: Some(serde_json::json!({
+                            "diagnostics_only": true,
+                            "config": { "quote_styel": "AutoPreferSingle" },
+                        })),
+                        ..Default::default()
+                    })
+                    .unwrap(),
+                }),
+                initialized(),
+                shutdown(2),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let message = expect_log_message(receiver, MessageType::WARNING);
+                    assert!(message.starts_with("could not parse initialization options: "));
+                    assert!(message.contains("quote_styel"));
+                },
+                |receiver| expect_server_shutdown(receiver, 2)
+            ]
+        );
     }
 
     #[test]
-    fn test_lsp_document_formatting() {
+    fn test_lsp_uses_changed_configuration() {
         let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
-        let contents = "local  x  =  1";
+        let contents = "if x then\nlocal y = 1\nend";
 
-        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
-        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        config: Some(Config {
+                            indent_type: IndentType::Spaces,
+                            ..Config::default()
+                        }),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                change_configuration(InitializationOptions {
+                    config: Some(Config {
+                        indent_type: IndentType::Spaces,
+                        indent_width: 2,
+                        ..Config::default()
+                    }),
+                    ..Default::default()
+                }),
+                format_document(3, uri.clone(), FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "if x then\n    local y = 1\nend\n");
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "if x then\n  local y = 1\nend\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
 
-        let (server, client) = Connection::memory();
-        client.sender.send(initialize(1, None)).unwrap();
-        client.sender.send(initialized()).unwrap();
-        client
-            .sender
-            .send(open_text_document(uri.clone(), contents.to_string()))
-            .unwrap();
-        client
-            .sender
-            .send(format_document(
-                2,
-                uri.clone(),
-                FormattingOptions::default(),
-            ))
-            .unwrap();
-        client.sender.send(shutdown(3)).unwrap();
-        client.sender.send(exit()).unwrap();
+    #[test]
+    fn test_lsp_uses_config_path_from_initialization_options() {
+        let contents = "local x = 'hello'";
+        let cwd = construct_tree!({
+            "config/stylua.toml": "quote_style = 'ForceSingle'",
+            "stylua.toml": "quote_style = 'ForceDouble'",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                // The configuration file is used in place of the one found next to the document
+                initialize_with_root_and_options(
+                    1,
+                    cwd.path(),
+                    InitializationOptions {
+                        config_path: Some("config/stylua.toml".into()),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                effective_config(3, uri),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 'hello'\n");
+                },
+                |receiver| {
+                    let result: EffectiveConfigResult = expect_response(receiver, 3);
+                    assert_eq!(result.config.quote_style, QuoteStyle::ForceSingle);
+                    assert_eq!(
+                        result.config_file,
+                        Some(cwd.child("config/stylua.toml").to_path_buf())
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_configuration_file_takes_precedence_over_initialization_options() {
+        let contents = "local x = 'hello'";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferDouble'",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                Message::Request(Request {
+                    id: RequestId::from(1),
+                    method: <Initialize as lsp_types::request::Request>::METHOD.to_string(),
+                    params: to_value(InitializeParams {
+                        #[allow(deprecated)]
+                        root_uri: Some(Uri::from_str(cwd.path().to_str().unwrap()).unwrap()),
+                        initialization_options: Some(
+                            to_value(InitializationOptions {
+                                config: Some(Config {
+                                    quote_style: QuoteStyle::ForceSingle,
+                                    ..Config::default()
+                                }),
+                                ..Default::default()
+                            })
+                            .unwrap()
+                        ),
+                        ..Default::default()
+                    })
+                    .unwrap(),
+                }),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = \"hello\"\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_sorts_requires_when_enabled_in_initialization_options() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local bee = require(\"b\")\nlocal ah = require(\"a\")\n";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        config: Some(Config {
+                            sort_requires: SortRequiresConfig { enabled: true },
+                            ..Config::default()
+                        }),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local ah = require(\"a\")\nlocal bee = require(\"b\")\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_initialization_options_are_used_for_options_not_set_by_configuration_file() {
+        let contents = "local bee = require(\"b\")\nlocal ah = require(\"a\")\n";
+        let cwd = construct_tree!({
+            "stylua.toml": "quote_style = 'AutoPreferSingle'",
+            "foo.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_root_and_options(
+                    1,
+                    cwd.path(),
+                    InitializationOptions {
+                        config: Some(Config {
+                            quote_style: QuoteStyle::ForceDouble,
+                            sort_requires: SortRequiresConfig { enabled: true },
+                            ..Config::default()
+                        }),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local ah = require('a')\nlocal bee = require('b')\n"
+                    );
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_stylua_ignore() {
+        let contents = "local   x    =   1";
+        let cwd = construct_tree!({
+            ".styluaignore": "ignored/",
+            "foo.lua": contents,
+            "ignored/bar.lua": contents,
+        });
+
+        let foo_uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+        let bar_uri = Uri::from_str(cwd.child("ignored/bar.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(foo_uri.clone(), contents.to_string()),
+                open_text_document(bar_uri.clone(), contents.to_string()),
+                format_document(2, foo_uri.clone(), FormattingOptions::default()),
+                format_document(3, bar_uri.clone(), FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| {
+                    let edits: serde_json::Value = expect_response(receiver, 3);
+                    assert_eq!(edits, serde_json::Value::Null);
+                },
+                |receiver| {
+                    expect_log_message(receiver, MessageType::INFO);
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_survives_failed_formatting_requests() {
+        let contents = "local   x    =   1";
+        let cwd = construct_tree!({
+            "broken.lua": "local x =",
+            "foo.lua": contents,
+        });
+
+        let missing_uri = Uri::from_str(cwd.child("missing.lua").to_str().unwrap()).unwrap();
+        let untitled_uri = Uri::from_str("untitled:Untitled-1").unwrap();
+        let broken_uri = Uri::from_str(cwd.child("broken.lua").to_str().unwrap()).unwrap();
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                format_document(2, missing_uri, FormattingOptions::default()),
+                format_document(3, untitled_uri, FormattingOptions::default()),
+                format_document(4, broken_uri, FormattingOptions::default()),
+                format_document(5, uri, FormattingOptions::default()),
+                shutdown(6),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Option<Vec<TextEdit>> = expect_response(receiver, 2);
+                    assert_eq!(edits, None);
+                },
+                |receiver| {
+                    let edits: Option<Vec<TextEdit>> = expect_response(receiver, 3);
+                    assert_eq!(edits, None);
+                },
+                |receiver| {
+                    let edits: Option<Vec<TextEdit>> = expect_response(receiver, 4);
+                    assert_eq!(edits, None);
+                },
+                |receiver| {
+                    expect_log_message(receiver, MessageType::WARNING);
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 5);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 6)
+            ]
+        );
+    }
 
-        main_loop(server, false, &mut config_resolver).unwrap();
+    #[test]
+    fn test_lsp_shows_configuration_load_failure() {
+        let contents = "local   x    =   1";
+        let cwd = construct_tree!({
+            "stylua.toml": "column_width = \"wide\"",
+            "foo.lua": contents,
+        });
 
-        expect_server_initialized(&client.receiver, 1);
+        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
 
-        let edits: Vec<TextEdit> = expect_response(&client.receiver, 2);
-        assert_eq!(
-            edits,
+        lsp_test!(
+            [],
             [
-                TextEdit {
-                    range: Range::new(Position::new(0, 6), Position::new(0, 7)),
-                    new_text: "".to_string()
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                // The error is only shown once, but is logged every time
+                format_document(3, uri, FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
                 },
-                TextEdit {
-                    range: Range::new(Position::new(0, 8), Position::new(0, 9)),
-                    new_text: "".to_string()
+                |receiver| {
+                    let message = expect_show_message(receiver, MessageType::ERROR);
+                    assert!(message.contains("could not load configuration"));
+                    assert!(message.contains("column_width"));
                 },
-                TextEdit {
-                    range: Range::new(Position::new(0, 12), Position::new(0, 13)),
-                    new_text: "".to_string()
+                |receiver| {
+                    let message = expect_log_message(receiver, MessageType::ERROR);
+                    assert!(message.contains("could not load configuration"));
                 },
-                TextEdit {
-                    range: Range::new(Position::new(0, 14), Position::new(0, 14)),
-                    new_text: "\n".to_string()
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
+                },
+                |receiver| {
+                    expect_log_message(receiver, MessageType::ERROR);
                 },
+                |receiver| expect_server_shutdown(receiver, 4)
             ]
         );
-        let formatted = apply_text_edits_to(contents, edits);
-        assert_eq!(formatted, "local x = 1\n");
+    }
 
-        expect_server_shutdown(&client.receiver, 3);
-        assert!(client.receiver.is_empty());
+    #[test]
+    fn test_lsp_stylua_ignore_skips_range_formatting_and_diagnostics() {
+        let contents = "local   x    =";
+        let cwd = construct_tree!({
+            ".styluaignore": "ignored/",
+            "ignored/bar.lua": contents,
+        });
+
+        let uri = Uri::from_str(cwd.child("ignored/bar.lua").to_str().unwrap()).unwrap();
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, Some(cwd.path())),
+                initialized(),
+                // No syntax errors are published for the ignored document when it is opened
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri: uri.clone() },
+                        range: Range::new(Position::new(0, 0), Position::new(0, 14)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                document_diagnostic(3, uri),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: serde_json::Value = expect_response(receiver, 2);
+                    assert_eq!(edits, serde_json::Value::Null);
+                },
+                |receiver| {
+                    expect_log_message(receiver, MessageType::INFO);
+                },
+                |receiver| {
+                    let diagnostics = expect_diagnostic_report(receiver, 3);
+                    assert!(diagnostics.is_empty());
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
     }
 
     #[test]
-    fn test_lsp_range_formatting() {
-        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
-        let contents = "local  x  =  1\nlocal    y   =   2";
+    fn test_lsp_stylua_ignore_matches_cli_file_discovery() {
+        // The same tree as `test_list_files_nested_ignore_files` for the CLI
+        let contents = "local   x    =   1";
+        let cwd = construct_tree!({
+            ".styluaignore": "vendor/",
+            "src/.styluaignore": "generated.lua\n!keep/generated.lua",
+            "foo.lua": contents,
+            "vendor/lib.lua": contents,
+            "src/main.lua": contents,
+            "src/generated.lua": contents,
+            "src/nested/generated.lua": contents,
+            "src/keep/generated.lua": contents,
+        });
 
-        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
+        let files = [
+            ("foo.lua", false),
+            ("vendor/lib.lua", true),
+            ("src/main.lua", false),
+            ("src/generated.lua", true),
+            ("src/nested/generated.lua", true),
+            ("src/keep/generated.lua", false),
+        ];
+        let uri = |file: &str| Uri::from_str(cwd.child(file).to_str().unwrap()).unwrap();
+
+        let opt = Opt::parse_from(vec!["BINARY_NAME", "--lsp"]);
         let mut config_resolver = ConfigResolver::new(&opt).unwrap();
 
         let (server, client) = Connection::memory();
-        client.sender.send(initialize(1, None)).unwrap();
+        client.sender.send(initialize(1, Some(cwd.path()))).unwrap();
         client.sender.send(initialized()).unwrap();
-        client
-            .sender
-            .send(open_text_document(uri.clone(), contents.to_string()))
-            .unwrap();
-        client
-            .sender
-            .send(Message::Request(Request {
-                id: RequestId::from(2),
-                method: RangeFormatting::METHOD.to_string(),
-                params: to_value(DocumentRangeFormattingParams {
-                    text_document: TextDocumentIdentifier { uri },
-                    range: Range::new(Position::new(1, 0), Position::new(1, 18)),
-                    options: FormattingOptions::default(),
-                    work_done_progress_params: WorkDoneProgressParams::default(),
-                })
-                .unwrap(),
-            }))
-            .unwrap();
-        client.sender.send(shutdown(3)).unwrap();
+        for (id, (file, _)) in files.iter().enumerate() {
+            client
+                .sender
+                .send(open_text_document(uri(file), contents.to_string()))
+                .unwrap();
+            client
+                .sender
+                .send(format_document(
+                    id as i32 + 2,
+                    uri(file),
+                    FormattingOptions::default(),
+                ))
+                .unwrap();
+        }
+        client.sender.send(shutdown(100)).unwrap();
         client.sender.send(exit()).unwrap();
 
-        main_loop(server, false, &mut config_resolver).unwrap();
+        main_loop(server, &mut config_resolver).unwrap();
 
         expect_server_initialized(&client.receiver, 1);
+        for (id, (file, ignored)) in files.iter().enumerate() {
+            let edits: serde_json::Value = expect_response(&client.receiver, id as i32 + 2);
+            assert_eq!(edits.is_null(), *ignored, "{file}");
+            if *ignored {
+                expect_log_message(&client.receiver, MessageType::INFO);
+            }
+        }
+        expect_server_shutdown(&client.receiver, 100);
+    }
 
-        let edits: Vec<TextEdit> = expect_response(&client.receiver, 2);
-        assert_eq!(
-            edits,
+    #[test]
+    fn test_lsp_disabled_format_trigger_returns_no_edits() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1";
+
+        lsp_test!(
+            [],
             [
-                TextEdit {
-                    range: Range::new(Position::new(1, 6), Position::new(1, 9)),
-                    new_text: "".to_string()
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        format_triggers: Some(vec![RangeFormatting::METHOD.to_string()]),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                Message::Request(Request {
+                    id: RequestId::from(3),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        range: Range::new(Position::new(0, 0), Position::new(0, 14)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: serde_json::Value = expect_response(receiver, 2);
+                    assert_eq!(edits, serde_json::Value::Null);
                 },
-                TextEdit {
-                    range: Range::new(Position::new(1, 10), Position::new(1, 11)),
-                    new_text: "".to_string()
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, "local x = 1\n");
                 },
-                TextEdit {
-                    range: Range::new(Position::new(1, 12), Position::new(1, 13)),
-                    new_text: "".to_string()
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_diagnostics_only_publishes_diagnostic_instead_of_edits() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local  x  =  1";
+
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        diagnostics_only: Some(true),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                Message::Request(Request {
+                    id: RequestId::from(3),
+                    method: CodeActionRequest::METHOD.to_string(),
+                    params: to_value(CodeActionParams {
+                        text_document: TextDocumentIdentifier { uri: uri.clone() },
+                        range: Range::new(Position::new(0, 0), Position::new(0, 14)),
+                        context: Default::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| {
+                    let result: InitializeResult = expect_response(receiver, 1);
+                    assert_eq!(
+                        result.capabilities.code_action_provider,
+                        Some(CodeActionProviderCapability::Simple(true))
+                    );
                 },
-                TextEdit {
-                    range: Range::new(Position::new(1, 14), Position::new(1, 15)),
-                    new_text: "".to_string()
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert!(edits.is_empty());
                 },
-                TextEdit {
-                    range: Range::new(Position::new(1, 16), Position::new(1, 17)),
-                    new_text: "".to_string()
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Notification(notification)
+                        if notification.method == PublishDiagnostics::METHOD =>
+                    {
+                        let params: PublishDiagnosticsParams =
+                            serde_json::from_value(notification.params).unwrap();
+                        assert_eq!(params.uri, uri);
+                        assert_eq!(params.diagnostics.len(), 1);
+                    }
+                    _ => panic!("assertion failed"),
                 },
-                TextEdit {
-                    range: Range::new(Position::new(1, 18), Position::new(1, 18)),
-                    new_text: "\n".to_string()
+                |receiver| {
+                    let actions: Vec<CodeActionOrCommand> = expect_response(receiver, 3);
+                    let [CodeActionOrCommand::CodeAction(CodeAction {
+                        edit:
+                            Some(WorkspaceEdit {
+                                changes: Some(changes),
+                                ..
+                            }),
+                        ..
+                    })] = actions.as_slice()
+                    else {
+                        panic!("assertion failed")
+                    };
+                    let formatted = apply_text_edits_to(contents, changes[&uri].clone());
+                    assert_eq!(formatted, "local x = 1\n");
                 },
+                |receiver| expect_server_shutdown(receiver, 4)
             ]
         );
-        let formatted = apply_text_edits_to(contents, edits);
-        assert_eq!(formatted, "local  x  =  1\nlocal y = 2\n");
-
-        expect_server_shutdown(&client.receiver, 3);
-        assert!(client.receiver.is_empty());
     }
 
     #[test]
-    fn test_lsp_ignore_formatting_for_non_lua_files() {
-        let uri = Uri::from_str("file:///home/documents/file.txt").unwrap();
-        let contents = "local x";
-
-        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
-        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
-
-        let (server, client) = Connection::memory();
-        client.sender.send(initialize(1, None)).unwrap();
-        client.sender.send(initialized()).unwrap();
-        client
-            .sender
-            .send(Message::Notification(Notification {
-                method: DidOpenTextDocument::METHOD.to_string(),
-                params: to_value(DidOpenTextDocumentParams {
-                    text_document: TextDocumentItem {
-                        uri: uri.clone(),
-                        language_id: "txt".to_string(),
-                        version: 0,
-                        text: contents.to_string(),
-                    },
-                })
-                .unwrap(),
-            }))
-            .unwrap();
-        client
-            .sender
-            .send(Message::Request(Request {
-                id: RequestId::from(2),
-                method: RangeFormatting::METHOD.to_string(),
-                params: to_value(DocumentRangeFormattingParams {
-                    text_document: TextDocumentIdentifier { uri },
-                    range: Range::new(Position::new(1, 0), Position::new(1, 18)),
-                    options: FormattingOptions::default(),
+    fn test_lsp_code_actions_format_document_and_selection() {
+        let formatted_uri = Uri::from_str("file:///home/documents/formatted.lua").unwrap();
+        let unformatted_uri = Uri::from_str("file:///home/documents/unformatted.lua").unwrap();
+        let contents = "local  x  =  1\nlocal  y  =  2\n";
+        let code_action = |id: i32, uri: &Uri, range: Range| {
+            Message::Request(Request {
+                id: RequestId::from(id),
+                method: CodeActionRequest::METHOD.to_string(),
+                params: to_value(CodeActionParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                    range,
+                    context: Default::default(),
                     work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: Default::default(),
                 })
                 .unwrap(),
-            }))
-            .unwrap();
-        client.sender.send(shutdown(3)).unwrap();
-        client.sender.send(exit()).unwrap();
-
-        main_loop(server, false, &mut config_resolver).unwrap();
-
-        expect_server_initialized(&client.receiver, 1);
+            })
+        };
 
-        let edits: serde_json::Value = expect_response(&client.receiver, 2);
-        assert_eq!(edits, serde_json::Value::Null);
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(formatted_uri.clone(), "local x = 1\n".to_string()),
+                open_text_document(unformatted_uri.clone(), contents.to_string()),
+                code_action(
+                    2,
+                    &formatted_uri,
+                    Range::new(Position::new(0, 0), Position::new(0, 5))
+                ),
+                code_action(3, &unformatted_uri, Range::default()),
+                code_action(
+                    4,
+                    &unformatted_uri,
+                    Range::new(Position::new(1, 0), Position::new(1, 14))
+                ),
+                shutdown(5),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let actions: Vec<CodeActionOrCommand> = expect_response(receiver, 2);
+                    assert!(actions.is_empty());
+                },
+                |receiver| {
+                    let actions: Vec<CodeActionOrCommand> = expect_response(receiver, 3);
+                    let [CodeActionOrCommand::CodeAction(CodeAction {
+                        title,
+                        kind: Some(kind),
+                        edit:
+                            Some(WorkspaceEdit {
+                                changes: Some(changes),
+                                ..
+                            }),
+                        ..
+                    })] = actions.as_slice()
+                    else {
+                        panic!("assertion failed")
+                    };
+                    assert_eq!(title, "Format with StyLua");
+                    assert_eq!(*kind, CodeActionKind::SOURCE);
+                    let formatted =
+                        apply_text_edits_to(contents, changes[&unformatted_uri].clone());
+                    assert_eq!(formatted, "local x = 1\nlocal y = 2\n");
+                },
+                |receiver| {
+                    let actions: Vec<CodeActionOrCommand> = expect_response(receiver, 4);
+                    let titles: Vec<&str> = actions
+                        .iter()
+                        .map(|action| match action {
+                            CodeActionOrCommand::CodeAction(action) => action.title.as_str(),
+                            CodeActionOrCommand::Command(command) => command.title.as_str(),
+                        })
+                        .collect();
+                    assert_eq!(
+                        titles,
+                        ["Format with StyLua", "Format selection with StyLua"]
+                    );
 
-        expect_server_shutdown(&client.receiver, 3);
-        assert!(client.receiver.is_empty());
+                    let CodeActionOrCommand::CodeAction(CodeAction {
+                        edit:
+                            Some(WorkspaceEdit {
+                                changes: Some(changes),
+                                ..
+                            }),
+                        ..
+                    }) = &actions[1]
+                    else {
+                        panic!("assertion failed")
+                    };
+                    let formatted =
+                        apply_text_edits_to(contents, changes[&unformatted_uri].clone());
+                    assert_eq!(formatted, "local  x  =  1\nlocal y = 2\n");
+                },
+                |receiver| expect_server_shutdown(receiver, 5)
+            ]
+        );
     }
 
     #[test]
-    fn test_lsp_fails_for_unknown_files() {
-        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
-
-        let opt = Opt::parse_from(vec!["BINARY_NAME"]);
-        let mut config_resolver = ConfigResolver::new(&opt).unwrap();
-
-        let (server, client) = Connection::memory();
-        client.sender.send(initialize(1, None)).unwrap();
-        client.sender.send(initialized()).unwrap();
-        client
-            .sender
-            .send(format_document(
-                2,
-                uri.clone(),
-                FormattingOptions::default(),
-            ))
-            .unwrap();
-        client.sender.send(shutdown(3)).unwrap();
-        client.sender.send(exit()).unwrap();
-
-        main_loop(server, false, &mut config_resolver).unwrap();
+    fn test_lsp_uses_language_configuration_from_initialization_options() {
+        let lua_uri = Uri::from_str("file:///home/documents/file.lua").unwrap();
+        let luau_uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = call(aaaaaaaaaa, bbbbbbbbbb, cccccccccc, dddddddddd)";
 
-        expect_server_initialized(&client.receiver, 1);
-
-        let Message::Response(Response {
-            id,
-            result: None,
-            error: Some(error),
-        }) = client.receiver.recv().unwrap()
-        else {
-            unreachable!()
-        };
-        assert!(id == RequestId::from(2));
-        assert_eq!(error.code, ErrorCode::RequestFailed as i32);
-        assert_eq!(
-            error.message,
-            format!("no document found for '{}'", uri.as_str())
+        lsp_test!(
+            [],
+            [
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        config: Some(Config {
+                            column_width: 40,
+                            ..Config::default()
+                        }),
+                        language_config: std::collections::HashMap::from([(
+                            "luau".to_string(),
+                            Config {
+                                column_width: 100,
+                                ..Config::default()
+                            }
+                        )]),
+                        ..Default::default()
+                    }
+                ),
+                initialized(),
+                open_text_document_with_language_id(lua_uri.clone(), "lua", contents.to_string()),
+                open_text_document_with_language_id(luau_uri.clone(), "luau", contents.to_string()),
+                format_document(2, lua_uri.clone(), FormattingOptions::default()),
+                format_document(3, luau_uri.clone(), FormattingOptions::default()),
+                shutdown(4),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(
+                        formatted,
+                        "local x = call(\n\taaaaaaaaaa,\n\tbbbbbbbbbb,\n\tcccccccccc,\n\tdddddddddd\n)\n"
+                    );
+                },
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    let formatted = apply_text_edits_to(contents, edits);
+                    assert_eq!(formatted, format!("{contents}\n"));
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
+            ]
         );
-
-        expect_server_shutdown(&client.receiver, 3);
-        assert!(client.receiver.is_empty());
     }
 
     #[test]
-    fn test_lsp_respects_configuration_in_root_path() {
-        let contents = "local x = \"hello\"";
-        let cwd = construct_tree!({
-            "stylua.toml": "quote_style = 'AutoPreferSingle'",
-            "foo.lua": contents,
-        });
-
-        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+    fn test_lsp_empty_document_has_no_edits() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
 
         lsp_test!(
             [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize(1, None),
                 initialized(),
-                open_text_document(uri.clone(), contents.to_string()),
+                open_text_document(uri.clone(), String::new()),
                 format_document(2, uri.clone(), FormattingOptions::default()),
-                shutdown(3),
+                Message::Request(Request {
+                    id: RequestId::from(3),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri: uri.clone() },
+                        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(4),
                 exit()
             ],
             [
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = 'hello'\n");
+                    assert!(edits.is_empty());
                 },
-                |receiver| expect_server_shutdown(receiver, 3)
+                |receiver| {
+                    let edits: Vec<TextEdit> = expect_response(receiver, 3);
+                    assert!(edits.is_empty());
+                },
+                |receiver| expect_server_shutdown(receiver, 4)
             ]
         );
     }
 
     #[test]
-    fn test_lsp_cwd_configuration_respected_for_nested_file() {
-        let contents = "local x = \"hello\"";
-        let cwd = construct_tree!({
-            "stylua.toml": "quote_style = 'AutoPreferSingle'",
-            "build/foo.lua": contents,
-        });
-
-        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+    fn test_lsp_empty_document_inserts_final_newline_if_requested() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
 
         lsp_test!(
             [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize_with_options(
+                    1,
+                    InitializationOptions {
+                        respect_editor_formatting_options: Some(true),
+                        ..Default::default()
+                    }
+                ),
                 initialized(),
-                open_text_document(uri.clone(), contents.to_string()),
-                format_document(2, uri.clone(), FormattingOptions::default()),
+                open_text_document(uri.clone(), String::new()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        insert_final_newline: Some(true),
+                        ..Default::default()
+                    }
+                ),
                 shutdown(3),
                 exit()
             ],
@@ -884,8 +6875,13 @@ mod tests {
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = 'hello'\n");
+                    assert_eq!(
+                        edits,
+                        [TextEdit {
+                            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                            new_text: "\n".to_string()
+                        }]
+                    );
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -893,23 +6889,23 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_configuration_is_not_used_outside_of_cwd() {
-        let contents = "local x = \"hello\"";
-        let cwd = construct_tree!({
-            "stylua.toml": "quote_style = 'AutoPreferSingle'",
-            "build/foo.lua": contents,
-        });
-
-        let cwd = cwd.child("build");
-        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+    fn test_lsp_empty_document_ignores_final_newline_if_editor_options_not_respected() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
 
         lsp_test!(
             [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize(1, None),
                 initialized(),
-                open_text_document(uri.clone(), contents.to_string()),
-                format_document(2, uri.clone(), FormattingOptions::default()),
+                open_text_document(uri.clone(), String::new()),
+                format_document(
+                    2,
+                    uri.clone(),
+                    FormattingOptions {
+                        insert_final_newline: Some(true),
+                        ..Default::default()
+                    }
+                ),
                 shutdown(3),
                 exit()
             ],
@@ -917,8 +6913,7 @@ mod tests {
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = \"hello\"\n");
+                    assert!(edits.is_empty());
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -926,20 +6921,14 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_configuration_used_outside_of_cwd_when_search_parent_directories_is_enabled() {
-        let contents = "local x = \"hello\"";
-        let cwd = construct_tree!({
-            "stylua.toml": "quote_style = 'AutoPreferSingle'",
-            "build/foo.lua": contents,
-        });
-
-        let cwd = cwd.child("build");
-        let uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
+    fn test_lsp_final_newline_is_inserted_at_end_of_last_line() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = 1\nlocal y = 2";
 
         lsp_test!(
-            ["--search-parent-directories"],
+            [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize(1, None),
                 initialized(),
                 open_text_document(uri.clone(), contents.to_string()),
                 format_document(2, uri.clone(), FormattingOptions::default()),
@@ -950,8 +6939,13 @@ mod tests {
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = 'hello'\n");
+                    assert_eq!(
+                        edits,
+                        [TextEdit {
+                            range: Range::new(Position::new(1, 11), Position::new(1, 11)),
+                            new_text: "\n".to_string()
+                        }]
+                    );
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -959,19 +6953,14 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_configuration_is_searched_next_to_file() {
-        let contents = "local x = \"hello\"";
-        let cwd = construct_tree!({
-            "build/stylua.toml": "quote_style = 'AutoPreferSingle'",
-            "build/foo.lua": contents,
-        });
-
-        let uri = Uri::from_str(cwd.child("build/foo.lua").to_str().unwrap()).unwrap();
+    fn test_lsp_whitespace_only_document_is_cleared() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "  \n\n";
 
         lsp_test!(
             [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize(1, None),
                 initialized(),
                 open_text_document(uri.clone(), contents.to_string()),
                 format_document(2, uri.clone(), FormattingOptions::default()),
@@ -982,8 +6971,15 @@ mod tests {
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert_eq!(
+                        edits,
+                        [TextEdit {
+                            range: Range::new(Position::new(0, 0), Position::new(2, 0)),
+                            new_text: String::new()
+                        }]
+                    );
                     let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = 'hello'\n");
+                    assert_eq!(formatted, "");
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -991,39 +6987,49 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_does_not_use_editor_formatting_options() {
+    fn test_lsp_prefers_utf8_position_encoding() {
         let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
-        let contents = "do print(1) end";
+        // The emoji is 4 bytes in UTF-8, but 2 code units in UTF-16
+        let contents = "local x = '😀'";
 
         lsp_test!(
             [],
             [
-                initialize_with_options(
+                initialize_with_position_encodings(
                     1,
-                    InitializationOptions {
-                        respect_editor_formatting_options: None,
-                    }
+                    vec![PositionEncodingKind::UTF16, PositionEncodingKind::UTF8]
                 ),
                 initialized(),
                 open_text_document(uri.clone(), contents.to_string()),
-                format_document(
-                    2,
+                Message::Notification(change_text_document(
                     uri.clone(),
-                    FormattingOptions {
-                        tab_size: 2,
-                        insert_spaces: true,
-                        ..Default::default()
-                    }
-                ),
+                    1,
+                    Range::new(Position::new(0, 15), Position::new(0, 15)),
+                    "!".to_string(),
+                )),
+                format_document(2, uri.clone(), FormattingOptions::default()),
                 shutdown(3),
                 exit()
             ],
             [
-                |receiver| expect_server_initialized(receiver, 1),
+                |receiver| {
+                    let result: InitializeResult = expect_response(receiver, 1);
+                    assert_eq!(
+                        result.capabilities.position_encoding,
+                        Some(PositionEncodingKind::UTF8)
+                    );
+                },
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "do\n\tprint(1)\nend\n");
+                    assert_eq!(
+                        edits.last(),
+                        Some(&TextEdit {
+                            range: Range::new(Position::new(0, 16), Position::new(0, 17)),
+                            new_text: "\"\n".to_string()
+                        })
+                    );
+                    let formatted = apply_text_edits_to("local x = '😀!'", edits);
+                    assert_eq!(formatted, "local x = \"😀!\"\n");
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -1031,30 +7037,23 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_respects_editor_formatting_options_if_enabled() {
+    fn test_lsp_utf16_position_encoding() {
         let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
-        let contents = "do print(1) end";
+        let contents = "local x = '😀'";
 
         lsp_test!(
             [],
             [
-                initialize_with_options(
-                    1,
-                    InitializationOptions {
-                        respect_editor_formatting_options: Some(true)
-                    }
-                ),
+                initialize_with_position_encodings(1, vec![PositionEncodingKind::UTF16]),
                 initialized(),
                 open_text_document(uri.clone(), contents.to_string()),
-                format_document(
-                    2,
+                Message::Notification(change_text_document(
                     uri.clone(),
-                    FormattingOptions {
-                        tab_size: 2,
-                        insert_spaces: true,
-                        ..Default::default()
-                    }
-                ),
+                    1,
+                    Range::new(Position::new(0, 13), Position::new(0, 13)),
+                    "!".to_string(),
+                )),
+                format_document(2, uri.clone(), FormattingOptions::default()),
                 shutdown(3),
                 exit()
             ],
@@ -1062,8 +7061,13 @@ mod tests {
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
                     let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "do\n  print(1)\nend\n");
+                    assert_eq!(
+                        edits.last(),
+                        Some(&TextEdit {
+                            range: Range::new(Position::new(0, 14), Position::new(0, 15)),
+                            new_text: "\"\n".to_string()
+                        })
+                    );
                 },
                 |receiver| expect_server_shutdown(receiver, 3)
             ]
@@ -1071,41 +7075,173 @@ mod tests {
     }
 
     #[test]
-    fn test_lsp_stylua_ignore() {
-        let contents = "local   x    =   1";
-        let cwd = construct_tree!({
-            ".styluaignore": "ignored/",
-            "foo.lua": contents,
-            "ignored/bar.lua": contents,
-        });
+    fn test_position_to_offset() {
+        let document =
+            FullTextDocument::new("lua".to_string(), 0, "é = 1\n日本語\n😀x\n".to_string());
+        let offset = |line, character, position_encoding| {
+            position_to_offset(
+                &document,
+                Position::new(line, character),
+                &position_encoding,
+            )
+        };
 
-        let foo_uri = Uri::from_str(cwd.child("foo.lua").to_str().unwrap()).unwrap();
-        let bar_uri = Uri::from_str(cwd.child("ignored/bar.lua").to_str().unwrap()).unwrap();
+        // `é` is 2 bytes in UTF-8, and 1 code unit in UTF-16
+        assert_eq!(offset(0, 0, PositionEncodingKind::UTF8), Some(0));
+        assert_eq!(offset(0, 1, PositionEncodingKind::UTF8), None);
+        assert_eq!(offset(0, 2, PositionEncodingKind::UTF8), Some(2));
+        assert_eq!(offset(0, 6, PositionEncodingKind::UTF8), Some(6));
+        assert_eq!(offset(0, 7, PositionEncodingKind::UTF8), None);
+        assert_eq!(offset(0, 1, PositionEncodingKind::UTF16), Some(2));
+        assert_eq!(offset(0, 5, PositionEncodingKind::UTF16), Some(6));
+        assert_eq!(offset(0, 6, PositionEncodingKind::UTF16), None);
+
+        // Each character is 3 bytes in UTF-8, and 1 code unit in UTF-16
+        assert_eq!(offset(1, 3, PositionEncodingKind::UTF8), Some(10));
+        assert_eq!(offset(1, 4, PositionEncodingKind::UTF8), None);
+        assert_eq!(offset(1, 9, PositionEncodingKind::UTF8), Some(16));
+        assert_eq!(offset(1, 1, PositionEncodingKind::UTF16), Some(10));
+        assert_eq!(offset(1, 3, PositionEncodingKind::UTF16), Some(16));
+        assert_eq!(offset(1, 4, PositionEncodingKind::UTF16), None);
+
+        // The emoji is 4 bytes in UTF-8, and 2 code units (a surrogate pair) in UTF-16
+        assert_eq!(offset(2, 2, PositionEncodingKind::UTF8), None);
+        assert_eq!(offset(2, 4, PositionEncodingKind::UTF8), Some(21));
+        assert_eq!(offset(2, 5, PositionEncodingKind::UTF8), Some(22));
+        assert_eq!(offset(2, 1, PositionEncodingKind::UTF16), None);
+        assert_eq!(offset(2, 2, PositionEncodingKind::UTF16), Some(21));
+        assert_eq!(offset(2, 3, PositionEncodingKind::UTF16), Some(22));
+
+        // The empty line after the final line ending
+        assert_eq!(offset(3, 0, PositionEncodingKind::UTF16), Some(23));
+        assert_eq!(offset(4, 0, PositionEncodingKind::UTF16), None);
+    }
+
+    #[test]
+    fn test_position_to_offset_crlf() {
+        let document = FullTextDocument::new(
+            "lua".to_string(),
+            0,
+            "local x = 1\r\nlocal y = 2\r\n".to_string(),
+        );
+        let offset = |line, character| {
+            position_to_offset(
+                &document,
+                Position::new(line, character),
+                &PositionEncodingKind::UTF16,
+            )
+        };
+
+        // The `\r` is part of the line ending, rather than the last character of the line
+        assert_eq!(offset(0, 10), Some(10));
+        assert_eq!(offset(0, 11), Some(11));
+        assert_eq!(offset(0, 12), None);
+        assert_eq!(offset(1, 0), Some(13));
+        assert_eq!(offset(1, 10), Some(23));
+        assert_eq!(offset(1, 11), Some(24));
+        assert_eq!(offset(1, 12), None);
+        assert_eq!(offset(2, 0), Some(26));
+    }
+
+    #[test]
+    fn test_lsp_range_formatting_within_character_fails() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = '😀'";
 
         lsp_test!(
             [],
             [
-                initialize(1, Some(cwd.path())),
+                initialize(1, None),
                 initialized(),
-                open_text_document(foo_uri.clone(), contents.to_string()),
-                open_text_document(bar_uri.clone(), contents.to_string()),
-                format_document(2, foo_uri.clone(), FormattingOptions::default()),
-                format_document(3, bar_uri.clone(), FormattingOptions::default()),
-                shutdown(4),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Request(Request {
+                    id: RequestId::from(2),
+                    method: RangeFormatting::METHOD.to_string(),
+                    params: to_value(DocumentRangeFormattingParams {
+                        text_document: TextDocumentIdentifier { uri },
+                        range: Range::new(Position::new(0, 0), Position::new(0, 12)),
+                        options: FormattingOptions::default(),
+                        work_done_progress_params: WorkDoneProgressParams::default(),
+                    })
+                    .unwrap(),
+                }),
+                shutdown(3),
+                exit()
+            ],
+            [
+                |receiver| expect_server_initialized(receiver, 1),
+                |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+                    Message::Response(Response {
+                        id,
+                        result: None,
+                        error: Some(error),
+                    }) if id == RequestId::from(2) => {
+                        assert_eq!(error.code, ErrorCode::InvalidParams as i32)
+                    }
+                    _ => panic!("assertion failed"),
+                },
+                |receiver| expect_server_shutdown(receiver, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lsp_publishes_syntax_error_diagnostics() {
+        let uri = Uri::from_str("file:///home/documents/file.luau").unwrap();
+        let contents = "local x = 1\nlocal y = 😀\n";
+
+        let expect_diagnostics = |receiver: &Receiver<Message>| match receiver.recv().unwrap() {
+            Message::Notification(notification)
+                if notification.method == PublishDiagnostics::METHOD =>
+            {
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                assert_eq!(params.uri, uri);
+                params.diagnostics
+            }
+            _ => panic!("assertion failed"),
+        };
+
+        lsp_test!(
+            [],
+            [
+                initialize(1, None),
+                initialized(),
+                open_text_document(uri.clone(), contents.to_string()),
+                Message::Notification(change_text_document(
+                    uri.clone(),
+                    1,
+                    Range::new(Position::new(1, 10), Position::new(1, 12)),
+                    "2".to_string(),
+                )),
+                format_document(2, uri.clone(), FormattingOptions::default()),
+                shutdown(3),
                 exit()
             ],
             [
                 |receiver| expect_server_initialized(receiver, 1),
                 |receiver| {
-                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
-                    let formatted = apply_text_edits_to(contents, edits);
-                    assert_eq!(formatted, "local x = 1\n");
+                    let diagnostics = expect_diagnostics(receiver);
+                    // The emoji is an unexpected character, leaving the assignment without an expression
+                    assert_eq!(
+                        diagnostics
+                            .iter()
+                            .map(|diagnostic| diagnostic.range)
+                            .collect::<Vec<_>>(),
+                        [
+                            Range::new(Position::new(1, 8), Position::new(1, 9)),
+                            Range::new(Position::new(1, 10), Position::new(1, 12))
+                        ]
+                    );
+                    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
                 },
+                // Once the syntax error is fixed, its diagnostics are cleared
+                |receiver| assert!(expect_diagnostics(receiver).is_empty()),
                 |receiver| {
-                    let edits: serde_json::Value = expect_response(receiver, 3);
-                    assert_eq!(edits, serde_json::Value::Null);
+                    let edits: Vec<TextEdit> = expect_response(receiver, 2);
+                    assert!(edits.is_empty());
                 },
-                |receiver| expect_server_shutdown(receiver, 4)
+                |receiver| expect_server_shutdown(receiver, 3)
             ]
         );
     }
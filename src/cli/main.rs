@@ -8,25 +8,53 @@ use std::collections::HashSet;
 use std::fs;
 use std::io::{stderr, stdin, stdout, Read, Write};
 use std::path::Path;
-use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
 use thiserror::Error;
 use threadpool::ThreadPool;
 
-use stylua_lib::{format_code, Config, OutputVerification, Range};
+use stylua_lib::{format_code_verbose, Config, OutputVerification, Range, Warning, WarningKind};
 
 mod config;
+mod discovery;
+mod input;
 #[cfg(feature = "lsp")]
 mod lsp;
 mod opt;
+mod output;
 mod output_diff;
-mod stylua_ignore;
+mod schema;
 
-use stylua_ignore::{is_explicitly_provided, path_is_stylua_ignored, should_respect_ignores};
+use discovery::{
+    explain_ignore, is_explicitly_provided, is_ignored, should_respect_ignores, IgnoreFiles,
+    DEFAULT_GLOB,
+};
 
-static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+static EXIT_CODE: AtomicI32 = AtomicI32::new(ExitCode::Success as i32);
 static UNFORMATTED_FILE_COUNT: AtomicU32 = AtomicU32::new(0);
+static ADDED_LINE_COUNT: AtomicUsize = AtomicUsize::new(0);
+static REMOVED_LINE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The exit codes of the CLI. If multiple kinds of failure occur, the highest exit code is used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum ExitCode {
+    /// All files were formatted successfully, or were already correctly formatted when using `--check`
+    Success = 0,
+    /// Some files were not correctly formatted when using `--check`
+    CheckFailed = 1,
+    /// A file failed to parse or could not be formatted. This is also used for any other kind of error
+    FormatError = 2,
+    /// The configuration could not be loaded
+    ConfigError = 3,
+    /// A file could not be found, read or written
+    IoError = 4,
+}
+
+/// Records the exit code, if it is higher than the exit code recorded so far
+fn set_exit_code(exit_code: ExitCode) {
+    EXIT_CODE.fetch_max(exit_code as i32, Ordering::SeqCst);
+}
 
 enum FormatResult {
     /// Operation was a success, the output was either written to a file or stdout. If diffing, there was no diff to create.
@@ -36,8 +64,18 @@ enum FormatResult {
     SuccessBufferedOutput(Vec<u8>),
     /// There is a diff output. This stores the diff created
     Diff(Vec<u8>),
+    /// The number of lines which formatting would add to and remove from a file, when using `--report-line-delta`
+    LineDelta {
+        file: String,
+        changes: output_diff::LineChanges,
+    },
 }
 
+/// Wraps an error which occurred whilst loading configuration, so that it can be reported with its own exit code
+#[derive(Error, Debug)]
+#[error("{:#}", .0)]
+struct ConfigError(anyhow::Error);
+
 /// Wraps an error to include information about the file it resonated from
 #[derive(Error, Debug)]
 #[error("{:#}", .error)]
@@ -46,6 +84,25 @@ struct ErrorFileWrapper {
     error: anyhow::Error,
 }
 
+/// Determines the exit code to use for the given error
+fn exit_code_for_error(error: &anyhow::Error) -> ExitCode {
+    if let Some(ErrorFileWrapper { error, .. }) = error.downcast_ref::<ErrorFileWrapper>() {
+        return exit_code_for_error(error);
+    }
+
+    for cause in error.chain() {
+        if cause.is::<ConfigError>() {
+            return ExitCode::ConfigError;
+        } else if cause.is::<stylua_lib::Error>() {
+            return ExitCode::FormatError;
+        } else if cause.is::<std::io::Error>() {
+            return ExitCode::IoError;
+        }
+    }
+
+    ExitCode::FormatError
+}
+
 fn convert_parse_error_to_json(file: &str, errs: Vec<full_moon::Error>) -> serde_json::Value {
     errs.iter()
         .map(|err| {
@@ -133,6 +190,60 @@ fn create_diff(
     }
 }
 
+/// Logs a warning produced whilst formatting. Overlong lines are only reported as a warning when
+/// `--warn-overlong` is enabled, and all other warnings are only shown in verbose output.
+fn log_warning(opt: &opt::Opt, file_name: &str, warning: &Warning) {
+    match warning.kind {
+        WarningKind::LineTooLong { .. } if opt.warn_overlong => warn!("{}:{}", file_name, warning),
+        _ => debug!("{}:{}", file_name, warning),
+    }
+}
+
+/// Formats the already formatted code a second time, returning an error containing the changes made if the output
+/// differs, as formatting should be idempotent
+fn verify_idempotent(
+    opt: &opt::Opt,
+    formatted_contents: &str,
+    config: Config,
+    range: Option<Range>,
+    verify_output: OutputVerification,
+    file_name: &str,
+) -> Result<()> {
+    // Positions in a range refer to the input, so would not match the formatted code
+    if !opt.check_idempotent || range.is_some() {
+        return Ok(());
+    }
+
+    let (reformatted_contents, _) =
+        format_code_verbose(formatted_contents, config, None, verify_output)
+            .context("failed to format the output a second time")?;
+
+    if let Some(diff) = output_diff::output_diff(
+        formatted_contents,
+        &reformatted_contents,
+        3,
+        &format!("Diff when formatting {file_name} a second time:"),
+        opt.color,
+    )? {
+        bail!(
+            "formatting is not idempotent, the output changed when formatted again\n{}",
+            String::from_utf8_lossy(&diff)
+        );
+    }
+
+    Ok(())
+}
+
+/// Determines the verification to perform on the formatted output. Reparsing the output is slow, so it is only
+/// performed when `--verify` is passed
+fn output_verification(opt: &opt::Opt) -> OutputVerification {
+    if opt.verify {
+        OutputVerification::Full
+    } else {
+        OutputVerification::None
+    }
+}
+
 fn format_file(
     path: &Path,
     config: Config,
@@ -141,20 +252,40 @@ fn format_file(
     verify_output: OutputVerification,
 ) -> Result<FormatResult> {
     let contents =
-        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        input::read_file(path).with_context(|| format!("failed to read {}", path.display()))?;
 
     let before_formatting = Instant::now();
-    let formatted_contents = format_code(&contents, config, range, verify_output)
-        .with_context(|| format!("could not format file {}", path.display()))?;
+    let (formatted_contents, warnings) =
+        format_code_verbose(&contents, config, range, verify_output)
+            .with_context(|| format!("could not format file {}", path.display()))?;
     let after_formatting = Instant::now();
 
+    for warning in warnings {
+        log_warning(opt, &path.display().to_string(), &warning);
+    }
+
+    verify_idempotent(
+        opt,
+        &formatted_contents,
+        config,
+        range,
+        verify_output,
+        &path.display().to_string(),
+    )
+    .with_context(|| format!("could not format file {}", path.display()))?;
+
     debug!(
         "formatted {} in {:?}",
         path.display(),
         after_formatting.duration_since(before_formatting)
     );
 
-    if opt.check {
+    if opt.report_line_delta {
+        Ok(FormatResult::LineDelta {
+            file: path.display().to_string(),
+            changes: output_diff::count_line_changes(&contents, &formatted_contents),
+        })
+    } else if opt.check {
         let diff = create_diff(
             opt,
             &contents,
@@ -168,8 +299,12 @@ fn format_file(
             None => Ok(FormatResult::Complete),
         }
     } else {
-        if formatted_contents != contents {
-            fs::write(path, formatted_contents)
+        let changed = formatted_contents != *contents;
+        // The file may be memory-mapped, which must be released before we write to it
+        drop(contents);
+
+        if changed {
+            output::write_file(path, formatted_contents)
                 .with_context(|| format!("could not write to {}", path.display()))?;
         }
         Ok(FormatResult::Complete)
@@ -189,10 +324,30 @@ fn format_string(
     let formatted_contents = if should_skip {
         input.clone()
     } else {
-        format_code(&input, config, range, verify_output).context("failed to format from stdin")?
+        let (formatted_contents, warnings) =
+            format_code_verbose(&input, config, range, verify_output)
+                .context("failed to format from stdin")?;
+        for warning in warnings {
+            log_warning(opt, "stdin", &warning);
+        }
+        verify_idempotent(
+            opt,
+            &formatted_contents,
+            config,
+            range,
+            verify_output,
+            "stdin",
+        )
+        .context("failed to format from stdin")?;
+        formatted_contents
     };
 
-    if opt.check {
+    if opt.report_line_delta {
+        Ok(FormatResult::LineDelta {
+            file: "stdin".to_string(),
+            changes: output_diff::count_line_changes(&input, &formatted_contents),
+        })
+    } else if opt.check {
         let diff = create_diff(opt, &input, &formatted_contents, "stdin")
             .context("failed to create diff")?;
 
@@ -222,6 +377,22 @@ fn format(opt: opt::Opt) -> Result<i32> {
         }
     }
 
+    if opt.config_schema {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema::config_schema())?
+        );
+        return Ok(0);
+    }
+
+    if let Some(ref path) = opt.explain_ignore {
+        match explain_ignore(&opt, path)? {
+            Some(reason) => println!("{} is {}", path.display(), reason),
+            None => println!("{} is not ignored", path.display()),
+        }
+        return Ok(0);
+    }
+
     if opt.files.is_empty() {
         bail!("no files provided");
     }
@@ -235,10 +406,14 @@ fn format(opt: opt::Opt) -> Result<i32> {
     {
         bail!("--output-format=unified and --output-format=summary can only be used when --check is enabled");
     }
+    if opt.check && opt.report_line_delta {
+        bail!("--report-line-delta cannot be used with --check");
+    }
 
     // Load the configuration
     let opt_for_config_resolver = opt.clone();
-    let mut config_resolver = config::ConfigResolver::new(&opt_for_config_resolver)?;
+    let mut config_resolver =
+        config::ConfigResolver::new(&opt_for_config_resolver).map_err(ConfigError)?;
 
     // Create range if provided
     let range = if opt.range_start.is_some() || opt.range_end.is_some() {
@@ -247,12 +422,7 @@ fn format(opt: opt::Opt) -> Result<i32> {
         None
     };
 
-    // Determine if we need to verify the output
-    let verify_output = if opt.verify {
-        OutputVerification::Full
-    } else {
-        OutputVerification::None
-    };
+    let verify_output = output_verification(&opt);
 
     let cwd = std::env::current_dir()?;
 
@@ -262,17 +432,17 @@ fn format(opt: opt::Opt) -> Result<i32> {
         walker_builder.add(file_path);
     }
 
+    // Filter out any files or directories ignored by a .styluaignore file whilst traversing
+    let ignore_files = Arc::new(IgnoreFiles::default());
     walker_builder
         .standard_filters(true)
         .hidden(!opt.allow_hidden)
         .parents(true)
-        .add_custom_ignore_filename(".styluaignore");
-
-    // Look for an ignore file in the current working directory
-    let ignore_path = cwd.join(".styluaignore");
-    if ignore_path.is_file() {
-        walker_builder.add_ignore(ignore_path);
-    }
+        .filter_entry(move |entry| {
+            !ignore_files
+                .is_ignored(entry.path(), false)
+                .unwrap_or(false)
+        });
 
     let use_default_glob = match opt.glob {
         Some(ref globs) => {
@@ -293,10 +463,11 @@ fn format(opt: opt::Opt) -> Result<i32> {
     let pool = ThreadPool::new(std::cmp::max(opt.num_threads, 2)); // Use a minimum of 2 threads, because we need at least one output reader as well as a formatter
     let (tx, rx) = crossbeam_channel::unbounded::<Result<_>>();
     let output_format = opt.output_format;
+    let quiet = opt.quiet;
     let opt = Arc::new(opt);
 
     // Output a header if in summary mode
-    if matches!(opt.output_format, opt::OutputFormat::Summary) {
+    if matches!(opt.output_format, opt::OutputFormat::Summary) && !opt.quiet {
         println!(
             "{} Checking formatting...",
             style("!")
@@ -323,12 +494,14 @@ fn format(opt: opt::Opt) -> Result<i32> {
                         };
                     }
                     FormatResult::Diff(diff) => {
-                        if EXIT_CODE.load(Ordering::SeqCst) != 2 {
-                            EXIT_CODE.store(1, Ordering::SeqCst);
-                        }
+                        set_exit_code(ExitCode::CheckFailed);
 
                         UNFORMATTED_FILE_COUNT.fetch_add(1, Ordering::SeqCst);
 
+                        if quiet {
+                            continue;
+                        }
+
                         let stdout = stdout();
                         let mut handle = stdout.lock();
                         match handle.write_all(&diff) {
@@ -336,8 +509,18 @@ fn format(opt: opt::Opt) -> Result<i32> {
                             Err(err) => error!("{:#}", err),
                         }
                     }
+                    FormatResult::LineDelta { file, changes } => {
+                        ADDED_LINE_COUNT.fetch_add(changes.added, Ordering::SeqCst);
+                        REMOVED_LINE_COUNT.fetch_add(changes.removed, Ordering::SeqCst);
+
+                        // Only files which formatting would change are reported
+                        if changes != output_diff::LineChanges::default() {
+                            println!("{file}: {changes}");
+                        }
+                    }
                 },
                 Err(err) if matches!(output_format, opt::OutputFormat::Json) => {
+                    set_exit_code(exit_code_for_error(&err));
                     match err.downcast_ref::<ErrorFileWrapper>() {
                         Some(ErrorFileWrapper { file, error }) => {
                             match error.downcast_ref::<stylua_lib::Error>() {
@@ -361,7 +544,10 @@ fn format(opt: opt::Opt) -> Result<i32> {
                         _ => error!("{:#}", err),
                     }
                 }
-                Err(err) => error!("{:#}", err),
+                Err(err) => {
+                    set_exit_code(exit_code_for_error(&err));
+                    error!("{:#}", err)
+                }
             }
         }
     });
@@ -373,22 +559,22 @@ fn format(opt: opt::Opt) -> Result<i32> {
         match result {
             Ok(entry) => {
                 if entry.is_stdin() {
+                    // There is no file to list when reading from stdin
+                    if opt.list_files {
+                        continue;
+                    }
+
                     let tx = tx.clone();
                     let opt = opt.clone();
 
                     let should_skip_format = match &opt.stdin_filepath {
-                        Some(path) => {
-                            opt.respect_ignores
-                                && path_is_stylua_ignored(
-                                    path,
-                                    opt.search_parent_directories,
-                                    None,
-                                )?
-                        }
+                        Some(path) => opt.respect_ignores && is_ignored(path)?,
                         None => false,
                     };
 
-                    let config = config_resolver.load_configuration_for_stdin()?;
+                    let config = config_resolver
+                        .load_configuration_for_stdin()
+                        .map_err(ConfigError)?;
 
                     pool.execute(move || {
                         let mut buf = String::new();
@@ -428,32 +614,46 @@ fn format(opt: opt::Opt) -> Result<i32> {
 
                     if path.is_file() {
                         // If the user didn't provide a glob pattern, we should match against our default one
-                        if use_default_glob && should_respect_ignores(opt.as_ref(), path.as_path())
+                        if use_default_glob
+                            && should_respect_ignores(opt.as_ref(), path.as_path())
+                            && !DEFAULT_GLOB.is_match(&path)
                         {
-                            lazy_static::lazy_static! {
-                                static ref DEFAULT_GLOB: globset::GlobSet = {
-                                    let mut builder = globset::GlobSetBuilder::new();
-                                    builder.add(globset::Glob::new("**/*.lua").expect("cannot create default glob"));
-                                    #[cfg(feature = "luau")]
-                                    builder.add(globset::Glob::new("**/*.luau").expect("cannot create default luau glob"));
-                                    builder.build().expect("cannot build default globset")
-                                };
-                            }
-                            if !DEFAULT_GLOB.is_match(&path) {
-                                continue;
-                            }
+                            continue;
                         }
 
                         // If `--respect-ignores` was given and this is an explicit file path,
                         // we should check .styluaignore
                         if is_explicitly_provided(opt.as_ref(), &path)
                             && should_respect_ignores(opt.as_ref(), &path)
-                            && path_is_stylua_ignored(&path, opt.search_parent_directories, None)?
+                            && is_ignored(&path)?
                         {
                             continue;
                         }
 
-                        let config = config_resolver.load_configuration(&path)?;
+                        // If the modification time cannot be read, the file is formatted, as it is not known
+                        // to be unchanged
+                        if let Some(modified_since) = opt.modified_since {
+                            match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+                                Ok(modified) if modified <= modified_since => {
+                                    debug!("skipping {}: not modified since", path.display());
+                                    continue;
+                                }
+                                Ok(_) => (),
+                                Err(err) => warn!(
+                                    "could not read modification time of {}: {err}",
+                                    path.display()
+                                ),
+                            }
+                        }
+
+                        if opt.list_files {
+                            println!("{}", path.display());
+                            continue;
+                        }
+
+                        let config = config_resolver
+                            .load_configuration(&path)
+                            .map_err(ConfigError)?;
 
                         let tx = tx.clone();
                         pool.execute(move || {
@@ -475,12 +675,15 @@ fn format(opt: opt::Opt) -> Result<i32> {
             }
             Err(error) => match error {
                 ignore::Error::WithPath { path, err } => match *err {
-                    ignore::Error::Io(error) => match error.kind() {
-                        std::io::ErrorKind::NotFound => {
-                            error!("no file or directory found matching '{:#}'", path.display())
+                    ignore::Error::Io(error) => {
+                        set_exit_code(ExitCode::IoError);
+                        match error.kind() {
+                            std::io::ErrorKind::NotFound => {
+                                error!("no file or directory found matching '{:#}'", path.display())
+                            }
+                            _ => error!("{:#}", error),
                         }
-                        _ => error!("{:#}", error),
-                    },
+                    }
                     _ => error!("{:#}", err),
                 },
                 _ => error!("{:#}", error),
@@ -493,7 +696,7 @@ fn format(opt: opt::Opt) -> Result<i32> {
 
     // Output summary
 
-    if matches!(opt.output_format, opt::OutputFormat::Summary) {
+    if matches!(opt.output_format, opt::OutputFormat::Summary) && !opt.quiet {
         let file_count = UNFORMATTED_FILE_COUNT.load(Ordering::SeqCst);
         if file_count == 0 {
             println!(
@@ -519,14 +722,20 @@ fn format(opt: opt::Opt) -> Result<i32> {
         }
     }
 
+    if opt.report_line_delta {
+        let changes = output_diff::LineChanges {
+            added: ADDED_LINE_COUNT.load(Ordering::SeqCst),
+            removed: REMOVED_LINE_COUNT.load(Ordering::SeqCst),
+        };
+        println!("total: {changes}");
+    }
+
     // Exit with non-zero code if we have a panic
-    let output_code = if pool.panic_count() > 0 {
-        2
-    } else {
-        EXIT_CODE.load(Ordering::SeqCst)
-    };
+    if pool.panic_count() > 0 {
+        set_exit_code(ExitCode::FormatError);
+    }
 
-    Ok(output_code)
+    Ok(EXIT_CODE.load(Ordering::SeqCst))
 }
 
 fn main() {
@@ -535,6 +744,8 @@ fn main() {
     let should_use_color = opt.color.should_use_color_stderr();
     let level_filter = if opt.verbose {
         LevelFilter::Debug
+    } else if opt.quiet {
+        LevelFilter::Error
     } else {
         LevelFilter::Warn
     };
@@ -544,7 +755,7 @@ fn main() {
         .format(move |buf, record| {
             // Side effect: set exit code
             if let Level::Error = record.level() {
-                EXIT_CODE.store(2, Ordering::SeqCst);
+                set_exit_code(ExitCode::FormatError);
             }
 
             let tag = match record.level() {
@@ -581,8 +792,9 @@ fn main() {
     let exit_code = match format(opt) {
         Ok(code) => code,
         Err(err) => {
+            set_exit_code(exit_code_for_error(&err));
             error!("{:#}", err);
-            2
+            EXIT_CODE.load(Ordering::SeqCst)
         }
     };
 
@@ -591,6 +803,7 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use assert_cmd::Command;
     use assert_fs::prelude::*;
 
@@ -771,6 +984,548 @@ mod tests {
         cwd.close().unwrap();
     }
 
+    #[test]
+    fn test_list_files() {
+        let cwd = construct_tree!({
+            ".styluaignore": "ignored/",
+            "foo.lua": "local   x    =   1",
+            "bar.txt": "local   x    =   1",
+            "ignored/baz.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--list-files", "."])
+            .assert()
+            .success()
+            .stdout(format!(
+                "{}\n",
+                std::path::Path::new(".").join("foo.lua").display()
+            ));
+
+        cwd.child("foo.lua").assert("local   x    =   1");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_list_files_nested_ignore_files() {
+        // The same tree as `test_lsp_stylua_ignore_matches_cli_file_discovery` for the language server
+        let cwd = construct_tree!({
+            ".styluaignore": "vendor/",
+            "src/.styluaignore": "generated.lua\n!keep/generated.lua",
+            "foo.lua": "local   x    =   1",
+            "vendor/lib.lua": "local   x    =   1",
+            "src/main.lua": "local   x    =   1",
+            "src/generated.lua": "local   x    =   1",
+            "src/nested/generated.lua": "local   x    =   1",
+            "src/keep/generated.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        let assert = cmd
+            .current_dir(cwd.path())
+            .args(["--list-files", "."])
+            .assert()
+            .success();
+
+        let listed: std::collections::HashSet<std::path::PathBuf> =
+            String::from_utf8_lossy(&assert.get_output().stdout)
+                .lines()
+                .map(|line| cwd.path().join(line).components().collect())
+                .collect();
+
+        for file in [
+            "foo.lua",
+            "vendor/lib.lua",
+            "src/main.lua",
+            "src/generated.lua",
+            "src/nested/generated.lua",
+            "src/keep/generated.lua",
+        ] {
+            let path = cwd.path().join(file);
+            assert_eq!(
+                listed.contains(&path),
+                !super::discovery::is_ignored(&path).unwrap(),
+                "{file}"
+            );
+        }
+        assert_eq!(listed.len(), 3);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_respect_ignores_path_outside_cwd() {
+        let cwd = construct_tree!({
+            "proj/.styluaignore": "foo.lua",
+            "other/foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.child("proj").path())
+            .args(["--check", "--respect-ignores", "../other/foo.lua"])
+            .assert()
+            .code(1);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_walking_directory_outside_cwd_ignores_cwd_ignore_file() {
+        let cwd = construct_tree!({
+            "proj/.styluaignore": "foo.lua",
+            "other/foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.child("proj").path())
+            .args(["--check", "../other"])
+            .assert()
+            .code(1);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_respect_ignores_parent_directory_ignore_file() {
+        // Ignore files above the current working directory apply without `--search-parent-directories`
+        let cwd = construct_tree!({
+            ".styluaignore": "proj/foo.lua",
+            "proj/foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.child("proj").path())
+            .args(["--check", "--respect-ignores", "foo.lua"])
+            .assert()
+            .success();
+
+        cwd.child("proj/foo.lua").assert("local   x    =   1");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_explain_ignore_styluaignore() {
+        let cwd = construct_tree!({
+            ".styluaignore": "*.txt\nfoo.lua",
+            "foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--explain-ignore", "foo.lua"])
+            .assert()
+            .success()
+            .stdout("foo.lua is ignored by pattern 'foo.lua' in .styluaignore\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_explain_ignore_glob() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+            "bar.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args([
+                "--glob",
+                "**/*.lua",
+                "--glob",
+                "!foo.lua",
+                "--explain-ignore",
+                "foo.lua",
+            ])
+            .assert()
+            .success()
+            .stdout("foo.lua is ignored by pattern '!foo.lua' provided by --glob\n");
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args([
+                "--glob",
+                "**/*.lua",
+                "--glob",
+                "!foo.lua",
+                "--explain-ignore",
+                "bar.lua",
+            ])
+            .assert()
+            .success()
+            .stdout("bar.lua is not ignored\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_quiet_clean_run() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--quiet", "."])
+            .assert()
+            .success()
+            .stdout("")
+            .stderr("");
+
+        cwd.child("foo.lua").assert("local x = 1\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_quiet_check_summary() {
+        let cwd = construct_tree!({
+            "foo.lua": "local x = 1\n",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "--output-format=summary", "--quiet", "."])
+            .assert()
+            .success()
+            .stdout("");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_quiet_check_keeps_exit_code() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "--quiet", "."])
+            .assert()
+            .code(1)
+            .stdout("");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_quiet_still_outputs_errors() {
+        let cwd = construct_tree!({
+            "foo.lua": "local x =",
+        });
+
+        let mut cmd = create_stylua();
+        let assert = cmd
+            .current_dir(cwd.path())
+            .args(["--quiet", "."])
+            .assert()
+            .code(2)
+            .stdout("");
+
+        let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+        assert!(stderr.starts_with("error"));
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_warn_overlong() {
+        let cwd = construct_tree!({
+            "foo.lua": "local message = 'this string literal cannot be split onto multiple lines'\n",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--column-width", "40", "foo.lua"])
+            .assert()
+            .success()
+            .stderr("");
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--column-width", "40", "--warn-overlong", "foo.lua"])
+            .assert()
+            .success()
+            .stderr("warn: foo.lua:2: line is 61 columns wide, which is over the column width\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_check_idempotent() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check-idempotent", "foo.lua"])
+            .assert()
+            .success();
+
+        cwd.child("foo.lua").assert("local x = 1\n");
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check-idempotent", "-"])
+            .write_stdin("local   y    =   2")
+            .assert()
+            .success()
+            .stdout("local y = 2\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_check_idempotent_reports_non_idempotent_output() {
+        // Collapsing the function only happens once the blank lines around `return` are removed
+        let contents = "function bar()\n\n\treturn\n\nend\n";
+        let cwd = construct_tree!({
+            "foo.lua": "function bar()\n\n\treturn\n\nend\n",
+        });
+
+        let mut cmd = create_stylua();
+        let assert = cmd
+            .current_dir(cwd.path())
+            .args([
+                "--collapse-simple-statement",
+                "Always",
+                "--check-idempotent",
+                "foo.lua",
+            ])
+            .assert()
+            .code(2);
+
+        let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+        assert!(stderr
+            .contains("formatting is not idempotent, the output changed when formatted again"));
+
+        // The file is left unchanged
+        cwd.child("foo.lua").assert(contents);
+
+        let mut cmd = create_stylua();
+        let assert = cmd
+            .current_dir(cwd.path())
+            .args([
+                "--collapse-simple-statement",
+                "Always",
+                "--check-idempotent",
+                "-",
+            ])
+            .write_stdin(contents)
+            .assert()
+            .code(2)
+            .stdout("");
+
+        let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+        assert!(stderr.contains("Diff when formatting stdin a second time"));
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_output_verification() {
+        let opt = opt::Opt::parse_from(["stylua", "foo.lua"]);
+        assert!(matches!(
+            output_verification(&opt),
+            OutputVerification::None
+        ));
+
+        let opt = opt::Opt::parse_from(["stylua", "--verify", "foo.lua"]);
+        assert!(matches!(
+            output_verification(&opt),
+            OutputVerification::Full
+        ));
+    }
+
+    #[test]
+    fn test_report_line_delta() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1\n\n\n\nlocal y = 2\n",
+            "bar.lua": "local z = 3\n",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--report-line-delta", "foo.lua", "bar.lua"])
+            .assert()
+            .success()
+            .stdout("foo.lua: -2 (+1 -3)\ntotal: -2 (+1 -3)\n");
+
+        // Files are not written to
+        cwd.child("foo.lua")
+            .assert("local   x    =   1\n\n\n\nlocal y = 2\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_report_line_delta_with_check() {
+        let cwd = construct_tree!({
+            "foo.lua": "local x = 1\n",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--report-line-delta", "--check", "foo.lua"])
+            .assert()
+            .failure()
+            .code(2);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_exit_code_check_failed() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "foo.lua"])
+            .assert()
+            .code(1);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_exit_code_parse_error() {
+        let cwd = construct_tree!({
+            "foo.lua": "local x =",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "foo.lua"])
+            .assert()
+            .code(2);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_exit_code_config_error() {
+        let cwd = construct_tree!({
+            "stylua.toml": "column_width = 'wide'",
+            "foo.lua": "local x = 1\n",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "foo.lua"])
+            .assert()
+            .code(3);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_exit_code_io_error() {
+        let cwd = construct_tree!({});
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "missing.lua"])
+            .assert()
+            .code(4);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_exit_code_uses_highest_code() {
+        let cwd = construct_tree!({
+            "foo.lua": "local   x    =   1",
+            "bar.lua": "local x =",
+        });
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--check", "foo.lua", "bar.lua", "missing.lua"])
+            .assert()
+            .code(4);
+
+        cwd.close().unwrap();
+    }
+
+    fn set_modified_time(path: &std::path::Path, time: std::time::SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_modified_since_duration() {
+        let cwd = construct_tree!({
+            ".styluaignore": "ignored.lua",
+            "old.lua": "local   x    =   1",
+            "new.lua": "local   x    =   1",
+            "ignored.lua": "local   x    =   1",
+        });
+
+        let day_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 60 * 24);
+        set_modified_time(cwd.child("old.lua").path(), day_ago);
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args(["--modified-since", "1h", "."])
+            .assert()
+            .success();
+
+        cwd.child("old.lua").assert("local   x    =   1");
+        cwd.child("new.lua").assert("local x = 1\n");
+        cwd.child("ignored.lua").assert("local   x    =   1");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_modified_since_timestamp() {
+        let cwd = construct_tree!({
+            "old.lua": "local   x    =   1",
+            "new.lua": "local   x    =   1",
+        });
+
+        set_modified_time(
+            cwd.child("old.lua").path(),
+            humantime::parse_rfc3339("2019-12-31T00:00:00Z").unwrap(),
+        );
+
+        let mut cmd = create_stylua();
+        cmd.current_dir(cwd.path())
+            .args([
+                "--modified-since",
+                "2020-01-01T00:00:00Z",
+                "old.lua",
+                "new.lua",
+            ])
+            .assert()
+            .success();
+
+        cwd.child("old.lua").assert("local   x    =   1");
+        cwd.child("new.lua").assert("local x = 1\n");
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_modified_since_invalid_value() {
+        let mut cmd = create_stylua();
+        cmd.args(["--modified-since", "yesterday", "."])
+            .assert()
+            .failure()
+            .code(2);
+    }
+
     #[test]
     fn test_stdin_filepath_respects_cwd_configuration_next_to_file() {
         let cwd = construct_tree!({
@@ -1,8 +1,12 @@
 use clap::{ArgEnum, StructOpt};
 use std::path::PathBuf;
+use std::time::SystemTime;
 use stylua_lib::{
-    BlockNewlineGaps, CallParenType, CollapseSimpleStatement, IndentType, LineEndings, LuaVersion,
-    QuoteStyle, SpaceAfterFunctionNames,
+    AssignedFunction, BinaryChainIndent, BlockNewlineGaps, CallParenType, CollapseSimpleStatement,
+    DispatchTableStyle, IfExpressionStyle, IndentType, LastArgCallback, LeadingCommentNewlineGap,
+    LineEndings, LuaVersion, NestedTableExpansion, ParameterComments, QuoteStyle,
+    ReturnParentheses, ReturnTypeStyle, SingleTableArgument, SpaceAfterFunctionNames,
+    UnaryOperatorSpacing,
 };
 
 lazy_static::lazy_static! {
@@ -54,10 +58,36 @@ pub struct Opt {
     #[structopt(long)]
     pub verify: bool,
 
+    /// Verifies that formatting is idempotent.
+    ///
+    /// Formats the output a second time, and fails if doing so changes it, reporting the changes made.
+    /// This check is skipped when only formatting a range.
+    #[structopt(long)]
+    pub check_idempotent: bool,
+
+    /// Reports the change in line count of each file after formatting, and the total across all files.
+    ///
+    /// Any files input will not be overwritten. Cannot be used with 'check'.
+    #[structopt(long)]
+    pub report_line_delta: bool,
+
     /// Enables verbose output
     #[structopt(short, long)]
     pub verbose: bool,
 
+    /// Warns about any lines which are still over the column width after formatting.
+    ///
+    /// These lines could not be split, e.g. because they contain a long string literal or identifier.
+    #[structopt(long)]
+    pub warn_overlong: bool,
+
+    /// Suppresses all output other than errors.
+    ///
+    /// Diffs and summaries in 'check' mode, along with warnings, are not printed. The exit code is still set.
+    /// Formatted code is still written to stdout when formatting from stdin.
+    #[structopt(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
     /// Use colored output.
     #[structopt(long, ignore_case = true, default_value_t = Color::Auto, arg_enum)]
     pub color: Color,
@@ -112,6 +142,51 @@ pub struct Opt {
     /// Run Stylua as a language server (following LSP protocol)
     #[structopt(long)]
     pub lsp: bool,
+
+    /// Listens for a language client connecting over TCP at the given address (e.g. `127.0.0.1:9257`),
+    /// rather than communicating over stdin and stdout.
+    ///
+    /// Only a single connection is accepted.
+    #[structopt(long, requires = "lsp")]
+    pub lsp_listen: Option<String>,
+
+    /// Prints the paths of all files which would be formatted, without formatting them.
+    ///
+    /// Files are filtered using globs and ignore files in the same way as when formatting.
+    #[structopt(long)]
+    pub list_files: bool,
+
+    /// Prints which ignore file pattern or glob causes the given path to be skipped, or that it is not ignored.
+    ///
+    /// Useful for debugging why a file is not being formatted.
+    #[structopt(long, parse(from_os_str))]
+    pub explain_ignore: Option<PathBuf>,
+
+    /// Prints a JSON Schema describing the options of a configuration file, including their defaults.
+    ///
+    /// Editors can use this to validate and complete `stylua.toml`.
+    #[structopt(long)]
+    pub config_schema: bool,
+
+    /// Only format files which were last modified after the given time.
+    ///
+    /// Accepts either an RFC3339 timestamp (e.g. `2024-01-01T00:00:00Z`),
+    /// or a duration before the current time (e.g. `30min`, `1day 12h`).
+    #[structopt(long, parse(try_from_str = parse_modified_since))]
+    pub modified_since: Option<SystemTime>,
+}
+
+fn parse_modified_since(input: &str) -> Result<SystemTime, String> {
+    if let Ok(time) = humantime::parse_rfc3339_weak(input) {
+        return Ok(time);
+    }
+
+    let duration = humantime::parse_duration(input).map_err(|_| {
+        format!("`{input}` is not an RFC3339 timestamp or a duration (e.g. `30min`)")
+    })?;
+    SystemTime::now()
+        .checked_sub(duration)
+        .ok_or_else(|| format!("duration `{input}` is too large"))
 }
 
 #[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
@@ -196,8 +271,53 @@ pub struct FormatOpts {
     /// Enable requires sorting
     #[structopt(long)]
     pub sort_requires: bool,
+    /// Rewrite an `else` block containing only a nested if statement into an `elseif`
+    #[structopt(long)]
+    pub normalize_elseif: bool,
     #[structopt(long, arg_enum, ignore_case = true)]
     pub space_after_function_names: Option<ArgSpaceAfterFunctionNames>,
+    /// Specify how Luau if-expressions should be broken when they do not fit onto a single line.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub if_expression_style: Option<ArgIfExpressionStyle>,
+    /// Specify where Luau function return type annotations should be placed.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub return_type_style: Option<ArgReturnTypeStyle>,
+    /// Specify whether parentheses around returned expressions should be kept.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub return_parentheses: Option<ArgReturnParentheses>,
+    /// Specify how higher-precedence operands are indented when a binary expression chain is hung.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub binary_chain_indent: Option<ArgBinaryChainIndent>,
+    /// Specify how a call is formatted when its last argument is an anonymous function spanning multiple lines.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub last_arg_callback: Option<ArgLastArgCallback>,
+    /// Specify how tables where every field is keyed and has an anonymous function value are formatted.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub dispatch_table_style: Option<ArgDispatchTableStyle>,
+    /// Specify whether symbolic unary operators, such as `-` and `#`, are followed by a space.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub unary_operator_spacing: Option<ArgUnaryOperatorSpacing>,
+    /// The maximum width of a single table element before the table is forced onto multiple lines. 0 disables the limit.
+    #[structopt(long)]
+    pub table_element_max_width: Option<usize>,
+    /// Break a chain of `.name` indexes which does not fit within the column width before each `.`
+    #[structopt(long)]
+    pub wrap_index_chains: bool,
+    /// Specify whether a table being broken over multiple lines causes the tables nested within it to be broken too.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub nested_table_expansion: Option<ArgNestedTableExpansion>,
+    /// Specify how an anonymous function spanning multiple lines is formatted when it is the only value of an assignment.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub assigned_function: Option<ArgAssignedFunction>,
+    /// Specify how a call is formatted when its only argument is a table spanning multiple lines.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub single_table_argument: Option<ArgSingleTableArgument>,
+    /// Specify whether a newline gap is kept between the comments at the start of a block and its first statement.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub leading_comment_newline_gap: Option<ArgLeadingCommentNewlineGap>,
+    /// Specify how comments written between the parameters of a function are handled.
+    #[structopt(long, arg_enum, ignore_case = true)]
+    pub parameter_comments: Option<ArgParameterComments>,
 }
 
 // Convert [`stylua_lib::Config`] enums into clap-friendly enums
@@ -276,6 +396,7 @@ convert_enum!(CollapseSimpleStatement, ArgCollapseSimpleStatement, {
     Never,
     FunctionOnly,
     ConditionalOnly,
+    GettersSettersOnly,
     Always,
 });
 
@@ -291,6 +412,67 @@ convert_enum!(SpaceAfterFunctionNames, ArgSpaceAfterFunctionNames, {
     Always,
 });
 
+convert_enum!(IfExpressionStyle, ArgIfExpressionStyle, {
+    Hanging,
+    BreakAfterKeywords,
+});
+
+convert_enum!(ReturnTypeStyle, ArgReturnTypeStyle, {
+    SameLine,
+    BreakWhenLong,
+});
+
+convert_enum!(ReturnParentheses, ArgReturnParentheses, {
+    RemoveRedundant,
+    Input,
+});
+
+convert_enum!(BinaryChainIndent, ArgBinaryChainIndent, {
+    Minimal,
+    Precedence,
+});
+
+convert_enum!(LastArgCallback, ArgLastArgCallback, {
+    Hug,
+    Wrap,
+});
+
+convert_enum!(DispatchTableStyle, ArgDispatchTableStyle, {
+    Default,
+    Switch,
+});
+
+convert_enum!(UnaryOperatorSpacing, ArgUnaryOperatorSpacing, {
+    Tight,
+    Spaced,
+});
+
+convert_enum!(NestedTableExpansion, ArgNestedTableExpansion, {
+    Independent,
+    Propagate,
+});
+
+convert_enum!(AssignedFunction, ArgAssignedFunction, {
+    Hug,
+    Wrap,
+});
+
+convert_enum!(SingleTableArgument, ArgSingleTableArgument, {
+    Hug,
+    Wrap,
+});
+
+convert_enum!(LeadingCommentNewlineGap, ArgLeadingCommentNewlineGap, {
+    Preserve,
+    Never,
+    Always,
+});
+
+convert_enum!(ParameterComments, ArgParameterComments, {
+    Wrap,
+    Inline,
+});
+
 #[cfg(test)]
 mod tests {
     use super::Opt;
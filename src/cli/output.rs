@@ -0,0 +1,187 @@
+//! Writing formatted code back to files.
+//! The output is written to a temporary file in the same directory, which is then renamed over the original file.
+//! As the rename is atomic, the original file is left intact if formatting is interrupted part way through a write.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Used to give each temporary file a unique name, as multiple files in the same directory may be written at once
+static TEMPORARY_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new temporary file in the same directory as the given path
+fn create_temporary_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    loop {
+        let temporary_path = path.with_file_name(format!(
+            ".{}.stylua-{}-{}.tmp",
+            file_name,
+            std::process::id(),
+            TEMPORARY_FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary_path)
+        {
+            Ok(file) => return Ok((temporary_path, file)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Copies the permissions, and ownership where possible, of the original file onto the temporary file
+fn copy_metadata(original: &fs::Metadata, file: &File) -> io::Result<()> {
+    file.set_permissions(original.permissions())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Changing the owner requires privileges we may not have, in which case the file is owned by the current user
+        let _ = std::os::unix::fs::fchown(file, Some(original.uid()), Some(original.gid()));
+    }
+
+    Ok(())
+}
+
+/// Atomically replaces the contents of the file at the given path with the output of `write`.
+/// If `write` fails, the original file is left untouched.
+fn write_file_with(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    // Write through any symlinks, rather than replacing the symlink itself with a file
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+
+    // The rename would succeed even if the file is read-only, so we must explicitly refuse to write to it
+    if metadata.permissions().readonly() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "file is read-only",
+        ));
+    }
+
+    let (temporary_path, mut file) = create_temporary_file(&path)?;
+
+    let result = write(&mut file)
+        .and_then(|_| copy_metadata(&metadata, &file))
+        .and_then(|_| file.sync_all())
+        .and_then(|_| {
+            drop(file);
+            fs::rename(&temporary_path, &path)
+        });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary_path);
+    }
+
+    result
+}
+
+/// Atomically replaces the contents of the file at the given path, preserving its permissions
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_file_with(path, |file| file.write_all(contents.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    fn directory_entries(path: &Path) -> Vec<String> {
+        let mut entries = fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn test_write_file() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local   x = 1").unwrap();
+
+        write_file(&cwd.child("foo.lua"), "local x = 1\n").unwrap();
+
+        cwd.child("foo.lua").assert("local x = 1\n");
+        assert_eq!(directory_entries(cwd.path()), vec!["foo.lua"]);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_write_leaves_original_intact() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local   x = 1").unwrap();
+
+        let result = write_file_with(&cwd.child("foo.lua"), |file| {
+            file.write_all(b"local x")?;
+            Err(io::Error::other("interrupted"))
+        });
+
+        assert!(result.is_err());
+        cwd.child("foo.lua").assert("local   x = 1");
+        assert_eq!(directory_entries(cwd.path()), vec!["foo.lua"]);
+
+        cwd.close().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_file_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local   x = 1").unwrap();
+        fs::set_permissions(cwd.child("foo.lua"), fs::Permissions::from_mode(0o751)).unwrap();
+
+        write_file(&cwd.child("foo.lua"), "local x = 1\n").unwrap();
+
+        let mode = fs::metadata(cwd.child("foo.lua"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o751);
+
+        cwd.close().unwrap();
+    }
+
+    #[test]
+    fn test_write_file_read_only() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local   x = 1").unwrap();
+        let mut permissions = fs::metadata(cwd.child("foo.lua")).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(cwd.child("foo.lua"), permissions).unwrap();
+
+        let error = write_file(&cwd.child("foo.lua"), "local x = 1\n").unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        cwd.child("foo.lua").assert("local   x = 1");
+        assert_eq!(directory_entries(cwd.path()), vec!["foo.lua"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_file_through_symlink() {
+        let cwd = assert_fs::TempDir::new().unwrap();
+        cwd.child("foo.lua").write_str("local   x = 1").unwrap();
+        std::os::unix::fs::symlink(cwd.child("foo.lua").path(), cwd.child("link.lua").path())
+            .unwrap();
+
+        write_file(&cwd.child("link.lua"), "local x = 1\n").unwrap();
+
+        cwd.child("foo.lua").assert("local x = 1\n");
+        assert!(fs::symlink_metadata(cwd.child("link.lua"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        cwd.close().unwrap();
+    }
+}
@@ -109,6 +109,41 @@ pub fn output_diff_unified(old: &str, new: &str) -> Result<Option<Vec<u8>>> {
     Ok(Some(buffer))
 }
 
+/// The number of lines added and removed between two versions of a file
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LineChanges {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl LineChanges {
+    /// The change in the total number of lines
+    pub fn delta(&self) -> i64 {
+        self.added as i64 - self.removed as i64
+    }
+}
+
+impl fmt::Display for LineChanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:+} (+{} -{})", self.delta(), self.added, self.removed)
+    }
+}
+
+pub fn count_line_changes(old: &str, new: &str) -> LineChanges {
+    let text_diff = TextDiff::from_lines(old, new);
+    let mut changes = LineChanges::default();
+
+    for change in text_diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => changes.added += 1,
+            ChangeTag::Delete => changes.removed += 1,
+            ChangeTag::Equal => (),
+        }
+    }
+
+    changes
+}
+
 #[derive(Serialize)]
 pub struct DiffMismatch {
     original_start_line: usize,
@@ -257,4 +292,27 @@ mod tests {
 "#
         );
     }
+
+    #[test]
+    fn test_count_line_changes() {
+        let changes = count_line_changes(
+            "local  x = 1\n\n\nlocal y = 2\n",
+            "local x = 1\n\nlocal y = 2\n",
+        );
+        assert_eq!(
+            changes,
+            LineChanges {
+                added: 1,
+                removed: 2
+            }
+        );
+        assert_eq!(changes.to_string(), "-1 (+1 -2)");
+    }
+
+    #[test]
+    fn test_count_line_changes_no_diff() {
+        let changes = count_line_changes("local x = 1\n", "local x = 1\n");
+        assert_eq!(changes, LineChanges::default());
+        assert_eq!(changes.to_string(), "+0 (+0 -0)");
+    }
 }
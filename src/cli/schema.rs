@@ -0,0 +1,336 @@
+//! Generates a [JSON Schema](https://json-schema.org/) describing the fields of a configuration file (`stylua.toml`),
+//! for use by editors to validate and complete configuration.
+//! The possible values of each option are taken from the same enums the CLI uses, and the defaults from
+//! [`Config::default`], so that the schema stays in sync with the configuration.
+
+use std::fmt::Debug;
+
+use clap::ArgEnum;
+use serde_json::{json, Map, Value};
+use stylua_lib::{Config, StatementKind};
+
+use crate::opt::{
+    ArgAssignedFunction, ArgBinaryChainIndent, ArgBlockNewlineGaps, ArgCallParenType,
+    ArgCollapseSimpleStatement, ArgDispatchTableStyle, ArgIfExpressionStyle, ArgIndentType,
+    ArgLastArgCallback, ArgLeadingCommentNewlineGap, ArgLineEndings, ArgLuaVersion,
+    ArgNestedTableExpansion, ArgParameterComments, ArgQuoteStyle, ArgReturnParentheses,
+    ArgReturnTypeStyle, ArgSingleTableArgument, ArgSpaceAfterFunctionNames,
+    ArgUnaryOperatorSpacing,
+};
+
+/// The name of a [`Config`] enum variant, as written in a configuration file
+fn variant_name(value: impl Debug) -> String {
+    format!("{:?}", value)
+}
+
+/// A property which takes one of the variants of the [`Config`] enum `T`, found through its CLI equivalent `A`
+fn enum_property<A, T>(description: &str, default: T) -> Value
+where
+    A: ArgEnum + Into<T>,
+    T: Debug,
+{
+    let variants = A::value_variants()
+        .iter()
+        .map(|variant| variant_name(variant.clone().into()))
+        .collect::<Vec<_>>();
+
+    json!({
+        "description": description,
+        "type": "string",
+        "enum": variants,
+        "default": variant_name(default),
+    })
+}
+
+fn integer_property(description: &str, default: usize) -> Value {
+    json!({
+        "description": description,
+        "type": "integer",
+        "minimum": 0,
+        "default": default,
+    })
+}
+
+fn boolean_property(description: &str, default: bool) -> Value {
+    json!({
+        "description": description,
+        "type": "boolean",
+        "default": default,
+    })
+}
+
+/// Creates the JSON Schema for a configuration file
+pub fn config_schema() -> Value {
+    let config = Config::default();
+    let mut properties = Map::new();
+
+    properties.insert(
+        "syntax".to_string(),
+        enum_property::<ArgLuaVersion, _>("The type of Lua syntax to parse", config.syntax),
+    );
+    properties.insert(
+        "column_width".to_string(),
+        integer_property(
+            "The approximate line length to use when printing the code",
+            config.column_width,
+        ),
+    );
+    properties.insert(
+        "line_endings".to_string(),
+        enum_property::<ArgLineEndings, _>("The type of line endings to use", config.line_endings),
+    );
+    properties.insert(
+        "indent_type".to_string(),
+        enum_property::<ArgIndentType, _>("The type of indents to use", config.indent_type),
+    );
+    properties.insert(
+        "indent_width".to_string(),
+        integer_property(
+            "The width of a single indentation level",
+            config.indent_width,
+        ),
+    );
+    properties.insert(
+        "quote_style".to_string(),
+        enum_property::<ArgQuoteStyle, _>(
+            "The style of quotes to use in string literals",
+            config.quote_style,
+        ),
+    );
+    #[allow(deprecated)]
+    let mut no_call_parentheses = boolean_property(
+        "Deprecated, use `call_parentheses` instead. Whether to omit parentheses around function calls which take a single string literal or table",
+        config.no_call_parentheses,
+    );
+    no_call_parentheses["deprecated"] = json!(true);
+    properties.insert("no_call_parentheses".to_string(), no_call_parentheses);
+    properties.insert(
+        "call_parentheses".to_string(),
+        enum_property::<ArgCallParenType, _>(
+            "Whether parentheses should be applied on function calls with a single string or table argument",
+            config.call_parentheses,
+        ),
+    );
+    properties.insert(
+        "collapse_simple_statement".to_string(),
+        enum_property::<ArgCollapseSimpleStatement, _>(
+            "Whether simple statements should be collapsed onto a single line",
+            config.collapse_simple_statement,
+        ),
+    );
+    properties.insert(
+        "sort_requires".to_string(),
+        json!({
+            "description": "Configuration for the sort requires codemod",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "enabled": boolean_property(
+                    "Whether the sort requires codemod is enabled",
+                    config.sort_requires.enabled,
+                ),
+            },
+        }),
+    );
+    properties.insert(
+        "normalize_elseif".to_string(),
+        boolean_property(
+            "Whether an `else` block containing only a nested `if` statement is rewritten into an `elseif`",
+            config.normalize_elseif,
+        ),
+    );
+    properties.insert(
+        "space_after_function_names".to_string(),
+        enum_property::<ArgSpaceAfterFunctionNames, _>(
+            "Whether a space should be placed after function names in definitions and calls",
+            config.space_after_function_names,
+        ),
+    );
+    properties.insert(
+        "block_newline_gaps".to_string(),
+        enum_property::<ArgBlockNewlineGaps, _>(
+            "Whether newline gaps at the start and end of blocks should be preserved",
+            config.block_newline_gaps,
+        ),
+    );
+    properties.insert(
+        "skip_statement_kinds".to_string(),
+        json!({
+            "description": "The kinds of statement which are left unformatted, other than correcting their indentation",
+            "type": "array",
+            "uniqueItems": true,
+            "items": {
+                "type": "string",
+                "enum": StatementKind::ALL.iter().map(variant_name).collect::<Vec<_>>(),
+            },
+            "default": config.skip_statement_kinds.iter().map(variant_name).collect::<Vec<_>>(),
+        }),
+    );
+    properties.insert(
+        "if_expression_style".to_string(),
+        enum_property::<ArgIfExpressionStyle, _>(
+            "How Luau if-expressions are broken when they do not fit onto a single line",
+            config.if_expression_style,
+        ),
+    );
+    properties.insert(
+        "return_type_style".to_string(),
+        enum_property::<ArgReturnTypeStyle, _>(
+            "Where Luau function return type annotations are placed",
+            config.return_type_style,
+        ),
+    );
+    properties.insert(
+        "return_parentheses".to_string(),
+        enum_property::<ArgReturnParentheses, _>(
+            "Whether parentheses around returned expressions should be kept",
+            config.return_parentheses,
+        ),
+    );
+    properties.insert(
+        "binary_chain_indent".to_string(),
+        enum_property::<ArgBinaryChainIndent, _>(
+            "How higher-precedence operands are indented when a binary expression chain is broken",
+            config.binary_chain_indent,
+        ),
+    );
+    properties.insert(
+        "last_arg_callback".to_string(),
+        enum_property::<ArgLastArgCallback, _>(
+            "How a call is formatted when its last argument is an anonymous function spanning multiple lines",
+            config.last_arg_callback,
+        ),
+    );
+    properties.insert(
+        "dispatch_table_style".to_string(),
+        enum_property::<ArgDispatchTableStyle, _>(
+            "How tables where every field is keyed and has an anonymous function value are formatted",
+            config.dispatch_table_style,
+        ),
+    );
+    properties.insert(
+        "unary_operator_spacing".to_string(),
+        enum_property::<ArgUnaryOperatorSpacing, _>(
+            "Whether symbolic unary operators, such as `-` and `#`, are followed by a space",
+            config.unary_operator_spacing,
+        ),
+    );
+    properties.insert(
+        "table_element_max_width".to_string(),
+        integer_property(
+            "The maximum width of a single table element before the table is forced onto multiple lines. 0 disables the limit",
+            config.table_element_max_width,
+        ),
+    );
+    properties.insert(
+        "wrap_index_chains".to_string(),
+        boolean_property(
+            "Whether a chain of `.name` indexes which does not fit within the column width is broken before each `.`",
+            config.wrap_index_chains,
+        ),
+    );
+    properties.insert(
+        "nested_table_expansion".to_string(),
+        enum_property::<ArgNestedTableExpansion, _>(
+            "Whether a table being broken over multiple lines causes the tables nested within it to be broken too",
+            config.nested_table_expansion,
+        ),
+    );
+    properties.insert(
+        "assigned_function".to_string(),
+        enum_property::<ArgAssignedFunction, _>(
+            "How an anonymous function spanning multiple lines is formatted when it is the only value of an assignment",
+            config.assigned_function,
+        ),
+    );
+    properties.insert(
+        "single_table_argument".to_string(),
+        enum_property::<ArgSingleTableArgument, _>(
+            "How a call is formatted when its only argument is a table spanning multiple lines",
+            config.single_table_argument,
+        ),
+    );
+    properties.insert(
+        "leading_comment_newline_gap".to_string(),
+        enum_property::<ArgLeadingCommentNewlineGap, _>(
+            "Whether a newline gap is kept between the comments at the start of a block and its first statement",
+            config.leading_comment_newline_gap,
+        ),
+    );
+    properties.insert(
+        "parameter_comments".to_string(),
+        enum_property::<ArgParameterComments, _>(
+            "How comments written between the parameters of a function are handled",
+            config.parameter_comments,
+        ),
+    );
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "StyLua configuration",
+        "description": "Configuration for StyLua, written in a `stylua.toml` or `.stylua.toml` file",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": properties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_has_every_enum_variant() {
+        let schema = config_schema();
+        assert_eq!(
+            schema["properties"]["quote_style"]["enum"],
+            json!([
+                "AutoPreferDouble",
+                "AutoPreferSingle",
+                "ForceDouble",
+                "ForceSingle"
+            ])
+        );
+        assert_eq!(
+            schema["properties"]["quote_style"]["default"],
+            json!("AutoPreferDouble")
+        );
+    }
+
+    // The serialized default configuration has the same fields as a configuration file, so the schema must
+    // describe exactly these fields, with the same defaults
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn test_schema_matches_config() {
+        let schema = config_schema();
+        let properties = schema["properties"].as_object().unwrap();
+        let default_config = serde_json::to_value(Config::default()).unwrap();
+        let default_config = default_config.as_object().unwrap();
+
+        let mut property_names = properties.keys().collect::<Vec<_>>();
+        let mut config_names = default_config.keys().collect::<Vec<_>>();
+        property_names.sort();
+        config_names.sort();
+        assert_eq!(property_names, config_names);
+
+        for (name, default) in default_config {
+            let property = &properties[name];
+            match property["type"].as_str() {
+                Some("object") => {
+                    for (field, default) in default.as_object().unwrap() {
+                        assert_eq!(
+                            &property["properties"][field]["default"], default,
+                            "{}.{}",
+                            name, field
+                        );
+                    }
+                }
+                _ => assert_eq!(&property["default"], default, "{}", name),
+            }
+
+            if let Some(variants) = property["enum"].as_array() {
+                assert!(variants.contains(default), "{}", name);
+            }
+        }
+    }
+}
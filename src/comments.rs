@@ -0,0 +1,66 @@
+//! A pass which only formats comments, leaving all other code exactly as written.
+//! The code is tokenized, and comments are changed in the same way as when formatting the whole file: trailing
+//! whitespace is trimmed from single-line comments and shebangs, and the line endings within multi-line comments are converted
+//! to the configured line endings. All other tokens, including whitespace, are output as they are written in the input.
+
+use full_moon::tokenizer::{Token, TokenType};
+
+use crate::{
+    context::line_ending_character, formatters::general::format_single_line_comment_string,
+    whitespace::tokenize, Config, Error,
+};
+
+pub(crate) fn format_comments(code: &str, config: Config) -> Result<String, Error> {
+    let tokens = tokenize(code, config)?;
+
+    let line_ending = line_ending_character(config.line_endings);
+    let mut output = String::with_capacity(code.len());
+    let mut formatting_disabled = false;
+
+    for token in tokens {
+        match token.token_type() {
+            TokenType::SingleLineComment { comment } => {
+                match comment.trim() {
+                    "stylua: ignore start" => formatting_disabled = true,
+                    "stylua: ignore end" => formatting_disabled = false,
+                    _ => (),
+                }
+
+                if formatting_disabled {
+                    output.push_str(&token.to_string());
+                } else {
+                    output.push_str(
+                        &Token::new(TokenType::SingleLineComment {
+                            comment: format_single_line_comment_string(comment).into(),
+                        })
+                        .to_string(),
+                    );
+                }
+            }
+            TokenType::Shebang { line } if !formatting_disabled => {
+                output.push_str(
+                    &Token::new(TokenType::Shebang {
+                        line: format_single_line_comment_string(line).into(),
+                    })
+                    .to_string(),
+                );
+            }
+            TokenType::MultiLineComment { blocks, comment } if !formatting_disabled => {
+                output.push_str(
+                    &Token::new(TokenType::MultiLineComment {
+                        blocks: *blocks,
+                        comment: comment
+                            .replace("\r\n", "\n")
+                            .replace('\n', &line_ending)
+                            .into(),
+                    })
+                    .to_string(),
+                );
+            }
+            TokenType::Eof => (),
+            _ => output.push_str(&token.to_string()),
+        }
+    }
+
+    Ok(output)
+}
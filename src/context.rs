@@ -1,8 +1,11 @@
 use crate::{
-    shape::Shape, BlockNewlineGaps, CallParenType, CollapseSimpleStatement, Config, IndentType,
-    LineEndings, Range as FormatRange, SpaceAfterFunctionNames,
+    shape::Shape, AssignedFunction, BinaryChainIndent, BlockNewlineGaps, CallParenType,
+    CollapseSimpleStatement, Config, DispatchTableStyle, IndentType, LastArgCallback, LineEndings,
+    NestedTableExpansion, ParameterComments, Range as FormatRange, ReturnParentheses,
+    SingleTableArgument, SpaceAfterFunctionNames, StatementKind, UnaryOperatorSpacing,
 };
 use full_moon::{
+    ast::{Block, LastStmt, Stmt},
     node::Node,
     tokenizer::{Token, TokenType},
 };
@@ -25,6 +28,17 @@ pub struct Context {
     range: Option<FormatRange>,
     /// Whether the formatting has currently been disabled. This should occur when we see the relevant comment.
     formatting_disabled: bool,
+    /// Whether tables should keep the line breaks between their fields from the input.
+    /// This should occur when we see a `-- stylua: keep-breaks` comment before the current node.
+    keep_table_breaks: bool,
+    /// Whether anonymous functions should be expanded, even if they could be collapsed onto a single line.
+    /// This occurs for the values of a dispatch table when [`DispatchTableStyle::Switch`] is set, and is reset
+    /// when formatting the function bodies themselves.
+    expand_functions: bool,
+    /// Whether the table currently being formatted should be expanded, even if it could fit onto a single line.
+    /// This occurs for the tables which are the values of the fields of a multiline table when
+    /// [`NestedTableExpansion::Propagate`] is set, and is reset when formatting the fields of the table itself.
+    expand_table: bool,
 }
 
 impl Context {
@@ -34,6 +48,9 @@ impl Context {
             config,
             range,
             formatting_disabled: false,
+            keep_table_breaks: false,
+            expand_functions: false,
+            expand_table: false,
         }
     }
 
@@ -44,6 +61,8 @@ impl Context {
 
     /// Determines whether we need to toggle whether formatting is enabled or disabled.
     /// Formatting is toggled on/off whenever we see a `-- stylua: ignore start` or `-- stylua: ignore end` comment respectively.
+    /// Also determines whether tables within the node should keep their line breaks, which is the case whenever
+    /// we see a `-- stylua: keep-breaks` comment.
     // To preserve immutability of Context, we return a new Context with the `formatting_disabled` field toggled or left the same
     // where necessary. Context is cheap so this is reasonable to do.
     pub fn check_toggle_formatting(&self, node: &impl Node) -> Self {
@@ -63,6 +82,7 @@ impl Context {
 
         // Load the current formatting disabled state
         let mut formatting_disabled = self.formatting_disabled;
+        let mut keep_table_breaks = false;
 
         // Work through all the lines and update the state as necessary
         for line in comment_lines {
@@ -70,11 +90,14 @@ impl Context {
                 formatting_disabled = true;
             } else if line == "stylua: ignore end" {
                 formatting_disabled = false;
+            } else if line == "stylua: keep-breaks" {
+                keep_table_breaks = true;
             }
         }
 
         Self {
             formatting_disabled,
+            keep_table_breaks,
             ..*self
         }
     }
@@ -127,6 +150,37 @@ impl Context {
         FormatNode::Normal
     }
 
+    /// Whether tables should keep the line breaks between their fields from the input
+    pub fn should_keep_table_breaks(&self) -> bool {
+        self.keep_table_breaks
+    }
+
+    /// Returns a new Context where anonymous functions are always expanded, or not, depending on `expand_functions`
+    pub fn with_expand_functions(&self, expand_functions: bool) -> Self {
+        Self {
+            expand_functions,
+            ..*self
+        }
+    }
+
+    /// Whether anonymous functions should be expanded, even if they could be collapsed onto a single line
+    pub fn should_expand_functions(&self) -> bool {
+        self.expand_functions
+    }
+
+    /// Returns a new Context where the next table is always expanded, or not, depending on `expand_table`
+    pub fn with_expand_table(&self, expand_table: bool) -> Self {
+        Self {
+            expand_table,
+            ..*self
+        }
+    }
+
+    /// Whether the table being formatted should be expanded, even if it could fit onto a single line
+    pub fn should_expand_table(&self) -> bool {
+        self.expand_table
+    }
+
     #[allow(deprecated)]
     pub fn should_omit_string_parens(&self) -> bool {
         self.config().no_call_parentheses
@@ -144,7 +198,17 @@ impl Context {
     pub fn should_collapse_simple_functions(&self) -> bool {
         matches!(
             self.config().collapse_simple_statement,
-            CollapseSimpleStatement::FunctionOnly | CollapseSimpleStatement::Always
+            CollapseSimpleStatement::FunctionOnly
+                | CollapseSimpleStatement::GettersSettersOnly
+                | CollapseSimpleStatement::Always
+        )
+    }
+
+    /// Whether only simple functions which are getters or setters should be collapsed
+    pub fn should_collapse_only_getters_setters(&self) -> bool {
+        matches!(
+            self.config().collapse_simple_statement,
+            CollapseSimpleStatement::GettersSettersOnly
         )
     }
 
@@ -162,6 +226,161 @@ impl Context {
     pub fn should_preserve_trailing_block_newline_gaps(&self) -> bool {
         matches!(self.config().block_newline_gaps, BlockNewlineGaps::Preserve)
     }
+
+    #[cfg(feature = "luau")]
+    pub fn should_break_after_if_expression_keywords(&self) -> bool {
+        matches!(
+            self.config().if_expression_style,
+            crate::IfExpressionStyle::BreakAfterKeywords
+        )
+    }
+
+    #[cfg(feature = "luau")]
+    pub fn should_break_long_return_types(&self) -> bool {
+        matches!(
+            self.config().return_type_style,
+            crate::ReturnTypeStyle::BreakWhenLong
+        )
+    }
+
+    pub fn should_indent_binary_chains_by_precedence(&self) -> bool {
+        matches!(
+            self.config().binary_chain_indent,
+            BinaryChainIndent::Precedence
+        )
+    }
+
+    pub fn should_wrap_last_arg_callback(&self) -> bool {
+        matches!(self.config().last_arg_callback, LastArgCallback::Wrap)
+    }
+
+    pub fn should_format_dispatch_tables_as_switch(&self) -> bool {
+        matches!(
+            self.config().dispatch_table_style,
+            DispatchTableStyle::Switch
+        )
+    }
+
+    pub fn should_hug_single_table_argument(&self) -> bool {
+        matches!(
+            self.config().single_table_argument,
+            SingleTableArgument::Hug
+        )
+    }
+
+    pub fn should_keep_inline_parameter_comments(&self) -> bool {
+        matches!(self.config().parameter_comments, ParameterComments::Inline)
+    }
+
+    pub fn should_wrap_assigned_function(&self) -> bool {
+        matches!(self.config().assigned_function, AssignedFunction::Wrap)
+    }
+
+    pub fn should_propagate_table_expansion(&self) -> bool {
+        matches!(
+            self.config().nested_table_expansion,
+            NestedTableExpansion::Propagate
+        )
+    }
+
+    pub fn should_space_unary_operators(&self) -> bool {
+        matches!(
+            self.config().unary_operator_spacing,
+            UnaryOperatorSpacing::Spaced
+        )
+    }
+
+    pub fn should_keep_return_parentheses(&self) -> bool {
+        matches!(self.config().return_parentheses, ReturnParentheses::Input)
+    }
+
+    /// Whether the statement is of a kind which has been excluded from formatting
+    pub fn should_skip_stmt(&self, stmt: &Stmt) -> bool {
+        let kind = match stmt {
+            Stmt::Assignment(_) => StatementKind::Assignment,
+            Stmt::Do(_) => StatementKind::Do,
+            Stmt::FunctionCall(_) => StatementKind::FunctionCall,
+            Stmt::FunctionDeclaration(_) => StatementKind::FunctionDeclaration,
+            Stmt::GenericFor(_) => StatementKind::GenericFor,
+            Stmt::If(_) => StatementKind::If,
+            Stmt::LocalAssignment(_) => StatementKind::LocalAssignment,
+            Stmt::LocalFunction(_) => StatementKind::LocalFunction,
+            Stmt::NumericFor(_) => StatementKind::NumericFor,
+            Stmt::Repeat(_) => StatementKind::Repeat,
+            Stmt::While(_) => StatementKind::While,
+            #[cfg(any(feature = "luau", feature = "cfxlua"))]
+            Stmt::CompoundAssignment(_) => StatementKind::CompoundAssignment,
+            #[cfg(feature = "luau")]
+            Stmt::ExportedTypeDeclaration(_) | Stmt::TypeDeclaration(_) => {
+                StatementKind::TypeDeclaration
+            }
+            #[cfg(feature = "luau")]
+            Stmt::ExportedTypeFunction(_) | Stmt::TypeFunction(_) => StatementKind::TypeFunction,
+            #[cfg(any(feature = "lua52", feature = "luajit"))]
+            Stmt::Goto(_) => StatementKind::Goto,
+            #[cfg(any(feature = "lua52", feature = "luajit"))]
+            Stmt::Label(_) => StatementKind::Label,
+            _ => return false,
+        };
+
+        self.config().skip_statement_kinds.contains(kind)
+    }
+
+    /// Whether the last statement is of a kind which has been excluded from formatting
+    pub fn should_skip_last_stmt(&self, last_stmt: &LastStmt) -> bool {
+        let kind = match last_stmt {
+            LastStmt::Return(_) => StatementKind::Return,
+            LastStmt::Break(_) => StatementKind::Break,
+            #[cfg(feature = "luau")]
+            LastStmt::Continue(_) => StatementKind::Continue,
+            _ => return false,
+        };
+
+        self.config().skip_statement_kinds.contains(kind)
+    }
+
+    /// Whether the block directly contains a statement of a kind which has been excluded from formatting
+    pub fn block_contains_skipped_stmt(&self, block: &Block) -> bool {
+        if self.config().skip_statement_kinds.is_empty() {
+            return false;
+        }
+
+        block.stmts().any(|stmt| self.should_skip_stmt(stmt))
+            || block
+                .last_stmt()
+                .is_some_and(|last_stmt| self.should_skip_last_stmt(last_stmt))
+    }
+}
+
+/// A directive which changes the base indentation level of the following statements in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentDirective {
+    /// Increase the indentation level by the given amount, from a `-- stylua: indent +N` comment
+    Increase(usize),
+    /// Return to the indentation level of the block, from a `-- stylua: indent reset` comment
+    Reset,
+}
+
+/// Finds the last indentation directive in the leading comments of the node, if present
+pub fn indent_directive(node: &impl Node) -> Option<IndentDirective> {
+    let leading_trivia = node.surrounding_trivia().0;
+    leading_trivia
+        .iter()
+        .filter_map(|trivia| match trivia.token_type() {
+            TokenType::SingleLineComment { comment } => Some(comment),
+            TokenType::MultiLineComment { comment, .. } => Some(comment),
+            _ => None,
+        })
+        .flat_map(|comment| comment.lines().map(|line| line.trim()))
+        .filter_map(|line| match line.strip_prefix("stylua: indent ")?.trim() {
+            "reset" => Some(IndentDirective::Reset),
+            amount => amount
+                .strip_prefix('+')?
+                .parse()
+                .ok()
+                .map(IndentDirective::Increase),
+        })
+        .next_back()
 }
 
 /// Returns the relevant line ending string from the [`LineEndings`] enum
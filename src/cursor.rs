@@ -0,0 +1,51 @@
+//! Maps a cursor position in the input code to the equivalent position in the formatted output.
+//! The input and output are diffed by character, and the cursor is moved along with the text surrounding it.
+//! A cursor inside text which was removed or replaced is placed at the start of its replacement.
+
+use similar::{DiffOp, TextDiff};
+
+/// Maps the cursor, as a byte offset into `code`, to a byte offset into `output`.
+/// Offsets past the end of `code` are treated as the end of the code, and offsets within a character
+/// are treated as the start of that character.
+pub(crate) fn map_cursor(code: &str, output: &str, cursor: usize) -> usize {
+    let mut cursor = cursor.min(code.len());
+    while !code.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+
+    // The diff is indexed by character, rather than by byte
+    let cursor = code[..cursor].chars().count();
+    let diff = TextDiff::from_chars(code, output);
+
+    let new_cursor = diff
+        .ops()
+        .iter()
+        .find_map(|op| match *op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } if (old_index..old_index + len).contains(&cursor) => {
+                Some(new_index + cursor - old_index)
+            }
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            }
+            | DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                ..
+            } if (old_index..old_index + old_len).contains(&cursor) => Some(new_index),
+            _ => None,
+        })
+        // The cursor is at the end of the code
+        .unwrap_or_else(|| output.chars().count());
+
+    output
+        .char_indices()
+        .nth(new_cursor)
+        .map_or(output.len(), |(offset, _)| offset)
+}
@@ -78,6 +78,7 @@ property_choice! {
     (Never, "never"),
     (FunctionOnly, "functiononly"),
     (ConditionalOnly, "conditionalonly"),
+    (GettersSettersOnly, "getterssettersonly"),
     (Always, "always")
 }
 
@@ -87,6 +88,12 @@ property_choice! {
     (False, "false")
 }
 
+property_choice! {
+    NormalizeElseifChoice, "normalize_elseif";
+    (True, "true"),
+    (False, "false")
+}
+
 // Override StyLua config with EditorConfig properties
 fn load(mut config: Config, properties: &Properties) -> Config {
     if let Ok(end_of_line) = properties.get::<EndOfLine>() {
@@ -163,6 +170,9 @@ fn load(mut config: Config, properties: &Properties) -> Config {
             CollapseSimpleStatementChoice::ConditionalOnly => {
                 config.collapse_simple_statement = CollapseSimpleStatement::ConditionalOnly
             }
+            CollapseSimpleStatementChoice::GettersSettersOnly => {
+                config.collapse_simple_statement = CollapseSimpleStatement::GettersSettersOnly
+            }
             CollapseSimpleStatementChoice::Always => {
                 config.collapse_simple_statement = CollapseSimpleStatement::Always
             }
@@ -176,6 +186,12 @@ fn load(mut config: Config, properties: &Properties) -> Config {
             }
         }
     }
+    if let Ok(normalize_elseif) = properties.get::<NormalizeElseifChoice>() {
+        match normalize_elseif {
+            NormalizeElseifChoice::True => config.normalize_elseif = true,
+            NormalizeElseifChoice::False => config.normalize_elseif = false,
+        }
+    }
 
     config
 }
@@ -410,6 +426,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_collapse_simple_statement_getters_setters_only() {
+        let mut properties = Properties::new();
+        properties.insert_raw_for_key("collapse_simple_statement", "GettersSettersOnly");
+        let config = Config::from(&properties);
+        assert_eq!(
+            config.collapse_simple_statement,
+            CollapseSimpleStatement::GettersSettersOnly
+        );
+    }
+
     #[test]
     fn test_collapse_simple_statement_always() {
         let mut properties = Properties::new();
@@ -437,6 +464,14 @@ mod tests {
         assert!(!config.sort_requires.enabled);
     }
 
+    #[test]
+    fn test_normalize_elseif_enabled() {
+        let mut properties = Properties::new();
+        properties.insert_raw_for_key("normalize_elseif", "true");
+        let config = Config::from(&properties);
+        assert!(config.normalize_elseif);
+    }
+
     #[test]
     fn test_invalid_properties() {
         let mut properties = Properties::new();
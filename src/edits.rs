@@ -0,0 +1,192 @@
+//! Computes the edits which transform input code into its formatted output, for editors and language servers
+//! which apply changes to a document rather than replacing it.
+//! The input and output are diffed by character, so an edit never starts or ends within a multi-byte character.
+
+use similar::{DiffOp, TextDiff};
+#[cfg(feature = "lsp")]
+use std::convert::TryInto;
+
+/// A position within code, used by [`Edit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EditPosition {
+    /// The line, starting at 0
+    pub line: usize,
+    /// The offset within the line in UTF-16 code units, starting at 0, as used by the Language Server Protocol
+    pub character: usize,
+}
+
+/// A change to the input code, replacing the text between `start` and `end` with `new_text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The start of the replaced text
+    pub start: EditPosition,
+    /// The end of the replaced text, which is the same as `start` when text is inserted
+    pub end: EditPosition,
+    /// The text to replace with, which is empty when text is removed
+    pub new_text: String,
+}
+
+#[cfg(feature = "lsp")]
+impl From<EditPosition> for lsp_types::Position {
+    fn from(position: EditPosition) -> Self {
+        lsp_types::Position::new(
+            position.line.try_into().expect("line fits into u32"),
+            position
+                .character
+                .try_into()
+                .expect("character fits into u32"),
+        )
+    }
+}
+
+#[cfg(feature = "lsp")]
+impl From<Edit> for lsp_types::TextEdit {
+    fn from(edit: Edit) -> Self {
+        lsp_types::TextEdit::new(
+            lsp_types::Range::new(edit.start.into(), edit.end.into()),
+            edit.new_text,
+        )
+    }
+}
+
+/// The position of each character in the code, followed by the position of the end of the code.
+/// Line endings are `\n` or a lone `\r`. The `\r` of a `\r\n` line ending is treated as the end of its line.
+fn char_positions(code: &str) -> Vec<EditPosition> {
+    let mut positions = Vec::with_capacity(code.len() + 1);
+    let mut position = EditPosition {
+        line: 0,
+        character: 0,
+    };
+    let mut chars = code.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        positions.push(position);
+        match char {
+            '\n' => {
+                position.line += 1;
+                position.character = 0;
+            }
+            '\r' if chars.peek() != Some(&'\n') => {
+                position.line += 1;
+                position.character = 0;
+            }
+            _ => position.character += char.len_utf16(),
+        }
+    }
+
+    positions.push(position);
+    positions
+}
+
+pub(crate) fn compute_edits(code: &str, output: &str) -> Vec<Edit> {
+    let code_positions = char_positions(code);
+    let output_chars: Vec<char> = output.chars().collect();
+    let new_text = |index: usize, len: usize| output_chars[index..index + len].iter().collect();
+
+    TextDiff::from_chars(code, output)
+        .ops()
+        .iter()
+        .filter_map(|op| {
+            let (old_index, old_len, new_text) = match *op {
+                DiffOp::Equal { .. } => return None,
+                DiffOp::Delete {
+                    old_index, old_len, ..
+                } => (old_index, old_len, String::new()),
+                DiffOp::Insert {
+                    old_index,
+                    new_index,
+                    new_len,
+                } => (old_index, 0, new_text(new_index, new_len)),
+                DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                } => (old_index, old_len, new_text(new_index, new_len)),
+            };
+
+            Some(Edit {
+                start: code_positions[old_index],
+                end: code_positions[old_index + old_len],
+                new_text,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: usize, character: usize) -> EditPosition {
+        EditPosition { line, character }
+    }
+
+    #[test]
+    fn test_compute_edits() {
+        let edits = compute_edits("local  x = '😀'\nlocal y", "local x = \"😀\"\nlocal y\n");
+        assert_eq!(
+            edits,
+            [
+                Edit {
+                    start: position(0, 6),
+                    end: position(0, 7),
+                    new_text: String::new(),
+                },
+                Edit {
+                    start: position(0, 11),
+                    end: position(0, 12),
+                    new_text: "\"".to_string(),
+                },
+                // The emoji is 2 code units in UTF-16
+                Edit {
+                    start: position(0, 14),
+                    end: position(0, 15),
+                    new_text: "\"".to_string(),
+                },
+                Edit {
+                    start: position(1, 7),
+                    end: position(1, 7),
+                    new_text: "\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_positions_line_endings() {
+        assert_eq!(
+            char_positions("a\r\nb\rc\n"),
+            [
+                position(0, 0),
+                position(0, 1),
+                position(0, 2),
+                position(1, 0),
+                position(1, 1),
+                position(2, 0),
+                position(2, 1),
+                position(3, 0),
+            ]
+        );
+    }
+
+    #[cfg(feature = "lsp")]
+    #[test]
+    fn test_edit_into_lsp_text_edit() {
+        let edit = Edit {
+            start: position(1, 2),
+            end: position(3, 4),
+            new_text: "x".to_string(),
+        };
+        assert_eq!(
+            lsp_types::TextEdit::from(edit),
+            lsp_types::TextEdit::new(
+                lsp_types::Range::new(
+                    lsp_types::Position::new(1, 2),
+                    lsp_types::Position::new(3, 4)
+                ),
+                "x".to_string()
+            )
+        );
+    }
+}
@@ -204,6 +204,25 @@ pub fn hang_at_equals_due_to_comments(
     (equal_token, expression)
 }
 
+/// Whether the expression list is a single anonymous function spanning multiple lines, which should be placed on its own
+/// line after the equals token. The formatted expressions are checked, so that a function wrapped in redundant
+/// parentheses is treated the same as one without
+fn should_wrap_function(
+    ctx: &Context,
+    expressions: &Punctuated<Expression>,
+    formatted_expressions: &Punctuated<Expression>,
+) -> bool {
+    ctx.should_wrap_assigned_function()
+        && expressions.len() == 1
+        && formatted_expressions
+            .iter()
+            .next()
+            .is_some_and(|expression| {
+                matches!(expression, Expression::Function(_))
+                    && trivia_util::spans_multiple_lines(&strip_trivia(expression))
+            })
+}
+
 /// Attempts different formatting tactics on an expression list being assigned (`= foo, bar`), to find the best
 /// formatting output.
 fn attempt_assignment_tactics(
@@ -294,6 +313,13 @@ fn attempt_assignment_tactics(
         let expr_list = format_punctuated(ctx, expressions, shape, format_expression);
         let formatting_shape = shape.take_first_line(&strip_trailing_trivia(&expr_list));
 
+        if should_wrap_function(ctx, expressions, &expr_list) {
+            let hanging_equal_token = hang_equal_token(ctx, &equal_token, shape, true);
+            let hanging_shape = shape.reset().increment_additional_indent();
+            let expr_list = format_punctuated(ctx, expressions, hanging_shape, format_expression);
+            return (expr_list, hanging_equal_token);
+        }
+
         // See if we can hang the expression, and if we can, check whether hanging or formatting normally is nicer
         if trivia_util::can_hang_expression(expression) {
             // Create an example hanging the expression - we need to create a new context so that we don't overwrite it
@@ -375,10 +401,14 @@ pub fn format_assignment_no_trivia(
         + (strip_leading_trivia(&var_list).to_string().len()
             + 3
             + strip_trailing_trivia(&expr_list).to_string().len());
-    if contains_comments || singleline_shape.over_budget() {
+    if contains_comments
+        || singleline_shape.over_budget()
+        || should_wrap_function(ctx, assignment.expressions(), &expr_list)
+    {
         // We won't attempt anything else with the var_list. Format it normally
         var_list = try_format_punctuated(ctx, assignment.variables(), shape, format_var, Some(1));
-        let shape = shape + (strip_leading_trivia(&var_list).to_string().len() + 3);
+        // The var list may have been broken over multiple lines, in which case the expressions follow its last line
+        let shape = shape.take_last_line(&strip_leading_trivia(&var_list)) + 3;
 
         let (new_expr_list, new_equal_token) =
             attempt_assignment_tactics(ctx, assignment.expressions(), shape, equal_token);
@@ -536,7 +566,10 @@ pub fn format_local_assignment_no_trivia(
                 + type_specifier_len
                 + strip_trailing_trivia(&expr_list).to_string().len());
 
-        if contains_comments || singleline_shape.over_budget() {
+        if contains_comments
+            || singleline_shape.over_budget()
+            || should_wrap_function(ctx, assignment.expressions(), &expr_list)
+        {
             // We won't attempt anything else with the name_list. Format it normally
             name_list = try_format_punctuated(
                 ctx,
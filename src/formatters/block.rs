@@ -1,7 +1,10 @@
 #[cfg(feature = "luau")]
 use crate::formatters::general::format_symbol;
 use crate::{
-    context::{create_indent_trivia, create_newline_trivia, Context, FormatNode},
+    context::{
+        create_indent_trivia, create_newline_trivia, indent_directive, Context, FormatNode,
+        IndentDirective,
+    },
     fmt_symbol,
     formatters::{
         assignment::{hang_equal_token, hang_punctuated_list},
@@ -17,16 +20,20 @@ use crate::{
         },
     },
     shape::Shape,
+    warnings::{push_warning, WarningKind},
+    LeadingCommentNewlineGap,
 };
 use full_moon::ast::{
-    punctuated::Punctuated, Block, Expression, LastStmt, Prefix, Return, Stmt, Var,
+    punctuated::Punctuated, span::ContainedSpan, Block, Expression, LastStmt, Prefix, Return, Stmt,
+    Var,
 };
+use full_moon::node::Node;
 use full_moon::tokenizer::TokenType;
 use full_moon::tokenizer::{Token, TokenReference};
 
 macro_rules! update_first_token {
-    ($enum:ident, $var:ident, $token:expr, $update_method:ident) => {{
-        let leading_trivia = trivia_remove_leading_newlines($token.leading_trivia().collect());
+    ($enum:ident, $var:ident, $token:expr, $update_method:ident, $update_trivia:expr) => {{
+        let leading_trivia = $update_trivia($token.leading_trivia().collect());
         let new_token = $token.update_leading_trivia(FormatTriviaType::Replace(leading_trivia));
         Stmt::$enum($var.$update_method(new_token))
     }};
@@ -195,12 +202,53 @@ pub fn format_return(ctx: &Context, return_node: &Return, shape: Shape) -> Retur
             singleline_returns
         };
 
+        let formatted_returns = match ctx.should_keep_return_parentheses() {
+            true => formatted_returns
+                .into_pairs()
+                .zip(returns)
+                .map(|(pair, original)| {
+                    pair.map(|expression| restore_parentheses(ctx, original, expression, shape))
+                })
+                .collect(),
+            false => formatted_returns,
+        };
+
         Return::new()
             .with_token(token)
             .with_returns(formatted_returns)
     }
 }
 
+/// Wraps the formatted expression in parentheses again if the original expression was parenthesised,
+/// but they were removed during formatting
+fn restore_parentheses(
+    ctx: &Context,
+    original: &Expression,
+    formatted: Expression,
+    shape: Shape,
+) -> Expression {
+    match (original, &formatted) {
+        (Expression::Parentheses { contained, .. }, formatted)
+            if !matches!(formatted, Expression::Parentheses { .. }) =>
+        {
+            let (start_parens, end_parens) = contained.tokens();
+            let leading_trivia = formatted.leading_trivia();
+            let trailing_trivia = formatted.trailing_trivia();
+
+            Expression::Parentheses {
+                contained: ContainedSpan::new(
+                    fmt_symbol!(ctx, start_parens, "(", shape)
+                        .update_leading_trivia(FormatTriviaType::Replace(leading_trivia)),
+                    fmt_symbol!(ctx, end_parens, ")", shape)
+                        .update_trailing_trivia(FormatTriviaType::Replace(trailing_trivia)),
+                ),
+                expression: Box::new(strip_trivia(formatted)),
+            }
+        }
+        _ => formatted,
+    }
+}
+
 // Only formats a block within the last stmt
 fn format_last_stmt_block(ctx: &Context, last_stmt: &LastStmt, shape: Shape) -> LastStmt {
     match last_stmt {
@@ -253,6 +301,10 @@ pub fn format_last_stmt(ctx: &Context, last_stmt: &LastStmt, shape: Shape) -> La
         return format_last_stmt_block(ctx, last_stmt, shape);
     }
 
+    if ctx.should_skip_last_stmt(last_stmt) {
+        return trivia_util::format_skipped_last_stmt(ctx, last_stmt, shape);
+    }
+
     // Calculate trivia
     let leading_trivia = FormatTriviaType::Append(vec![create_indent_trivia(ctx, shape)]);
     let trailing_trivia = FormatTriviaType::Append(vec![create_newline_trivia(ctx)]);
@@ -271,10 +323,43 @@ fn trivia_remove_leading_newlines(trivia: Vec<&Token>) -> Vec<Token> {
         .collect()
 }
 
-fn prefix_remove_leading_newlines(prefix: &Prefix) -> Prefix {
+/// Updates the newline gap between the last comment in the leading trivia of a block's first statement and the
+/// statement itself. The newline directly after the comment terminates it, so any further newlines form the gap
+fn trivia_update_comment_newline_gap(ctx: &Context, mut trivia: Vec<Token>) -> Vec<Token> {
+    let Some(comment_index) = trivia.iter().rposition(trivia_util::trivia_is_comment) else {
+        return trivia;
+    };
+    let mut newlines = (comment_index + 1..trivia.len())
+        .filter(|index| trivia_util::trivia_is_newline(&trivia[*index]));
+    let Some(terminator_index) = newlines.next() else {
+        return trivia;
+    };
+    let gap: Vec<usize> = newlines.collect();
+
+    match ctx.config().leading_comment_newline_gap {
+        LeadingCommentNewlineGap::Preserve => (),
+        LeadingCommentNewlineGap::Never => {
+            for index in gap.into_iter().rev() {
+                trivia.remove(index);
+            }
+        }
+        LeadingCommentNewlineGap::Always => {
+            if gap.is_empty() {
+                trivia.insert(terminator_index + 1, create_newline_trivia(ctx));
+            }
+        }
+    }
+
+    trivia
+}
+
+fn prefix_update_leading_trivia(
+    prefix: &Prefix,
+    update_trivia: &dyn Fn(Vec<&Token>) -> Vec<Token>,
+) -> Prefix {
     match prefix {
         Prefix::Name(token) => {
-            let leading_trivia = trivia_remove_leading_newlines(token.leading_trivia().collect());
+            let leading_trivia = update_trivia(token.leading_trivia().collect());
             Prefix::Name(token.update_leading_trivia(FormatTriviaType::Replace(leading_trivia)))
         }
         Prefix::Expression(expr) => Prefix::Expression(match &**expr {
@@ -283,8 +368,7 @@ fn prefix_remove_leading_newlines(prefix: &Prefix) -> Prefix {
                 expression,
             } => {
                 let (start_parens, end_parens) = contained.tokens();
-                let leading_trivia =
-                    trivia_remove_leading_newlines(start_parens.leading_trivia().collect());
+                let leading_trivia = update_trivia(start_parens.leading_trivia().collect());
                 Box::new(Expression::Parentheses {
                     contained: full_moon::ast::span::ContainedSpan::new(
                         start_parens
@@ -303,28 +387,33 @@ fn prefix_remove_leading_newlines(prefix: &Prefix) -> Prefix {
     }
 }
 
-fn var_remove_leading_newline(var: Var) -> Var {
+fn var_update_leading_trivia(var: Var, update_trivia: &dyn Fn(Vec<&Token>) -> Vec<Token>) -> Var {
     match var {
         Var::Name(token) => {
-            let leading_trivia = trivia_remove_leading_newlines(token.leading_trivia().collect());
+            let leading_trivia = update_trivia(token.leading_trivia().collect());
             Var::Name(token.update_leading_trivia(FormatTriviaType::Replace(leading_trivia)))
         }
         Var::Expression(var_expr) => {
-            let prefix = prefix_remove_leading_newlines(var_expr.prefix());
+            let prefix = prefix_update_leading_trivia(var_expr.prefix(), update_trivia);
             Var::Expression(Box::new(var_expr.with_prefix(prefix)))
         }
         other => panic!("unknown node {:?}", other),
     }
 }
 
-fn stmt_remove_leading_newlines(stmt: Stmt) -> Stmt {
+fn stmt_update_leading_trivia(
+    stmt: Stmt,
+    update_trivia: &dyn Fn(Vec<&Token>) -> Vec<Token>,
+) -> Stmt {
     match stmt {
         Stmt::Assignment(assignment) => {
             let mut var_list = Punctuated::new();
 
             for (idx, pair) in assignment.variables().pairs().enumerate() {
                 if idx == 0 {
-                    let pair = pair.to_owned().map(var_remove_leading_newline);
+                    let pair = pair
+                        .to_owned()
+                        .map(|var| var_update_leading_trivia(var, update_trivia));
                     var_list.push(pair);
                 } else {
                     var_list.push(pair.to_owned());
@@ -334,10 +423,16 @@ fn stmt_remove_leading_newlines(stmt: Stmt) -> Stmt {
             Stmt::Assignment(assignment.with_variables(var_list))
         }
         Stmt::Do(do_block) => {
-            update_first_token!(Do, do_block, do_block.do_token(), with_do_token)
+            update_first_token!(
+                Do,
+                do_block,
+                do_block.do_token(),
+                with_do_token,
+                update_trivia
+            )
         }
         Stmt::FunctionCall(function_call) => {
-            let prefix = prefix_remove_leading_newlines(function_call.prefix());
+            let prefix = prefix_update_leading_trivia(function_call.prefix(), update_trivia);
             Stmt::FunctionCall(function_call.with_prefix(prefix))
         }
         Stmt::FunctionDeclaration(function_declaration) => {
@@ -345,42 +440,54 @@ fn stmt_remove_leading_newlines(stmt: Stmt) -> Stmt {
                 FunctionDeclaration,
                 function_declaration,
                 function_declaration.function_token(),
-                with_function_token
+                with_function_token,
+                update_trivia
             )
         }
         Stmt::GenericFor(generic_for) => update_first_token!(
             GenericFor,
             generic_for,
             generic_for.for_token(),
-            with_for_token
+            with_for_token,
+            update_trivia
         ),
         Stmt::If(if_block) => {
-            update_first_token!(If, if_block, if_block.if_token(), with_if_token)
+            update_first_token!(
+                If,
+                if_block,
+                if_block.if_token(),
+                with_if_token,
+                update_trivia
+            )
         }
         Stmt::LocalAssignment(local_assignment) => update_first_token!(
             LocalAssignment,
             local_assignment,
             local_assignment.local_token(),
-            with_local_token
+            with_local_token,
+            update_trivia
         ),
         Stmt::LocalFunction(local_function) => update_first_token!(
             LocalFunction,
             local_function,
             local_function.local_token(),
-            with_local_token
+            with_local_token,
+            update_trivia
         ),
         Stmt::NumericFor(numeric_for) => update_first_token!(
             NumericFor,
             numeric_for,
             numeric_for.for_token(),
-            with_for_token
+            with_for_token,
+            update_trivia
         ),
         Stmt::Repeat(repeat_block) => {
             update_first_token!(
                 Repeat,
                 repeat_block,
                 repeat_block.repeat_token(),
-                with_repeat_token
+                with_repeat_token,
+                update_trivia
             )
         }
         Stmt::While(while_block) => {
@@ -388,12 +495,14 @@ fn stmt_remove_leading_newlines(stmt: Stmt) -> Stmt {
                 While,
                 while_block,
                 while_block.while_token(),
-                with_while_token
+                with_while_token,
+                update_trivia
             )
         }
         #[cfg(feature = "luau")]
         Stmt::CompoundAssignment(compound_assignment) => {
-            let lhs = var_remove_leading_newline(compound_assignment.lhs().to_owned());
+            let lhs =
+                var_update_leading_trivia(compound_assignment.lhs().to_owned(), update_trivia);
             Stmt::CompoundAssignment(compound_assignment.with_lhs(lhs))
         }
 
@@ -402,49 +511,68 @@ fn stmt_remove_leading_newlines(stmt: Stmt) -> Stmt {
             ExportedTypeDeclaration,
             exported_type_declaration,
             exported_type_declaration.export_token(),
-            with_export_token
+            with_export_token,
+            update_trivia
         ),
         #[cfg(feature = "luau")]
         Stmt::TypeDeclaration(type_declaration) => update_first_token!(
             TypeDeclaration,
             type_declaration,
             type_declaration.type_token(),
-            with_type_token
+            with_type_token,
+            update_trivia
         ),
         #[cfg(feature = "luau")]
         Stmt::ExportedTypeFunction(exported_type_function) => update_first_token!(
             ExportedTypeFunction,
             exported_type_function,
             exported_type_function.export_token(),
-            with_export_token
+            with_export_token,
+            update_trivia
         ),
         #[cfg(feature = "luau")]
         Stmt::TypeFunction(type_function) => update_first_token!(
             TypeFunction,
             type_function,
             type_function.type_token(),
-            with_type_token
+            with_type_token,
+            update_trivia
         ),
         #[cfg(any(feature = "lua52", feature = "luajit"))]
-        Stmt::Goto(goto) => update_first_token!(Goto, goto, goto.goto_token(), with_goto_token),
+        Stmt::Goto(goto) => update_first_token!(
+            Goto,
+            goto,
+            goto.goto_token(),
+            with_goto_token,
+            update_trivia
+        ),
         #[cfg(any(feature = "lua52", feature = "luajit"))]
         Stmt::Label(label) => {
-            update_first_token!(Label, label, label.left_colons(), with_left_colons)
+            update_first_token!(
+                Label,
+                label,
+                label.left_colons(),
+                with_left_colons,
+                update_trivia
+            )
         }
         other => panic!("unknown node {:?}", other),
     }
 }
 
-fn last_stmt_remove_leading_newlines(last_stmt: LastStmt) -> LastStmt {
+fn last_stmt_update_leading_trivia(
+    last_stmt: LastStmt,
+    update_trivia: &dyn Fn(Vec<&Token>) -> Vec<Token>,
+) -> LastStmt {
     match last_stmt {
         LastStmt::Break(token) => {
-            let leading_trivia = trivia_remove_leading_newlines(token.leading_trivia().collect());
+            let leading_trivia = update_trivia(token.leading_trivia().collect());
             LastStmt::Break(token.update_leading_trivia(FormatTriviaType::Replace(leading_trivia)))
         }
         LastStmt::Return(return_node) => {
             let token = return_node
                 .token()
-                .update_leading_trivia(FormatTriviaType::Replace(trivia_remove_leading_newlines(
+                .update_leading_trivia(FormatTriviaType::Replace(update_trivia(
                     return_node.token().leading_trivia().collect(),
                 )));
 
@@ -452,7 +580,7 @@ fn last_stmt_remove_leading_newlines(last_stmt: LastStmt) -> LastStmt {
         }
         #[cfg(feature = "luau")]
         LastStmt::Continue(token) => {
-            let leading_trivia = trivia_remove_leading_newlines(token.leading_trivia().collect());
+            let leading_trivia = update_trivia(token.leading_trivia().collect());
             LastStmt::Continue(
                 token.update_leading_trivia(FormatTriviaType::Replace(leading_trivia)),
             )
@@ -535,31 +663,73 @@ fn check_stmt_requires_semicolon(
         }
 }
 
+/// Applies any `-- stylua: indent` directive before the node to the current indentation offset
+fn update_indent_offset(indent_offset: usize, node: &impl Node) -> usize {
+    match indent_directive(node) {
+        Some(IndentDirective::Increase(amount)) => indent_offset + amount,
+        Some(IndentDirective::Reset) => 0,
+        None => indent_offset,
+    }
+}
+
+/// Records a warning if the semicolon being removed has comments, as they will be moved to the end of the statement
+fn warn_if_semicolon_comments_moved(semi: &TokenReference) {
+    if semi
+        .leading_trivia()
+        .chain(semi.trailing_trivia())
+        .any(trivia_util::trivia_is_comment)
+    {
+        push_warning(WarningKind::CommentMoved, semi.start_position());
+    }
+}
+
 /// Formats a block node. Note: the given shape to the block formatter should already be at the correct indentation level
 pub fn format_block(ctx: &Context, block: &Block, shape: Shape) -> Block {
     let mut ctx = *ctx;
     let mut formatted_statements: Vec<(Stmt, Option<TokenReference>)> = Vec::new();
-    let mut remove_next_stmt_leading_newlines = !ctx.should_preserve_leading_block_newline_gaps();
+    let remove_leading_newlines = !ctx.should_preserve_leading_block_newline_gaps();
+    let update_leading_trivia = move |trivia: Vec<&Token>| {
+        let trivia = match remove_leading_newlines {
+            true => trivia_remove_leading_newlines(trivia),
+            false => trivia.into_iter().cloned().collect(),
+        };
+        trivia_update_comment_newline_gap(&ctx, trivia)
+    };
+    let mut update_next_stmt_leading_trivia = remove_leading_newlines
+        || ctx.config().leading_comment_newline_gap != LeadingCommentNewlineGap::Preserve;
     let mut stmt_iterator = block.stmts_with_semicolon().peekable();
+    // The additional indentation applied to statements through `-- stylua: indent` directives
+    let mut indent_offset = 0;
 
     while let Some((stmt, semi)) = stmt_iterator.next() {
         ctx = ctx.check_toggle_formatting(stmt);
+        indent_offset = update_indent_offset(indent_offset, stmt);
 
-        let shape = shape.reset();
+        let shape = shape
+            .with_indent(shape.indent().add_block_indent_level(indent_offset))
+            .reset();
+        let input_end_position = stmt.end_position();
         let mut stmt = format_stmt(&ctx, stmt, shape);
 
-        // If this is the first stmt, and leading newlines should be removed, then remove them
-        if remove_next_stmt_leading_newlines {
+        // If this is the first stmt, then remove its leading newlines and update the gap after its comments if necessary
+        if update_next_stmt_leading_trivia {
             if let FormatNode::Normal = ctx.should_format_node(&stmt) {
-                stmt = stmt_remove_leading_newlines(stmt);
+                stmt = stmt_update_leading_trivia(stmt, &update_leading_trivia);
             }
-            remove_next_stmt_leading_newlines = false;
+            update_next_stmt_leading_trivia = false;
         }
 
         // If we have a semicolon, we need to push all the trailing trivia from the statement
         // and move it to the end of the semicolon
         let semicolon = match check_stmt_requires_semicolon(&stmt, stmt_iterator.peek()) {
             true => {
+                push_warning(
+                    WarningKind::SemicolonPreserved,
+                    semi.as_ref()
+                        .and_then(|semi| semi.start_position())
+                        .or(input_end_position),
+                );
+
                 let (updated_stmt, trivia) = trivia_util::get_stmt_trailing_trivia(stmt);
                 stmt = updated_stmt;
                 Some(
@@ -574,6 +744,8 @@ pub fn format_block(ctx: &Context, block: &Block, shape: Shape) -> Block {
                 Some(semi) => {
                     // We used to have a semicolon, but now we are removing it
                     // We want to keep any old comments on the semicolon token, otherwise we will lose it
+                    warn_if_semicolon_comments_moved(semi);
+
                     // Move the comments to the end of the stmt, but before the newline token
                     // TODO: this is a bit of a hack - we should probably move newline appending to this function
                     let trivia = trivia_util::get_stmt_trailing_trivia(stmt.to_owned())
@@ -612,14 +784,17 @@ pub fn format_block(ctx: &Context, block: &Block, shape: Shape) -> Block {
     let formatted_last_stmt = match block.last_stmt_with_semicolon() {
         Some((last_stmt, semi)) => {
             ctx = ctx.check_toggle_formatting(last_stmt);
+            indent_offset = update_indent_offset(indent_offset, last_stmt);
 
-            let shape = shape.reset();
+            let shape = shape
+                .with_indent(shape.indent().add_block_indent_level(indent_offset))
+                .reset();
             let mut last_stmt = format_last_stmt(&ctx, last_stmt, shape);
-            // If this is the first stmt, and leading newlines should be removed, then remove them
-            if remove_next_stmt_leading_newlines
+            // If this is the first stmt, then remove its leading newlines and update the gap after its comments if necessary
+            if update_next_stmt_leading_trivia
                 && matches!(ctx.should_format_node(&last_stmt), FormatNode::Normal)
             {
-                last_stmt = last_stmt_remove_leading_newlines(last_stmt);
+                last_stmt = last_stmt_update_leading_trivia(last_stmt, &update_leading_trivia);
             }
 
             // LastStmt will never need a semicolon
@@ -627,6 +802,8 @@ pub fn format_block(ctx: &Context, block: &Block, shape: Shape) -> Block {
             let semicolon = match semi {
                 Some(semi) => {
                     // Append semicolon trailing trivia to the end, but before the newline
+                    warn_if_semicolon_comments_moved(semi);
+
                     // TODO: this is a bit of a hack - we should probably move newline appending to this function
                     let trivia = last_stmt
                         .trailing_trivia()
@@ -533,11 +533,13 @@ pub fn format_suffix(
 
 /// Formats and else if expression onto a single line.
 /// This function does not take into account for comments
+/// If `force_newline` is set, the expression is instead placed on a new line after the `then` token.
 #[cfg(feature = "luau")]
 fn format_else_if_expression_singleline(
     ctx: &Context,
     else_if_expression: &ElseIfExpression,
     shape: Shape,
+    force_newline: bool,
 ) -> ElseIfExpression {
     let else_if_token = fmt_symbol!(ctx, else_if_expression.else_if_token(), "elseif ", shape);
     let else_if_condition = remove_condition_parentheses(else_if_expression.condition().to_owned());
@@ -547,6 +549,7 @@ fn format_else_if_expression_singleline(
         else_if_expression.then_token(),
         else_if_expression.expression(),
         shape.take_first_line(&else_if_condition) + 13, // 13 = "elseif " + " then ",
+        force_newline,
     );
 
     // Add a space before the then token
@@ -561,12 +564,14 @@ fn format_else_if_expression_singleline(
 
 /// Formats a `<token> <expr>` sequence, such as `then <expr>` or `else <expr>`.
 /// In particular, this handles when the <expr> has to be formatted onto multiple lines (either due to comments, or going over width)
+/// If `force_newline` is set, the <expr> is always placed on a new line after the token.
 #[cfg(feature = "luau")]
 fn format_token_expression_sequence(
     ctx: &Context,
     token: &TokenReference,
     expression: &Expression,
     shape: Shape,
+    force_newline: bool,
 ) -> (TokenReference, Expression) {
     const SPACE_LEN: usize = " ".len();
     let formatted_token = format_token_reference(ctx, token, shape);
@@ -575,13 +580,15 @@ fn format_token_expression_sequence(
     let formatted_expression =
         format_expression(ctx, expression, shape.add_width(token_width + SPACE_LEN));
 
-    let requires_multiline_expression = shape.take_first_line(&formatted_expression).over_budget()
-        || token.has_trailing_comments(CommentSearch::All)
+    let contains_comments = token.has_trailing_comments(CommentSearch::All)
         || trivia_util::contains_comments(
             expression.update_trailing_trivia(FormatTriviaType::Replace(vec![])),
         ); // Remove trailing trivia (comments) before checking, as they shouldn't have an impact
+    let requires_multiline_expression =
+        shape.take_first_line(&formatted_expression).over_budget() || contains_comments;
 
-    let newline_after_token = token.has_trailing_comments(CommentSearch::Single)
+    let newline_after_token = force_newline
+        || token.has_trailing_comments(CommentSearch::Single)
         || expression.has_leading_comments(CommentSearch::Single);
 
     let token = match newline_after_token {
@@ -596,23 +603,29 @@ fn format_token_expression_sequence(
         }
     };
 
-    let expression = match requires_multiline_expression {
-        true => match newline_after_token {
-            true => {
-                let shape = shape.reset().increment_additional_indent();
-                hang_expression(ctx, expression, shape, calculate_hang_level(expression))
-                    .update_leading_trivia(FormatTriviaType::Append(vec![create_indent_trivia(
-                        ctx, shape,
-                    )]))
-            }
-            false => hang_expression(
+    let expression = match newline_after_token {
+        true => {
+            let shape = shape.reset().increment_additional_indent();
+            let formatted_expression = format_expression(ctx, expression, shape);
+            let expression = match contains_comments
+                || shape.take_first_line(&formatted_expression).over_budget()
+            {
+                true => hang_expression(ctx, expression, shape, calculate_hang_level(expression)),
+                false => formatted_expression,
+            };
+            expression.update_leading_trivia(FormatTriviaType::Append(vec![create_indent_trivia(
+                ctx, shape,
+            )]))
+        }
+        false => match requires_multiline_expression {
+            true => hang_expression(
                 ctx,
                 expression,
                 shape.add_width(token_width + SPACE_LEN),
                 calculate_hang_level(expression),
             ),
+            false => formatted_expression,
         },
-        false => formatted_expression,
     };
 
     (token, expression)
@@ -643,6 +656,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
                         ctx,
                         else_if_expression,
                         shape.with_infinite_width(),
+                        false,
                     )
                 })
                 .collect::<Vec<_>>()
@@ -687,6 +701,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
         || trivia_util::spans_multiple_lines(&singleline_else_expression);
 
     if require_multiline_expression {
+        let break_after_keywords = ctx.should_break_after_if_expression_keywords();
         let condition = hang_expression_trailing_newline(
             ctx,
             if_expression.condition(),
@@ -701,6 +716,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
             if_expression.then_token(),
             if_expression.if_expression(),
             hanging_shape,
+            break_after_keywords,
         );
 
         // Indent the then token
@@ -721,6 +737,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
                             ctx,
                             else_if_expression,
                             hanging_shape,
+                            break_after_keywords,
                         );
                         let singleline_shape = hanging_shape.take_first_line(&singleline_else_if);
 
@@ -765,6 +782,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
                                 else_if_expression.then_token(),
                                 else_if_expression.expression(),
                                 hanging_shape,
+                                break_after_keywords,
                             );
 
                             let then_token =
@@ -794,6 +812,7 @@ fn format_if_expression(ctx: &Context, if_expression: &IfExpression, shape: Shap
             if_expression.else_token(),
             if_expression.else_expression(),
             hanging_shape + 5, // 5 = "else "
+            break_after_keywords,
         );
 
         // Put the else on a new line
@@ -882,6 +901,30 @@ pub fn format_var(ctx: &Context, var: &Var, shape: Shape) -> Var {
     }
 }
 
+/// Whether every suffix of the VarExpression is a `.name` index, i.e. `a.b.c`
+fn is_index_chain(var_expression: &VarExpression) -> bool {
+    var_expression
+        .suffixes()
+        .all(|suffix| matches!(suffix, Suffix::Index(Index::Dot { .. })))
+}
+
+/// Breaks a chain of `.name` indexes before each `.`, placing every index on its own line,
+/// indented one level further than the prefix
+fn hang_index_chain(ctx: &Context, var_expression: &VarExpression, shape: Shape) -> VarExpression {
+    let prefix = format_prefix(ctx, var_expression.prefix(), shape);
+    let hanging_shape = shape.reset().increment_additional_indent();
+
+    let suffixes = var_expression
+        .suffixes()
+        .map(|suffix| {
+            let suffix = format_suffix(ctx, suffix, hanging_shape, FunctionCallNextNode::None);
+            prepend_newline_indent(ctx, &suffix, hanging_shape)
+        })
+        .collect();
+
+    VarExpression::new(prefix).with_suffixes(suffixes)
+}
+
 pub fn format_var_expression(
     ctx: &Context,
     var_expression: &VarExpression,
@@ -895,19 +938,41 @@ pub fn format_var_expression(
             .with_suffixes(var_expression.suffixes().cloned().collect()),
         shape,
     );
-    VarExpression::new(function_call.prefix().clone())
-        .with_suffixes(function_call.suffixes().cloned().collect())
+    let formatted = VarExpression::new(function_call.prefix().clone())
+        .with_suffixes(function_call.suffixes().cloned().collect());
+
+    // If a chain of indexes goes over the column width, break it at each `.`
+    if ctx.config().wrap_index_chains
+        && var_expression.suffixes().count() > 1
+        && is_index_chain(var_expression)
+        && !trivia_util::spans_multiple_lines(&formatted)
+        && shape.test_over_budget(&strip_trivia(&formatted))
+    {
+        return hang_index_chain(ctx, var_expression, shape);
+    }
+
+    formatted
 }
 
 /// Formats an UnOp Node
 pub fn format_unop(ctx: &Context, unop: &UnOp, shape: Shape) -> UnOp {
-    fmt_op!(ctx, UnOp, unop, shape, {
-        Minus = "-",
-        Not = "not ",
-        Hash = "#",
-        #[cfg(feature = "lua53")]
-        Tilde = "~",
-    }, |other| panic!("unknown node {:?}", other))
+    if ctx.should_space_unary_operators() {
+        fmt_op!(ctx, UnOp, unop, shape, {
+            Minus = "- ",
+            Not = "not ",
+            Hash = "# ",
+            #[cfg(feature = "lua53")]
+            Tilde = "~ ",
+        }, |other| panic!("unknown node {:?}", other))
+    } else {
+        fmt_op!(ctx, UnOp, unop, shape, {
+            Minus = "-",
+            Not = "not ",
+            Hash = "#",
+            #[cfg(feature = "lua53")]
+            Tilde = "~",
+        }, |other| panic!("unknown node {:?}", other))
+    }
 }
 
 /// Pushes a [`BinOp`] onto a newline, and indent its depending on indent_level.
@@ -984,16 +1049,14 @@ fn binop_expression_length(expression: &Expression, top_binop: &BinOp) -> usize
 
 fn binop_expression_contains_comments(expression: &Expression, top_binop: &BinOp) -> bool {
     match expression {
-        Expression::BinaryOperator { lhs, binop, rhs } => {
-            if binop.precedence() == top_binop.precedence() {
-                contains_comments(binop)
-                    || rhs.has_leading_comments(CommentSearch::All)
-                    || lhs.has_trailing_comments(CommentSearch::All)
-                    || binop_expression_contains_comments(lhs, top_binop)
-                    || binop_expression_contains_comments(rhs, top_binop)
-            } else {
-                false
-            }
+        Expression::BinaryOperator { lhs, binop, rhs }
+            if binop.precedence() == top_binop.precedence() =>
+        {
+            contains_comments(binop)
+                || rhs.has_leading_comments(CommentSearch::All)
+                || lhs.has_trailing_comments(CommentSearch::All)
+                || binop_expression_contains_comments(lhs, top_binop)
+                || binop_expression_contains_comments(rhs, top_binop)
         }
         _ => false,
     }
@@ -1203,6 +1266,19 @@ fn hang_binop_expression(
                                     lhs_range,
                                     expression_context,
                                 )
+                            } else if ctx.should_indent_binary_chains_by_precedence()
+                                && matches!(*rhs, Expression::BinaryOperator { .. })
+                            {
+                                // The RHS is a chain of higher precedence operators. Hang it separately, so that
+                                // its operators are indented further than the current chain if it does not fit
+                                hang_binop_expression(
+                                    ctx,
+                                    *rhs,
+                                    binop,
+                                    rhs_shape,
+                                    lhs_range,
+                                    expression_context,
+                                )
                             } else {
                                 format_expression_internal(
                                     ctx,
@@ -5,6 +5,7 @@ use full_moon::ast::{
     FunctionDeclaration, FunctionName, Index, LastStmt, LocalFunction, MethodCall, Parameter,
     Prefix, Stmt, Suffix, TableConstructor, Var,
 };
+use full_moon::node::Node;
 use full_moon::tokenizer::{Token, TokenKind, TokenReference, TokenType};
 
 #[cfg(feature = "luau")]
@@ -263,6 +264,11 @@ fn function_args_multiline_heuristic(
                         return true;
                     }
 
+                    // If the function is the last argument, and we should not hug it, then the function args should be expanded
+                    if pair.punctuation().is_none() && ctx.should_wrap_last_arg_callback() {
+                        return true;
+                    }
+
                     current_state = current_state.record_multiline_arg();
 
                     // First check the top line of the anonymous function (i.e. the function token and any parameters)
@@ -384,6 +390,26 @@ fn format_argument_multiline(ctx: &Context, argument: &Expression, shape: Shape)
     }
 }
 
+/// Whether the only argument is a table which spans multiple lines, and the call should be expanded so that the table is
+/// placed on its own line, rather than hugging the parentheses.
+/// The argument is checked once formatted, so that a table wrapped in redundant parentheses is treated the same as one without
+fn wrap_single_table_argument(
+    ctx: &Context,
+    arguments: &Punctuated<Expression>,
+    shape: Shape,
+) -> bool {
+    if ctx.should_hug_single_table_argument() || arguments.len() != 1 {
+        return false;
+    }
+
+    let argument = format_expression(
+        ctx,
+        arguments.iter().next().unwrap(),
+        shape + 2, // 2 = "(" + ")"
+    );
+    is_table_constructor(&argument) && trivia_util::spans_multiple_lines(&strip_trivia(&argument))
+}
+
 /// Formats a FunctionArgs node.
 /// [`call_next_node`] provides information about the node after the FunctionArgs. This only matters if the configuration specifies no call parentheses.
 pub fn format_function_args(
@@ -418,31 +444,29 @@ pub fn format_function_args(
                 let trailing_comments = parentheses.tokens().1.trailing_trivia().cloned().collect();
 
                 match argument {
-                    Expression::String(token_reference) => {
-                        if ctx.should_omit_string_parens() {
-                            return format_function_args(
-                                ctx,
-                                &FunctionArgs::String(token_reference.update_trailing_trivia(
-                                    FormatTriviaType::Append(trailing_comments),
-                                )),
-                                shape,
-                                call_next_node,
-                            );
-                        }
+                    Expression::String(token_reference) if ctx.should_omit_string_parens() => {
+                        return format_function_args(
+                            ctx,
+                            &FunctionArgs::String(token_reference.update_trailing_trivia(
+                                FormatTriviaType::Append(trailing_comments),
+                            )),
+                            shape,
+                            call_next_node,
+                        );
                     }
-                    Expression::TableConstructor(table_constructor) => {
-                        if ctx.should_omit_table_parens() {
-                            return format_function_args(
-                                ctx,
-                                &FunctionArgs::TableConstructor(
-                                    table_constructor.update_trailing_trivia(
-                                        FormatTriviaType::Append(trailing_comments),
-                                    ),
-                                ),
-                                shape,
-                                call_next_node,
-                            );
-                        }
+                    Expression::TableConstructor(table_constructor)
+                        if ctx.should_omit_table_parens() =>
+                    {
+                        return format_function_args(
+                            ctx,
+                            &FunctionArgs::TableConstructor(
+                                table_constructor.update_trailing_trivia(FormatTriviaType::Append(
+                                    trailing_comments,
+                                )),
+                            ),
+                            shape,
+                            call_next_node,
+                        );
                     }
                     _ => (),
                 }
@@ -453,14 +477,16 @@ pub fn format_function_args(
             // If there is a comment present anywhere in between the start parentheses and end parentheses, we should keep it multiline
             let force_mutliline = function_args_contains_comments(parentheses, arguments);
 
-            let is_multiline =
-                force_mutliline || function_args_multiline_heuristic(ctx, arguments, shape);
+            let is_multiline = force_mutliline
+                || function_args_multiline_heuristic(ctx, arguments, shape)
+                || wrap_single_table_argument(ctx, arguments, shape);
 
             // Handle special case: we want to go multiline, but we have a single argument which is a table constructor
-            // In this case, we want to hug the table braces with the parentheses.
+            // In this case, we want to hug the table braces with the parentheses, unless configured otherwise.
             // To do this, we format single line, but include the closing parentheses in the shape
             let hug_table_constructor = is_multiline
                 && !force_mutliline
+                && ctx.should_hug_single_table_argument()
                 && arguments.len() == 1
                 && is_table_constructor(arguments.iter().next().unwrap());
 
@@ -575,6 +601,32 @@ pub fn format_function_args(
                 return FunctionArgs::TableConstructor(table_constructor);
             }
 
+            // If the table should be wrapped rather than hugging the parentheses, format it as a parenthesised argument
+            // instead, so that the output is the same as if the parentheses were present in the input
+            let argument = Expression::TableConstructor(table_constructor.to_owned());
+            if wrap_single_table_argument(
+                ctx,
+                &std::iter::once(Pair::new(argument.to_owned(), None)).collect(),
+                shape,
+            ) {
+                let (argument, comments_buffer) = trivia_util::take_trailing_comments(&argument);
+                let parentheses = ContainedSpan::new(
+                    TokenReference::symbol("(").unwrap(),
+                    TokenReference::symbol(")").unwrap(),
+                )
+                .update_trailing_trivia(FormatTriviaType::Append(comments_buffer));
+
+                return format_function_args(
+                    ctx,
+                    &FunctionArgs::Parentheses {
+                        parentheses,
+                        arguments: std::iter::once(Pair::new(argument, None)).collect(),
+                    },
+                    shape,
+                    call_next_node,
+                );
+            }
+
             let mut arguments = Punctuated::new();
             let new_expression = format_expression(
                 ctx,
@@ -628,9 +680,15 @@ fn should_parameters_format_multiline(
     // Otherwise, include them in the total length
     #[cfg(feature = "luau")]
     {
+        // If long return types can be moved onto their own line, they should not cause the parameters to expand
+        let return_type = match ctx.should_break_long_return_types() {
+            true => None,
+            false => function_body.return_type(),
+        };
+
         let (extra_line_length, multiline_specifier_present) = function_body
             .type_specifiers()
-            .chain(std::iter::once(function_body.return_type())) // Include optional return type
+            .chain(std::iter::once(return_type)) // Include optional return type
             .map(|x| {
                 x.map_or((0, false), |specifier| {
                     let formatted = format_type_specifier(ctx, specifier, shape).to_string();
@@ -776,6 +834,10 @@ pub fn should_collapse_function_body(ctx: &Context, function_body: &FunctionBody
         && (trivia_util::is_block_empty(function_body.block())
             || (trivia_util::is_block_simple(function_body.block())
                 && ctx.should_collapse_simple_functions()
+                && !ctx.should_expand_functions()
+                && (!ctx.should_collapse_only_getters_setters()
+                    || trivia_util::is_block_getter_or_setter(function_body.block()))
+                && !ctx.block_contains_skipped_stmt(function_body.block())
                 && !block_contains_nested_function(function_body.block())))
 }
 
@@ -789,18 +851,32 @@ pub fn format_function_body(
     let leading_trivia = vec![create_indent_trivia(ctx, shape)];
 
     let should_collapse = should_collapse_function_body(ctx, function_body);
+    // Any expansion of this function does not apply to the functions nested within it
+    let ctx = &ctx.with_expand_functions(false);
 
     // Check if the parameters should be placed across multiple lines
     let multiline_params = {
         #[cfg(feature = "luau")]
         let mut type_specifiers = function_body.type_specifiers();
 
-        // Check whether they contain comments
+        // Check whether they contain comments. If configured, comments written inline, such as `--[[reserved]]`,
+        // are kept in place
         let contains_comments = function_body.parameters().pairs().any(|pair| {
-            let contains_comments = pair
-                .punctuation()
-                .is_some_and(trivia_util::token_contains_comments)
-                || trivia_util::contains_comments(pair.value());
+            let contains_comments = match ctx.should_keep_inline_parameter_comments() {
+                true => {
+                    pair.punctuation()
+                        .is_some_and(trivia_util::token_contains_non_inline_comments)
+                        || pair
+                            .value()
+                            .tokens()
+                            .any(trivia_util::token_contains_non_inline_comments)
+                }
+                false => {
+                    pair.punctuation()
+                        .is_some_and(trivia_util::token_contains_comments)
+                        || trivia_util::contains_comments(pair.value())
+                }
+            };
             #[cfg(feature = "luau")]
             let type_specifier_comments = type_specifiers
                 .next()
@@ -833,6 +909,10 @@ pub fn format_function_body(
             format_parameter,
             shape,
         ),
+        false if ctx.should_keep_inline_parameter_comments() => (
+            format_singleline_parameters_parentheses(ctx, function_body, shape),
+            format_singleline_parameters(ctx, function_body, shape),
+        ),
         false => (
             format_contained_span(ctx, function_body.parameters_parentheses(), shape),
             format_singleline_parameters(ctx, function_body, shape),
@@ -847,15 +927,56 @@ pub fn format_function_body(
             shape
         };
 
-        (
-            function_body
-                .type_specifiers()
-                .map(|x| x.map(|specifier| format_type_specifier(ctx, specifier, parameters_shape)))
-                .collect::<Vec<_>>(),
-            function_body
-                .return_type()
-                .map(|return_type| format_type_specifier(ctx, return_type, shape)),
-        )
+        let type_specifiers = function_body
+            .type_specifiers()
+            .map(|x| x.map(|specifier| format_type_specifier(ctx, specifier, parameters_shape)))
+            .collect::<Vec<_>>();
+
+        let return_type = function_body.return_type().map(|return_type| {
+            const PARENS_LEN: usize = "()".len();
+            let formatted_return_type = format_type_specifier(ctx, return_type, shape);
+
+            if !ctx.should_break_long_return_types() {
+                return formatted_return_type;
+            }
+
+            // Determine the position of the closing parenthesis of the parameters
+            let return_type_shape = match multiline_params {
+                true => shape.reset() + 1, // 1 = ")"
+                false => {
+                    shape.take_last_line(&formatted_parameters)
+                        + PARENS_LEN
+                        + type_specifiers.iter().fold(0, |acc, x| {
+                            acc + x.as_ref().map_or(0, |x| x.to_string().len())
+                        })
+                }
+            };
+
+            let hanging_shape = shape.reset().increment_additional_indent();
+            let hanging_return_type = format_type_specifier(ctx, return_type, hanging_shape);
+
+            // Move the return type onto its own indented line if it goes over width, or if doing so
+            // prevents it from expanding over multiple lines
+            if return_type_shape
+                .take_first_line(&formatted_return_type)
+                .over_budget()
+                || (trivia_util::spans_multiple_lines(&formatted_return_type)
+                    && !trivia_util::spans_multiple_lines(&hanging_return_type))
+            {
+                singleline_function = false;
+                let punctuation = hanging_return_type.punctuation().update_leading_trivia(
+                    FormatTriviaType::Append(vec![
+                        create_newline_trivia(ctx),
+                        create_indent_trivia(ctx, hanging_shape),
+                    ]),
+                );
+                hanging_return_type.with_punctuation(punctuation)
+            } else {
+                formatted_return_type
+            }
+        });
+
+        (type_specifiers, return_type)
     };
 
     let create_normal_block = || {
@@ -1158,6 +1279,66 @@ pub fn format_function_call(
     FunctionCall::new(formatted_prefix).with_suffixes(formatted_suffixes)
 }
 
+/// Removes any comments within the trivia of a token in a function definition's header (i.e. before the parameters),
+/// adding them to `comments`. If `include_leading` is not set, only comments in the trailing trivia are removed.
+/// These comments are moved before the function definition, to ensure that the header is never broken
+/// over multiple lines, and that a single-line comment cannot comment out the rest of the header.
+fn take_header_comments(
+    token_reference: &TokenReference,
+    include_leading: bool,
+    comments: &mut Vec<Token>,
+) -> TokenReference {
+    let leading_trivia = if include_leading {
+        comments.extend(
+            token_reference
+                .leading_trivia()
+                .filter(|trivia| trivia_util::trivia_is_comment(trivia))
+                .cloned(),
+        );
+        Vec::new()
+    } else {
+        token_reference.leading_trivia().cloned().collect()
+    };
+    comments.extend(
+        token_reference
+            .trailing_trivia()
+            .filter(|trivia| trivia_util::trivia_is_comment(trivia))
+            .cloned(),
+    );
+
+    TokenReference::new(leading_trivia, token_reference.token().clone(), Vec::new())
+}
+
+/// Removes any comments within a FunctionName node, adding them to `comments`
+fn take_function_name_comments(
+    function_name: &FunctionName,
+    comments: &mut Vec<Token>,
+) -> FunctionName {
+    let names = function_name
+        .names()
+        .pairs()
+        .map(|pair| match pair {
+            Pair::Punctuated(value, punctuation) => Pair::Punctuated(
+                take_header_comments(value, true, comments),
+                take_header_comments(punctuation, true, comments),
+            ),
+            Pair::End(value) => Pair::End(take_header_comments(value, true, comments)),
+        })
+        .collect();
+
+    let method = function_name
+        .method_colon()
+        .zip(function_name.method_name())
+        .map(|(method_colon, method_name)| {
+            (
+                take_header_comments(method_colon, true, comments),
+                take_header_comments(method_name, true, comments),
+            )
+        });
+
+    FunctionName::new(names).with_method(method)
+}
+
 /// Formats a FunctionName node
 pub fn format_function_name(
     ctx: &Context,
@@ -1216,14 +1397,21 @@ pub fn format_function_declaration(
         })
         .collect();
 
-    let function_token = fmt_symbol!(
-        ctx,
+    // Move any comments within the function header before the function definition
+    let mut header_comments = Vec::new();
+    let function_token = take_header_comments(
         function_declaration.function_token(),
-        "function ",
-        shape
-    )
-    .update_leading_trivia(FormatTriviaType::Append(leading_trivia));
-    let formatted_function_name = format_function_name(ctx, function_declaration.name(), shape)
+        false,
+        &mut header_comments,
+    );
+    let function_name =
+        take_function_name_comments(function_declaration.name(), &mut header_comments);
+    let function_token =
+        function_token.update_leading_trivia(FormatTriviaType::Append(header_comments));
+
+    let function_token = fmt_symbol!(ctx, &function_token, "function ", shape)
+        .update_leading_trivia(FormatTriviaType::Append(leading_trivia));
+    let formatted_function_name = format_function_name(ctx, &function_name, shape)
         .update_trailing_trivia(FormatTriviaType::Append(function_definition_trivia));
 
     let shape = shape + (9 + strip_trivia(&formatted_function_name).to_string().len()); // 9 = "function "
@@ -1260,10 +1448,19 @@ pub fn format_local_function(
                 .update_trailing_trivia(FormatTriviaType::Append(trailing_trivia.clone()))
         })
         .collect();
-    let local_token = fmt_symbol!(ctx, local_function.local_token(), "local ", shape)
+    // Move any comments within the function header before the function definition
+    let mut header_comments = Vec::new();
+    let local_token =
+        take_header_comments(local_function.local_token(), false, &mut header_comments);
+    let function_token =
+        take_header_comments(local_function.function_token(), true, &mut header_comments);
+    let name = take_header_comments(local_function.name(), true, &mut header_comments);
+    let local_token = local_token.update_leading_trivia(FormatTriviaType::Append(header_comments));
+
+    let local_token = fmt_symbol!(ctx, &local_token, "local ", shape)
         .update_leading_trivia(FormatTriviaType::Append(leading_trivia));
-    let function_token = fmt_symbol!(ctx, local_function.function_token(), "function ", shape);
-    let formatted_name = format_token_reference(ctx, local_function.name(), shape)
+    let function_token = fmt_symbol!(ctx, &function_token, "function ", shape);
+    let formatted_name = format_token_reference(ctx, &name, shape)
         .update_trailing_trivia(FormatTriviaType::Append(function_definition_trivia));
 
     let shape = shape + (6 + 9 + strip_trivia(&formatted_name).to_string().len()); // 6 = "local ", 9 = "function "
@@ -1311,7 +1508,71 @@ pub fn format_parameter(ctx: &Context, parameter: &Parameter, shape: Shape) -> P
     }
 }
 
+/// Collects the inline comments within the trivia, so that they can be kept in place on a single line.
+/// Each comment is separated from the token it is attached to by a single space, placed before the comment
+/// if `space_before` is set, or otherwise after it.
+fn inline_comments_trivia<'a>(
+    trivia: impl Iterator<Item = &'a Token>,
+    space_before: bool,
+) -> Vec<Token> {
+    trivia
+        .filter(|trivia| trivia_util::trivia_is_comment(trivia))
+        .flat_map(|comment| match space_before {
+            true => [Token::new(TokenType::spaces(1)), comment.to_owned()],
+            false => [comment.to_owned(), Token::new(TokenType::spaces(1))],
+        })
+        .collect()
+}
+
+/// Formats the parentheses around parameters which are on a single line, keeping any inline comments
+/// within them in place, e.g. `(--[[first]] a, b)`
+fn format_singleline_parameters_parentheses(
+    ctx: &Context,
+    function_body: &FunctionBody,
+    shape: Shape,
+) -> ContainedSpan {
+    let parentheses = format_contained_span(ctx, function_body.parameters_parentheses(), shape);
+    if function_body.parameters().is_empty() {
+        return parentheses;
+    }
+
+    let (start_parenthesis, end_parenthesis) = function_body.parameters_parentheses().tokens();
+    let (formatted_start, formatted_end) = parentheses.tokens();
+    ContainedSpan::new(
+        formatted_start.update_trailing_trivia(FormatTriviaType::Replace(inline_comments_trivia(
+            start_parenthesis.trailing_trivia(),
+            false,
+        ))),
+        formatted_end.update_leading_trivia(FormatTriviaType::Replace(inline_comments_trivia(
+            end_parenthesis.leading_trivia(),
+            true,
+        ))),
+    )
+}
+
+/// Formats a parameter on a single line, keeping any inline comments around it in place
+fn format_singleline_parameter(ctx: &Context, parameter: &Parameter, shape: Shape) -> Parameter {
+    let keep_inline_comments = |formatted: TokenReference, original: &TokenReference| {
+        TokenReference::new(
+            inline_comments_trivia(original.leading_trivia(), false),
+            formatted.token().to_owned(),
+            inline_comments_trivia(original.trailing_trivia(), true),
+        )
+    };
+
+    match (format_parameter(ctx, parameter, shape), parameter) {
+        (Parameter::Ellipsis(formatted), Parameter::Ellipsis(original)) => {
+            Parameter::Ellipsis(keep_inline_comments(formatted, original))
+        }
+        (Parameter::Name(formatted), Parameter::Name(original)) => {
+            Parameter::Name(keep_inline_comments(formatted, original))
+        }
+        (formatted, _) => formatted,
+    }
+}
+
 /// Formats the [`Parameters`] in the provided [`FunctionBody`] onto a single line.
+/// If configured, any inline comments within the parameters are kept in place.
 fn format_singleline_parameters(
     ctx: &Context,
     function_body: &FunctionBody,
@@ -1319,11 +1580,31 @@ fn format_singleline_parameters(
 ) -> Punctuated<Parameter> {
     let mut formatted_parameters = Punctuated::new();
 
+    if !ctx.should_keep_inline_parameter_comments() {
+        for pair in function_body.parameters().pairs() {
+            let parameter = format_parameter(ctx, pair.value(), shape);
+            let punctuation = pair
+                .punctuation()
+                .map(|punctuation| fmt_symbol!(ctx, punctuation, ", ", shape));
+
+            formatted_parameters.push(Pair::new(parameter, punctuation));
+        }
+
+        return formatted_parameters;
+    }
+
     for pair in function_body.parameters().pairs() {
-        let parameter = format_parameter(ctx, pair.value(), shape);
-        let punctuation = pair
-            .punctuation()
-            .map(|punctuation| fmt_symbol!(ctx, punctuation, ", ", shape));
+        let parameter = format_singleline_parameter(ctx, pair.value(), shape);
+        let punctuation = pair.punctuation().map(|punctuation| {
+            let mut trailing_trivia = inline_comments_trivia(punctuation.trailing_trivia(), true);
+            trailing_trivia.push(Token::new(TokenType::spaces(1)));
+
+            TokenReference::new(
+                inline_comments_trivia(punctuation.leading_trivia(), true),
+                fmt_symbol!(ctx, punctuation, ",", shape).token().to_owned(),
+                trailing_trivia,
+            )
+        });
 
         formatted_parameters.push(Pair::new(parameter, punctuation));
     }
@@ -81,7 +81,7 @@ fn get_quote_to_use(ctx: &Context, literal: &str) -> StringLiteralQuoteType {
     }
 }
 
-fn format_single_line_comment_string(comment: &str) -> &str {
+pub fn format_single_line_comment_string(comment: &str) -> &str {
     // Trim any trailing whitespace
     comment.trim_end()
 }
@@ -280,7 +280,7 @@ pub fn format_token(
 /// Handles any leading/trailing trivia provided by format_token, and appends it accordingly in relation to the formatted token.
 /// Mainly useful for comments
 /// Additional indent level will indent any trivia by the further level - useful for comments on the `end` token
-fn load_token_trivia(
+pub fn load_token_trivia(
     ctx: &Context,
     current_trivia: Vec<&Token>,
     format_token_type: FormatTokenType,
@@ -300,13 +300,11 @@ fn load_token_trivia(
                 // If we are formatting leading trivia, we will allow a single newline to be kept in succession, if we
                 // find one.
                 match format_token_type {
-                    FormatTokenType::LeadingTrivia => {
-                        if characters.contains('\n') {
-                            newline_count_in_succession += 1;
-                            if newline_count_in_succession == 1 {
-                                // We have a case where we will allow a single newline to be kept
-                                token_trivia.push(create_newline_trivia(ctx));
-                            }
+                    FormatTokenType::LeadingTrivia if characters.contains('\n') => {
+                        newline_count_in_succession += 1;
+                        if newline_count_in_succession == 1 {
+                            // We have a case where we will allow a single newline to be kept
+                            token_trivia.push(create_newline_trivia(ctx));
                         }
                     }
                     FormatTokenType::TrailingTrivia => {
@@ -454,6 +454,7 @@ pub fn format_if(ctx: &Context, if_node: &If, shape: Shape) -> If {
     if !require_multiline_expression
         && ctx.should_collapse_simple_conditionals()
         && is_if_guard(if_node)
+        && !ctx.block_contains_skipped_stmt(if_node.block())
     {
         // Rather than deferring to `format_block()`, since we know that there is only a single Stmt or LastStmt in the block, we can format it immediately
         // We need to modify the formatted LastStmt, since it will have automatically added leading/trailing trivia we don't want
@@ -1107,6 +1108,10 @@ pub fn format_stmt(ctx: &Context, stmt: &Stmt, shape: Shape) -> Stmt {
         return stmt_block::format_stmt_block(ctx, stmt, shape);
     }
 
+    if ctx.should_skip_stmt(stmt) {
+        return trivia_util::format_skipped_stmt(ctx, stmt, shape);
+    }
+
     fmt_stmt!(ctx, stmt, shape, {
         Assignment = format_assignment,
         Do = format_do_block,
@@ -144,6 +144,16 @@ fn handle_field_key_equals_comments<T: Node>(
     (key_leading_comments, equal)
 }
 
+/// Returns the context to format the value of a field in. If the table is multiline and its expansion propagates to
+/// nested tables, a value which is itself a table is expanded
+fn field_value_context(ctx: &Context, value: &Expression, table_type: TableType) -> Context {
+    ctx.with_expand_table(
+        matches!(table_type, TableType::MultiLine)
+            && ctx.should_propagate_table_expansion()
+            && matches!(value, Expression::TableConstructor(_)),
+    )
+}
+
 fn format_field(
     ctx: &Context,
     field: &Field,
@@ -203,6 +213,7 @@ fn format_field(
             } else {
                 let shape =
                     shape.take_last_line(&key) + (2 + 3 + if space_brackets { 2 } else { 0 }); // 2 = brackets, 3 = " = ", 2 = spaces around brackets if necessary
+                let ctx = &field_value_context(ctx, value, table_type);
                 let value = format_field_expression_value(ctx, value, shape);
                 (equal, value)
             };
@@ -233,6 +244,7 @@ fn format_field(
                 hang_field_value_at_equals_due_to_comments(ctx, &equal, value, shape)
             } else {
                 let shape = shape + (strip_trivia(&key).to_string().len() + 3); // 3 = " = "
+                let ctx = &field_value_context(ctx, value, table_type);
                 let value = format_field_expression_value(ctx, value, shape);
                 (equal, value)
             };
@@ -251,6 +263,7 @@ fn format_field(
             trailing_trivia = expression.trailing_comments_search(CommentSearch::Single);
 
             if let TableType::MultiLine = table_type {
+                let ctx = &field_value_context(ctx, expression, table_type);
                 let formatted_expression = format_field_expression_value(ctx, expression, shape);
                 Field::NoKey(formatted_expression.update_leading_trivia(leading_trivia))
             } else {
@@ -446,6 +459,76 @@ where
     (braces, fields)
 }
 
+/// Whether there is a line break in the input after the given field, before the next field
+fn field_is_followed_by_newline(pair: &Pair<Field>, next_field: Option<&Pair<Field>>) -> bool {
+    pair.punctuation().is_some_and(|punctuation| {
+        punctuation
+            .trailing_trivia()
+            .any(trivia_util::trivia_is_newline)
+    }) || next_field.is_some_and(|next_field| {
+        next_field
+            .value()
+            .surrounding_trivia()
+            .0
+            .iter()
+            .any(|trivia| trivia_util::trivia_is_newline(trivia))
+    })
+}
+
+/// Formats a table onto multiple lines, keeping the fields grouped onto the same lines as they were in the input.
+/// This is used for tables marked with a `-- stylua: keep-breaks` comment.
+/// This function does not take into account for comments
+fn format_table_keeping_breaks(
+    ctx: &Context,
+    table_constructor: &TableConstructor,
+    shape: Shape,
+) -> (ContainedSpan, Punctuated<Field>) {
+    let (start_brace, end_brace) = table_constructor.braces().tokens();
+    let braces = create_table_braces(ctx, start_brace, end_brace, TableType::MultiLine, shape);
+    let line_shape = shape.reset().increment_additional_indent(); // Will take new line, and additional indentation
+    let mut shape = line_shape;
+
+    let mut current_fields = table_constructor.fields().pairs().peekable();
+    let mut fields = Punctuated::new();
+    let mut at_line_start = true;
+
+    while let Some(pair) = current_fields.next() {
+        let next_field = current_fields.peek().copied();
+        let breaks_after = next_field.is_none() || field_is_followed_by_newline(pair, next_field);
+
+        // Fields at the start of a line are indented, whilst fields continuing a line follow the previous comma
+        let table_type = match at_line_start {
+            true => {
+                shape = line_shape.add_width(1); // Add 1 to include the trailing comma at the end
+                TableType::MultiLine
+            }
+            false => TableType::SingleLine,
+        };
+
+        let (formatted_field, _) = format_field(ctx, pair.value(), table_type, shape);
+
+        let symbol = match breaks_after {
+            true => match pair.punctuation() {
+                Some(punctuation) => fmt_symbol!(ctx, punctuation, ",", shape),
+                None => TokenReference::symbol(",").unwrap(),
+            }
+            .update_trailing_trivia(FormatTriviaType::Append(vec![create_newline_trivia(ctx)])),
+            false => {
+                shape = shape.take_last_line(&formatted_field) + 2; // 2 = ", "
+                match pair.punctuation() {
+                    Some(punctuation) => fmt_symbol!(ctx, punctuation, ", ", shape),
+                    None => TokenReference::symbol(", ").unwrap(),
+                }
+            }
+        };
+
+        at_line_start = breaks_after;
+        fields.push(Pair::new(formatted_field, Some(symbol)));
+    }
+
+    (braces, fields)
+}
+
 fn expression_is_multiline_function(ctx: &Context, expression: &Expression) -> bool {
     if let Expression::Function(anonymous_function) = expression {
         return !should_collapse_function_body(ctx, anonymous_function.body());
@@ -453,11 +536,48 @@ fn expression_is_multiline_function(ctx: &Context, expression: &Expression) -> b
     false
 }
 
+/// Whether the table is a dispatch table, where every field is keyed and has an anonymous function value,
+/// e.g. `{ [Action.Open] = function() ... end, [Action.Close] = function() ... end }`
+fn is_dispatch_table(table_constructor: &TableConstructor) -> bool {
+    !table_constructor.fields().is_empty()
+        && table_constructor.fields().iter().all(|field| {
+            matches!(
+                field,
+                Field::ExpressionKey {
+                    value: Expression::Function(_),
+                    ..
+                } | Field::NameKey {
+                    value: Expression::Function(_),
+                    ..
+                }
+            )
+        })
+}
+
 /// Examines the fields of a table constructor to see if we should force the table constructor multiline.
 /// This will only happen if either:
 ///  1) There are comments within the table
 ///  2) There are anonymous functions defined within the table [As these will expand multiline]
+///  3) The table is a dispatch table which is formatted like a switch statement
+///  4) A field within the table is wider than the configured maximum table element width
 fn should_expand(ctx: &Context, table_constructor: &TableConstructor) -> bool {
+    if ctx.should_expand_functions() {
+        return true;
+    }
+
+    // Like the width of the whole table, the width of a field is naively measured from the input
+    let max_element_width = ctx.config().table_element_max_width;
+    if max_element_width > 0
+        && table_constructor.fields().iter().any(|field| {
+            match (field.start_position(), field.end_position()) {
+                (Some(start), Some(end)) => end.bytes() - start.bytes() > max_element_width,
+                _ => false,
+            }
+        })
+    {
+        return true;
+    }
+
     let (start_brace, end_brace) = table_constructor.braces().tokens();
     let contains_comments = start_brace
         .trailing_trivia()
@@ -499,15 +619,45 @@ pub fn format_table_constructor(
 ) -> TableConstructor {
     const BRACE_LEN: usize = "{".len();
 
+    // Dispatch tables may be formatted like a switch statement, where every function value is expanded beneath its key.
+    // Otherwise, any expansion from an enclosing dispatch table does not apply to this table
+    let ctx = &ctx.with_expand_functions(
+        ctx.should_format_dispatch_tables_as_switch() && is_dispatch_table(table_constructor),
+    );
+
+    // This table may be expanded as its enclosing multiline table propagates its expansion to it.
+    // This does not apply to any tables nested further within its fields, unless this table is also multiline
+    let expand_table = ctx.should_expand_table();
+    let ctx = &ctx.with_expand_table(false);
+
     let (start_brace, end_brace) = table_constructor.braces().tokens();
 
+    // If the table was marked to keep its line breaks, and was written over multiple lines, preserve its structure
+    if ctx.should_keep_table_breaks()
+        && !table_constructor.fields().is_empty()
+        && strip_trivia(table_constructor).to_string().contains('\n')
+        && !start_brace
+            .trailing_trivia()
+            .any(trivia_util::trivia_is_comment)
+        && !end_brace
+            .leading_trivia()
+            .any(trivia_util::trivia_is_comment)
+        && !trivia_util::table_fields_contains_comments(table_constructor)
+    {
+        let (braces, fields) = format_table_keeping_breaks(ctx, table_constructor, shape);
+        return TableConstructor::new()
+            .with_braces(braces)
+            .with_fields(fields);
+    }
+
     let table_type = match table_constructor.fields().iter().next() {
         Some(_) => {
             // Determine if there was a new line at the end of the start brace
             // If so, then we should always be multiline
-            if start_brace
-                .trailing_trivia()
-                .any(trivia_util::trivia_is_newline)
+            if expand_table
+                || start_brace
+                    .trailing_trivia()
+                    .any(trivia_util::trivia_is_newline)
             {
                 TableType::MultiLine
             } else {
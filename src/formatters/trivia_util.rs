@@ -1,6 +1,9 @@
 use crate::{
     context::{create_indent_trivia, create_newline_trivia, Context},
-    formatters::trivia::{FormatTriviaType, UpdateLeadingTrivia, UpdateTrailingTrivia},
+    formatters::{
+        general::{load_token_trivia, FormatTokenType},
+        trivia::{FormatTriviaType, UpdateLeadingTrivia, UpdateTrailingTrivia},
+    },
     shape::Shape,
 };
 #[cfg(feature = "luau")]
@@ -80,6 +83,23 @@ pub fn trivia_is_comment(trivia: &Token) -> bool {
     )
 }
 
+/// Whether the trivia is a multiline comment which is written on a single line, e.g. `--[[reserved]]`, which
+/// can be kept inline between other tokens
+pub fn trivia_is_inline_comment(trivia: &Token) -> bool {
+    match trivia.token_type() {
+        TokenType::MultiLineComment { comment, .. } => !comment.contains('\n'),
+        _ => false,
+    }
+}
+
+/// Whether the token contains any comments which cannot be kept inline, such as single-line comments
+pub fn token_contains_non_inline_comments(token: &TokenReference) -> bool {
+    token
+        .leading_trivia()
+        .chain(token.trailing_trivia())
+        .any(|trivia| trivia_is_comment(trivia) && !trivia_is_inline_comment(trivia))
+}
+
 fn trivia_is_comment_search(trivia: &Token, search: CommentSearch) -> bool {
     match search {
         CommentSearch::Single => trivia_is_singleline_comment(trivia),
@@ -199,6 +219,16 @@ pub fn is_block_simple(block: &Block) -> bool {
             })
 }
 
+/// Whether the block is of the form of a getter or setter, i.e. it only contains a `return` with values,
+/// or a single assignment. Assumes the block is already [simple](is_block_simple).
+pub fn is_block_getter_or_setter(block: &Block) -> bool {
+    match (block.stmts().next(), block.last_stmt()) {
+        (None, Some(LastStmt::Return(r#return))) => !r#return.returns().is_empty(),
+        (Some(Stmt::Assignment(_)) | Some(Stmt::LocalAssignment(_)), None) => true,
+        _ => false,
+    }
+}
+
 impl<T> GetLeadingTrivia for &T
 where
     T: GetLeadingTrivia,
@@ -1238,6 +1268,52 @@ where
     node.update_leading_trivia(FormatTriviaType::Replace(leading_trivia))
 }
 
+/// Formats the leading trivia of a node in the same way as for a formatted node, reindenting any leading comments and
+/// collapsing newline gaps, then indents the node for the given shape.
+/// The rest of the node is kept as-is.
+pub fn reindent_node<T>(ctx: &Context, node: &T, shape: Shape) -> T
+where
+    T: Node + UpdateLeadingTrivia,
+{
+    let mut leading_trivia = load_token_trivia(
+        ctx,
+        node.surrounding_trivia().0,
+        FormatTokenType::LeadingTrivia,
+        shape,
+    );
+    leading_trivia.push(create_indent_trivia(ctx, shape));
+    node.update_leading_trivia(FormatTriviaType::Replace(leading_trivia))
+}
+
+/// The trailing trivia of a statement which is skipped from formatting. Trailing whitespace is replaced with a newline,
+/// keeping any trailing comments, so that the statement is placed on its own line as a formatted statement would be
+fn skipped_stmt_trailing_trivia(ctx: &Context, trailing_trivia: Vec<Token>) -> Vec<Token> {
+    let mut trailing_trivia: Vec<Token> = trailing_trivia
+        .into_iter()
+        .filter(trivia_is_comment)
+        .flat_map(|trivia| vec![Token::new(TokenType::spaces(1)), trivia])
+        .collect();
+    trailing_trivia.push(create_newline_trivia(ctx));
+    trailing_trivia
+}
+
+/// Outputs a statement which is skipped from formatting as it is written, other than its indentation and trailing trivia
+pub fn format_skipped_stmt(ctx: &Context, stmt: &Stmt, shape: Shape) -> Stmt {
+    let (stmt, trailing_trivia) = get_stmt_trailing_trivia(reindent_node(ctx, stmt, shape));
+    stmt.update_trailing_trivia(FormatTriviaType::Append(skipped_stmt_trailing_trivia(
+        ctx,
+        trailing_trivia,
+    )))
+}
+
+/// Outputs a last statement which is skipped from formatting as it is written, other than its indentation and trailing
+/// trivia
+pub fn format_skipped_last_stmt(ctx: &Context, last_stmt: &LastStmt, shape: Shape) -> LastStmt {
+    let trailing_trivia = skipped_stmt_trailing_trivia(ctx, last_stmt.trailing_trivia());
+    reindent_node(ctx, last_stmt, shape)
+        .update_trailing_trivia(FormatTriviaType::Replace(trailing_trivia))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
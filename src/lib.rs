@@ -8,13 +8,22 @@ use thiserror::Error;
 use wasm_bindgen::prelude::*;
 
 #[macro_use]
+mod comments;
 mod context;
+mod cursor;
 #[cfg(feature = "editorconfig")]
 pub mod editorconfig;
+mod edits;
 mod formatters;
+mod normalize_elseif;
 mod shape;
 mod sort_requires;
 mod verify_ast;
+mod warnings;
+mod whitespace;
+
+pub use edits::{Edit, EditPosition};
+pub use warnings::{Warning, WarningKind};
 
 /// The Lua syntax version to use
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
@@ -149,6 +158,9 @@ pub enum CollapseSimpleStatement {
     FunctionOnly,
     /// Collapse simple if guards onto a single line
     ConditionalOnly,
+    /// Collapse only simple functions which are getters or setters (i.e., a single `return` or a single assignment)
+    /// onto a single line
+    GettersSettersOnly,
     /// Collapse all simple statements onto a single line
     Always,
 }
@@ -166,6 +178,171 @@ pub enum BlockNewlineGaps {
     Preserve,
 }
 
+/// How a Luau if-expression should be broken when it does not fit onto a single line.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum IfExpressionStyle {
+    /// Place each `then` and `else` onto a new line, keeping their expression on the same line
+    #[default]
+    Hanging,
+    /// Place each `then` and `else` onto a new line, and additionally break after them, so their expression
+    /// is on its own line
+    BreakAfterKeywords,
+}
+
+/// Where a Luau function's return type annotation should be placed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum ReturnTypeStyle {
+    /// Always keep the return type on the same line as the closing parenthesis of the parameters
+    #[default]
+    SameLine,
+    /// Place the return type onto its own indented line if it would otherwise go over the column width
+    BreakWhenLong,
+}
+
+/// Whether parentheses around returned expressions should be kept.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum ReturnParentheses {
+    /// Remove parentheses around returned expressions when they are not required
+    #[default]
+    RemoveRedundant,
+    /// Keep parentheses around returned expressions based on their presence in input code
+    Input,
+}
+
+/// How operands with a higher precedence are indented when a binary expression chain is hung.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum BinaryChainIndent {
+    /// Only the first operand of a hung chain is broken further when it does not fit
+    #[default]
+    Minimal,
+    /// Any operand of a hung chain which contains lower-precedence operators, and does not fit, is also broken,
+    /// and indented one level further than the chain, e.g. `a and b` within `a and b or c`
+    Precedence,
+}
+
+/// How a call is formatted when its last argument is an anonymous function which spans multiple lines.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum LastArgCallback {
+    /// Keep the function on the same line as the call, hugging the parentheses, with only its body indented
+    #[default]
+    Hug,
+    /// Expand the call, placing each argument (including the function) on its own line
+    Wrap,
+}
+
+/// How a dispatch table is formatted, which is a table where every field is keyed and has an anonymous function value,
+/// such as `{ [Action.Open] = function() ... end }`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum DispatchTableStyle {
+    /// Format the table and its functions in the same way as any other table
+    #[default]
+    Default,
+    /// Format the table like a switch statement, with each entry on its own line. Every function body which
+    /// is not empty is expanded beneath its key, even if it could be collapsed onto a single line
+    Switch,
+}
+
+/// Whether symbolic unary operators are followed by a space.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum UnaryOperatorSpacing {
+    /// Place symbolic unary operators directly before their operand, e.g. `-x` and `#t`
+    #[default]
+    Tight,
+    /// Place a single space between symbolic unary operators and their operand, e.g. `- x` and `# t`
+    Spaced,
+}
+
+/// How an anonymous function which spans multiple lines is formatted when it is the only value of an assignment,
+/// such as `local handler = function() ... end`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum AssignedFunction {
+    /// Keep the function on the same line as the equals token, with only its body indented
+    #[default]
+    Hug,
+    /// Place the function on its own line after the equals token, indented one level further
+    Wrap,
+}
+
+/// How a call is formatted when its only argument is a table which spans multiple lines, such as when using a table
+/// of named arguments, e.g. `f({ name = x, value = y })`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum SingleTableArgument {
+    /// Keep the table braces against the parentheses of the call, e.g. `f({`, with only the fields indented
+    #[default]
+    Hug,
+    /// Expand the call, placing the table on its own line within the parentheses
+    Wrap,
+}
+
+/// How comments written between the parameters of a function, such as `function f(a, --[[reserved]] b)`, are handled.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum ParameterComments {
+    /// Any comment between the parameters forces them onto multiple lines
+    #[default]
+    Wrap,
+    /// Keep block comments written on a single line in place, only forcing the parameters onto multiple lines for
+    /// single-line comments and block comments spanning multiple lines
+    Inline,
+}
+
+/// Whether a newline gap is kept between the comments at the start of a block and the first statement of the block.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum LeadingCommentNewlineGap {
+    /// Keep a newline gap after the comments if present in input
+    #[default]
+    Preserve,
+    /// Never allow a newline gap after the comments
+    Never,
+    /// Always place a newline gap after the comments
+    Always,
+}
+
+/// Whether a table being broken over multiple lines causes the tables nested within it to be broken too.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum NestedTableExpansion {
+    /// Measure every table independently, so a nested table which fits within the column width stays on a single line
+    #[default]
+    Independent,
+    /// When a table is broken over multiple lines, also break any non-empty tables which are the values of its fields
+    Propagate,
+}
+
 /// An optional formatting range.
 /// If provided, only content within these boundaries (inclusive) will be formatted.
 /// Both boundaries are optional, and are given as byte offsets from the beginning of the file.
@@ -228,6 +405,147 @@ pub enum SpaceAfterFunctionNames {
     Always,
 }
 
+/// A kind of statement, used to exclude statements from formatting
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[cfg_attr(feature = "fromstr", derive(strum::EnumString))]
+pub enum StatementKind {
+    /// An assignment, e.g. `x = 1`
+    Assignment,
+    /// A compound assignment, e.g. `x += 1`
+    CompoundAssignment,
+    /// A `do ... end` block
+    Do,
+    /// A function call used as a statement, e.g. `print(x)`
+    FunctionCall,
+    /// A function declaration, e.g. `function foo() end`
+    FunctionDeclaration,
+    /// A generic for loop, e.g. `for k, v in pairs(t) do end`
+    GenericFor,
+    /// A goto statement, e.g. `goto continue`
+    Goto,
+    /// An if statement, including any `elseif` and `else` branches
+    If,
+    /// A label, e.g. `::continue::`
+    Label,
+    /// A local assignment, e.g. `local x = 1`
+    LocalAssignment,
+    /// A local function declaration, e.g. `local function foo() end`
+    LocalFunction,
+    /// A numeric for loop, e.g. `for i = 1, 10 do end`
+    NumericFor,
+    /// A `repeat ... until` loop
+    Repeat,
+    /// A type declaration, including exported type declarations
+    TypeDeclaration,
+    /// A type function, including exported type functions
+    TypeFunction,
+    /// A while loop
+    While,
+    /// A return statement
+    Return,
+    /// A break statement
+    Break,
+    /// A continue statement
+    Continue,
+}
+
+impl StatementKind {
+    /// Every kind of statement
+    pub const ALL: [StatementKind; 19] = [
+        StatementKind::Assignment,
+        StatementKind::CompoundAssignment,
+        StatementKind::Do,
+        StatementKind::FunctionCall,
+        StatementKind::FunctionDeclaration,
+        StatementKind::GenericFor,
+        StatementKind::Goto,
+        StatementKind::If,
+        StatementKind::Label,
+        StatementKind::LocalAssignment,
+        StatementKind::LocalFunction,
+        StatementKind::NumericFor,
+        StatementKind::Repeat,
+        StatementKind::TypeDeclaration,
+        StatementKind::TypeFunction,
+        StatementKind::While,
+        StatementKind::Return,
+        StatementKind::Break,
+        StatementKind::Continue,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// A set of [`StatementKind`]s.
+/// In a configuration file, this is written as a list, e.g. `["Return", "Goto"]`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "Vec<StatementKind>")]
+#[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize),
+    serde(into = "Vec<StatementKind>")
+)]
+pub struct StatementKinds {
+    bits: u32,
+}
+
+impl StatementKinds {
+    /// Creates an empty set of statement kinds
+    pub fn new() -> Self {
+        StatementKinds::default()
+    }
+
+    /// Whether the given statement kind is present in the set
+    pub fn contains(&self, kind: StatementKind) -> bool {
+        self.bits & kind.bit() != 0
+    }
+
+    /// Adds the given statement kind to the set
+    pub fn insert(&mut self, kind: StatementKind) {
+        self.bits |= kind.bit();
+    }
+
+    /// Whether the set contains no statement kinds
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Iterates over all the statement kinds present in the set
+    pub fn iter(&self) -> impl Iterator<Item = StatementKind> + '_ {
+        StatementKind::ALL
+            .iter()
+            .copied()
+            .filter(move |kind| self.contains(*kind))
+    }
+}
+
+impl std::iter::FromIterator<StatementKind> for StatementKinds {
+    fn from_iter<I: IntoIterator<Item = StatementKind>>(iter: I) -> Self {
+        let mut kinds = StatementKinds::new();
+        for kind in iter {
+            kinds.insert(kind);
+        }
+        kinds
+    }
+}
+
+impl From<Vec<StatementKind>> for StatementKinds {
+    fn from(kinds: Vec<StatementKind>) -> Self {
+        kinds.into_iter().collect()
+    }
+}
+
+impl From<StatementKinds> for Vec<StatementKind> {
+    fn from(kinds: StatementKinds) -> Self {
+        kinds.iter().collect()
+    }
+}
+
 /// The configuration to use when formatting.
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(default, deny_unknown_fields)]
@@ -266,6 +584,7 @@ pub struct Config {
     /// Whether we should collapse simple structures like functions or guard statements
     /// if set to [`CollapseSimpleStatement::None`] structures are never collapsed.
     /// if set to [`CollapseSimpleStatement::FunctionOnly`] then simple functions (i.e., functions with a single laststmt) can be collapsed
+    /// if set to [`CollapseSimpleStatement::GettersSettersOnly`] then only simple functions containing a single `return` or assignment can be collapsed
     pub collapse_simple_statement: CollapseSimpleStatement,
     /// Whether we should allow blocks to preserve leading and trailing newline gaps.
     /// if set to [`BlockNewlineGaps::Never`] then newline gaps are never allowed at the start or end of blocks.
@@ -273,12 +592,82 @@ pub struct Config {
     pub block_newline_gaps: BlockNewlineGaps,
     /// Configuration for the sort requires codemod
     pub sort_requires: SortRequiresConfig,
+    /// Whether an `else` block containing only a nested if statement should be rewritten into an `elseif` clause.
+    pub normalize_elseif: bool,
     /// Whether we should include a space between the function name and arguments.
     /// * if space_after_function_names is set to [`SpaceAfterFunctionNames::Never`] a space is never used.
     /// * if space_after_function_names is set to [`SpaceAfterFunctionNames::Definitions`] a space is used only for definitions.
     /// * if space_after_function_names is set to [`SpaceAfterFunctionNames::Calls`] a space is used only for calls.
     /// * if space_after_function_names is set to [`SpaceAfterFunctionNames::Always`] a space is used for both definitions and calls.
     pub space_after_function_names: SpaceAfterFunctionNames,
+    /// Kinds of statements which should not be formatted.
+    /// Statements of these kinds are output as they are written in the input, with only their indentation updated.
+    pub skip_statement_kinds: StatementKinds,
+    /// How Luau if-expressions should be formatted when they do not fit onto a single line.
+    /// * if set to [`IfExpressionStyle::Hanging`] then the expression following a `then` or `else` stays on the same line as it.
+    /// * if set to [`IfExpressionStyle::BreakAfterKeywords`] then a newline is placed after every `then` and `else`.
+    pub if_expression_style: IfExpressionStyle,
+    /// Where Luau function return type annotations should be placed.
+    /// * if set to [`ReturnTypeStyle::SameLine`] then the return type always follows the function parameters.
+    /// * if set to [`ReturnTypeStyle::BreakWhenLong`] then the return type is placed on a new line when it would go over width.
+    pub return_type_style: ReturnTypeStyle,
+    /// Whether parentheses around returned expressions should be kept.
+    /// Parentheses which change the meaning of the return, such as truncating a function call to a single value
+    /// in `return (f())`, are always kept.
+    /// * if set to [`ReturnParentheses::RemoveRedundant`] then parentheses which are not required are removed.
+    /// * if set to [`ReturnParentheses::Input`] then parentheses directly around a returned expression are kept if present in the input.
+    pub return_parentheses: ReturnParentheses,
+    /// How operands with a higher precedence are indented when a binary expression chain is hung.
+    /// * if set to [`BinaryChainIndent::Minimal`] then only the first operand of a hung chain is broken further.
+    /// * if set to [`BinaryChainIndent::Precedence`] then every operand of a hung chain which does not fit is broken,
+    ///   with its own operators indented one level further than the chain, so that the grouping of operators is visible.
+    pub binary_chain_indent: BinaryChainIndent,
+    /// How a call is formatted when its last argument is an anonymous function which spans multiple lines.
+    /// * if set to [`LastArgCallback::Hug`] then the function stays on the same line as the call, e.g. `foo(function()`.
+    /// * if set to [`LastArgCallback::Wrap`] then the call is expanded, with each argument on its own line.
+    pub last_arg_callback: LastArgCallback,
+    /// How tables where every field is keyed and has an anonymous function value are formatted.
+    /// * if set to [`DispatchTableStyle::Default`] then these tables are formatted like any other table.
+    /// * if set to [`DispatchTableStyle::Switch`] then every entry is placed on its own line, and function bodies are
+    ///   always expanded beneath their key, even when [`Config::collapse_simple_statement`] would collapse them.
+    pub dispatch_table_style: DispatchTableStyle,
+    /// Whether symbolic unary operators (`-`, `#` and `~`) are followed by a space. `not` is always followed by a single space.
+    /// * if set to [`UnaryOperatorSpacing::Tight`] then the operator is placed directly before its operand, e.g. `-x`.
+    /// * if set to [`UnaryOperatorSpacing::Spaced`] then a single space is placed after the operator, e.g. `- x`.
+    pub unary_operator_spacing: UnaryOperatorSpacing,
+    /// The maximum width of a single table element before the table is forced onto multiple lines, even if the
+    /// whole table would fit within [`Config::column_width`]. Set to `0` to disable this limit.
+    /// As with the width of the whole table, this is measured from the element as written in the input.
+    pub table_element_max_width: usize,
+    /// Whether a chain of `.name` indexes which does not fit within [`Config::column_width`], such as `a.b.c.d`,
+    /// should be broken before each `.`, with every index placed on its own indented line.
+    pub wrap_index_chains: bool,
+    /// Whether a table being broken over multiple lines causes the tables nested within it to be broken too.
+    /// * if set to [`NestedTableExpansion::Independent`] then every table is measured independently.
+    /// * if set to [`NestedTableExpansion::Propagate`] then the non-empty tables which are the values of the fields
+    ///   of a multiline table are also formatted over multiple lines, even if they would fit on a single line.
+    pub nested_table_expansion: NestedTableExpansion,
+    /// How an anonymous function which spans multiple lines is formatted when it is the only value of an assignment.
+    /// This is the assignment equivalent of [`Config::last_arg_callback`].
+    /// * if set to [`AssignedFunction::Hug`] then the function stays on the same line as the equals token,
+    ///   e.g. `local handler = function()`.
+    /// * if set to [`AssignedFunction::Wrap`] then the function is placed on its own line after the equals token.
+    pub assigned_function: AssignedFunction,
+    /// How a call is formatted when its only argument is a table which spans multiple lines.
+    /// * if set to [`SingleTableArgument::Hug`] then the table braces stay against the parentheses, e.g. `f({`.
+    /// * if set to [`SingleTableArgument::Wrap`] then the call is expanded, with the table on its own line.
+    pub single_table_argument: SingleTableArgument,
+    /// Whether a newline gap is kept between the comments at the start of a block and its first statement.
+    /// This is separate to [`Config::block_newline_gaps`], which only applies to gaps before the comments.
+    /// * if set to [`LeadingCommentNewlineGap::Preserve`] then a newline gap is kept if present in input.
+    /// * if set to [`LeadingCommentNewlineGap::Never`] then a newline gap is never allowed.
+    /// * if set to [`LeadingCommentNewlineGap::Always`] then a newline gap is always placed after the comments.
+    pub leading_comment_newline_gap: LeadingCommentNewlineGap,
+    /// How comments written between the parameters of a function are handled.
+    /// * if set to [`ParameterComments::Wrap`] then any comment forces the parameters onto multiple lines.
+    /// * if set to [`ParameterComments::Inline`] then block comments written on a single line are kept in place,
+    ///   e.g. `function f(a, --[[reserved]] b)`.
+    pub parameter_comments: ParameterComments,
 }
 
 #[cfg_attr(all(target_arch = "wasm32", feature = "wasm-bindgen"), wasm_bindgen)]
@@ -303,8 +692,24 @@ impl Default for Config {
             call_parentheses: CallParenType::default(),
             collapse_simple_statement: CollapseSimpleStatement::default(),
             sort_requires: SortRequiresConfig::default(),
+            normalize_elseif: false,
             space_after_function_names: SpaceAfterFunctionNames::default(),
             block_newline_gaps: BlockNewlineGaps::default(),
+            skip_statement_kinds: StatementKinds::default(),
+            if_expression_style: IfExpressionStyle::default(),
+            return_type_style: ReturnTypeStyle::default(),
+            return_parentheses: ReturnParentheses::default(),
+            binary_chain_indent: BinaryChainIndent::default(),
+            last_arg_callback: LastArgCallback::default(),
+            dispatch_table_style: DispatchTableStyle::default(),
+            unary_operator_spacing: UnaryOperatorSpacing::default(),
+            table_element_max_width: 0,
+            wrap_index_chains: false,
+            nested_table_expansion: NestedTableExpansion::default(),
+            assigned_function: AssignedFunction::default(),
+            single_table_argument: SingleTableArgument::default(),
+            leading_comment_newline_gap: LeadingCommentNewlineGap::default(),
+            parameter_comments: ParameterComments::default(),
         }
     }
 }
@@ -315,6 +720,11 @@ impl Default for Config {
 pub enum OutputVerification {
     /// Reparse the generated output to detect any changes to code correctness.
     Full,
+    /// Perform [`OutputVerification::Full`] verification, and additionally check that the bytes within every
+    /// comment and string literal are preserved exactly. Only the changes the formatter intends to make are allowed,
+    /// such as changing string quotes, removing unnecessary escapes, trimming trailing whitespace from comments
+    /// and converting line endings.
+    ByteFidelity,
     /// Perform no verification of the output.
     None,
 }
@@ -399,6 +809,9 @@ pub enum Error {
     /// The output AST after formatting differs from the input AST.
     #[error("INTERNAL WARNING: Output AST may be different to input AST. Code correctness may have changed. Please examine the formatting diff and report any issues at https://github.com/johnnymorganz/stylua/issues")]
     VerificationAstDifference,
+    /// The output changed the contents of a comment or string literal.
+    #[error("INTERNAL ERROR: Output changed the contents of a comment or string. Please report this at https://github.com/johnnymorganz/stylua/issues")]
+    VerificationByteDifference,
 }
 
 /// Formats given [`Ast`]
@@ -410,11 +823,12 @@ pub fn format_ast(
     verify_output: OutputVerification,
 ) -> Result<Ast, Error> {
     // Clone the input AST only if we are verifying, to later use for checking
-    let input_ast_for_verification = if let OutputVerification::Full = verify_output {
-        Some(input_ast.to_owned())
-    } else {
-        None
-    };
+    let input_ast_for_verification =
+        if let OutputVerification::Full | OutputVerification::ByteFidelity = verify_output {
+            Some(input_ast.to_owned())
+        } else {
+            None
+        };
 
     let ctx = Context::new(config, range);
 
@@ -424,6 +838,12 @@ pub fn format_ast(
         false => input_ast,
     };
 
+    // Rewrite nested `else if` statements into `elseif` beforehand if necessary
+    let input_ast = match config.normalize_elseif {
+        true => normalize_elseif::normalize_elseif(&ctx, input_ast),
+        false => input_ast,
+    };
+
     let code_formatter = formatters::CodeFormatter::new(ctx);
     let ast = code_formatter.format(input_ast);
 
@@ -438,6 +858,12 @@ pub fn format_ast(
                 }
             };
 
+        if let OutputVerification::ByteFidelity = verify_output {
+            if !verify_ast::compare_comment_and_string_bytes(&input_ast, &reparsed_output) {
+                return Err(Error::VerificationByteDifference);
+            }
+        }
+
         let mut ast_verifier = verify_ast::AstVerifier::new();
         if !ast_verifier.compare(input_ast, reparsed_output) {
             return Err(Error::VerificationAstDifference);
@@ -468,6 +894,131 @@ pub fn format_code(
     Ok(output)
 }
 
+/// Formats given Lua code, additionally returning warnings about any non-obvious decisions made by the formatter,
+/// such as keeping a semicolon to avoid ambiguous syntax.
+///
+/// When formatting the whole file (i.e. no range is provided), a [`WarningKind::LineTooLong`] warning is also
+/// returned for each line of the output which remains wider than [`Config::column_width`].
+#[allow(clippy::result_large_err)]
+pub fn format_code_verbose(
+    code: &str,
+    config: Config,
+    range: Option<Range>,
+    verify_output: OutputVerification,
+) -> Result<(String, Vec<Warning>), Error> {
+    let (output, warnings) = warnings::collect_warnings(|| {
+        let output = format_code(code, config, range, verify_output)?;
+        if range.is_none() {
+            warnings::push_line_too_long_warnings(&output, config);
+        }
+        Ok(output)
+    });
+
+    output.map(|output| (output, warnings))
+}
+
+/// Formats given Lua code, additionally mapping the cursor, given as a byte offset into the code, to its new position
+/// in the output. This allows editors to restore the cursor after formatting.
+///
+/// The cursor moves along with the code surrounding it, so a cursor before a statement which gains indentation
+/// remains before the statement. If the text under the cursor is removed or replaced, the cursor is placed at the
+/// start of the replacement.
+#[allow(clippy::result_large_err)]
+pub fn format_code_with_cursor(
+    code: &str,
+    config: Config,
+    cursor: usize,
+    range: Option<Range>,
+    verify_output: OutputVerification,
+) -> Result<(String, usize), Error> {
+    let output = format_code(code, config, range, verify_output)?;
+    let cursor = cursor::map_cursor(code, &output, cursor);
+
+    Ok((output, cursor))
+}
+
+/// Formats given Lua code, returning the edits to apply to the code to produce the formatted output, rather than the
+/// output itself. This is useful for editors and language servers which apply changes to a document.
+///
+/// Positions are given as lines and UTF-16 code units, as used by the Language Server Protocol. With the `lsp` feature,
+/// each [`Edit`] can be converted into an `lsp_types::TextEdit`.
+#[allow(clippy::result_large_err)]
+pub fn format_code_edits(
+    code: &str,
+    config: Config,
+    range: Option<Range>,
+    verify_output: OutputVerification,
+) -> Result<Vec<Edit>, Error> {
+    let output = format_code(code, config, range, verify_output)?;
+    Ok(edits::compute_edits(code, &output))
+}
+
+/// Runs a single round-trip check over the given input, for use as a fuzzing target.
+///
+/// The input is parsed, formatted, and the output reparsed and compared against the original AST,
+/// as with [`OutputVerification::Full`]. Inputs which are not valid UTF-8 or cannot be parsed are ignored.
+///
+/// # Panics
+///
+/// Panics if the formatted output fails to parse, or if its AST differs from the input AST.
+pub fn fuzz_one(data: &[u8]) {
+    let code = match std::str::from_utf8(data) {
+        Ok(code) => code,
+        Err(_) => return,
+    };
+
+    match format_code(code, Config::default(), None, OutputVerification::Full) {
+        Ok(_) | Err(Error::ParseError(_)) => (),
+        Err(error) => panic!("{}\ninput:\n{}", error, code),
+    }
+}
+
+/// Measures the width of the given text in the same way the formatter does when comparing lines against
+/// [`Config::column_width`]. If the text spans multiple lines, the width of the widest line is returned.
+///
+/// Leading tabs are treated as indentation, and take up [`Config::indent_width`] each. All other content
+/// is measured by its length in bytes, so tabs within a line count as a single column, and wide characters
+/// (e.g. CJK) count as the length of their UTF-8 encoding.
+pub fn display_width(text: &str, config: Config) -> usize {
+    text.lines()
+        .map(|line| {
+            let content = line.trim_start_matches('\t');
+            (line.len() - content.len()) * config.indent_width + content.len()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Normalizes only the whitespace of the given Lua code, as a faster alternative to [`format_code`] for very large files.
+///
+/// The code is tokenized, but not parsed, and the following changes are made:
+/// - trailing whitespace is removed from every line
+/// - the whitespace at the start of every line is converted to the configured [`Config::indent_type`].
+///   Tabs count as [`Config::indent_width`] columns, and columns which do not make up a full indent level are kept as spaces
+/// - line endings are converted to the configured [`Config::line_endings`]
+/// - the code ends with a single line ending
+///
+/// Unlike [`format_code`], the indentation of a line is not computed from the structure of the code, so code which
+/// is incorrectly indented remains so. No other formatting (e.g. line wrapping, spacing or quotes) is performed.
+/// Strings and multi-line comments are left untouched, and `-- stylua: ignore start` / `-- stylua: ignore end`
+/// are respected, but `-- stylua: ignore` before a single statement is not.
+#[allow(clippy::result_large_err)]
+pub fn normalize_whitespace(code: &str, config: Config) -> Result<String, Error> {
+    whitespace::normalize_whitespace(code, config)
+}
+
+/// Formats only the comments of the given Lua code, leaving all other code byte-for-byte identical.
+///
+/// Comments are changed in the same way as by [`format_code`]: trailing whitespace is removed from single-line comments,
+/// and the line endings within multi-line comments are converted to the configured [`Config::line_endings`].
+/// The code is tokenized, but not parsed, so it does not need to be valid syntax, other than being tokenizable.
+/// `-- stylua: ignore start` / `-- stylua: ignore end` are respected, but `-- stylua: ignore` before a single
+/// statement is not.
+#[allow(clippy::result_large_err)]
+pub fn format_comments(code: &str, config: Config) -> Result<String, Error> {
+    comments::format_comments(code, config)
+}
+
 #[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
 #[wasm_bindgen(js_name = formatCode)]
 pub fn format_code_wasm(
@@ -506,6 +1057,39 @@ mod tests {
         assert!(matches!(output, Err(Error::ParseError(_))))
     }
 
+    #[test]
+    fn test_call_trailing_comma() {
+        // Trailing commas are not valid in call arguments for any syntax, so they are rejected rather than removed
+        for syntax in [LuaVersion::Lua51, LuaVersion::All] {
+            let output = format_code(
+                "foo(a, b,)",
+                Config {
+                    syntax,
+                    ..Config::default()
+                },
+                None,
+                OutputVerification::None,
+            );
+            assert!(matches!(output, Err(Error::ParseError(_))))
+        }
+
+        // Expanding a call onto multiple lines does not add a trailing comma
+        let output = format_code(
+            "foo(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccc)",
+            Config {
+                column_width: 40,
+                ..Config::default()
+            },
+            None,
+            OutputVerification::Full,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "foo(\n\taaaaaaaaaaaaaaaaaaaa,\n\tbbbbbbbbbbbbbbbbbbbb,\n\tcccccccccccccccccccc\n)\n"
+        );
+    }
+
     #[test]
     fn test_with_ast_verification() {
         let output = format_code(
@@ -517,4 +1101,266 @@ mod tests {
         .unwrap();
         assert_eq!(output, "local x = 1\n");
     }
+
+    #[test]
+    fn test_verbose_entry_point_without_warnings() {
+        let (output, warnings) = format_code_verbose(
+            "local   x   =    1",
+            Config::default(),
+            None,
+            OutputVerification::None,
+        )
+        .unwrap();
+        assert_eq!(output, "local x = 1\n");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_verbose_semicolon_preserved() {
+        let (output, warnings) = format_code_verbose(
+            "local x = f;\n(g or h)()",
+            Config::default(),
+            None,
+            OutputVerification::None,
+        )
+        .unwrap();
+        assert_eq!(output, "local x = f;\n(g or h)()\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::SemicolonPreserved);
+        assert_eq!(
+            warnings[0].to_string(),
+            "1:12: semicolon preserved to avoid ambiguous syntax"
+        );
+    }
+
+    #[test]
+    fn test_verbose_comment_moved() {
+        let (output, warnings) = format_code_verbose(
+            "local x = 1; -- comment\nreturn x; -- other",
+            Config::default(),
+            None,
+            OutputVerification::None,
+        )
+        .unwrap();
+        assert_eq!(output, "local x = 1 -- comment\nreturn x -- other\n");
+        let kinds: Vec<_> = warnings.iter().map(|warning| warning.kind).collect();
+        assert_eq!(
+            kinds,
+            [WarningKind::CommentMoved, WarningKind::CommentMoved]
+        );
+    }
+
+    #[test]
+    fn test_verbose_line_too_long() {
+        let config = Config {
+            column_width: 40,
+            ..Config::default()
+        };
+        let (output, warnings) = format_code_verbose(
+            "local short = 1\nlocal message = \"this string literal cannot be split onto multiple lines\"",
+            config,
+            None,
+            OutputVerification::None,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "local short = 1\nlocal message =\n\t\"this string literal cannot be split onto multiple lines\"\n"
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].kind,
+            WarningKind::LineTooLong { line: 3, width: 61 }
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "3: line is 61 columns wide, which is over the column width"
+        );
+    }
+
+    #[test]
+    fn test_fuzz_one_seed_corpus() {
+        let corpus =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/round_trip");
+        for entry in std::fs::read_dir(corpus).unwrap() {
+            fuzz_one(&std::fs::read(entry.unwrap().path()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fuzz_one_ignores_invalid_input() {
+        fuzz_one(&[0xff, 0xfe, 0xfd]);
+        fuzz_one(b"local   x   = ");
+    }
+
+    #[test]
+    fn test_byte_fidelity_verification() {
+        let input = "local   x = 'caf\u{e9} \\xE9\\255 \\\"' --   \u{ff}\u{fe}\u{c3}\u{a9}\u{fffd}   \r\n--[[ \u{80}\u{9f}  \r\n  ]]\nreturn   x";
+        let output = format_code(
+            input,
+            Config::default(),
+            None,
+            OutputVerification::ByteFidelity,
+        )
+        .unwrap();
+        assert_eq!(output, "local x = 'caf\u{e9} \\xE9\\255 \"' --   \u{ff}\u{fe}\u{c3}\u{a9}\u{fffd}\n--[[ \u{80}\u{9f}  \n  ]]\nreturn x\n");
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let config = Config {
+            indent_type: IndentType::Spaces,
+            indent_width: 2,
+            ..Config::default()
+        };
+        let output = normalize_whitespace(
+            "local x = {   \r\n\ta = 1,  -- comment  \r\n   b = 2,\r\n}\r\n\r\n\r\n",
+            config,
+        )
+        .unwrap();
+        assert_eq!(output, "local x = {\n  a = 1,  -- comment\n   b = 2,\n}\n");
+    }
+
+    #[test]
+    fn test_normalize_whitespace_keeps_strings_and_ignored_code() {
+        let input = "local x = [[  \n    y  ]]   \n--[[  \n  ]]\n-- stylua: ignore start\n    local   z  \n-- stylua: ignore end\n    return x  \n";
+        let output = normalize_whitespace(input, Config::default()).unwrap();
+        assert_eq!(output, "local x = [[  \n    y  ]]\n--[[  \n  ]]\n-- stylua: ignore start\n    local   z  \n-- stylua: ignore end\n\treturn x\n");
+    }
+
+    #[test]
+    fn test_normalize_whitespace_invalid_input() {
+        let output = normalize_whitespace("local x = \"unterminated", Config::default());
+        assert!(matches!(output, Err(Error::ParseError(_))))
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let output = format_comments(
+            "#!/usr/bin/env lua   \nlocal   x =   {1,2}  -- comment   \n--[[ multi\r\nline ]]   print( x )\n",
+            Config::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "#!/usr/bin/env lua\nlocal   x =   {1,2}  -- comment\n--[[ multi\nline ]]   print( x )\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comments_keeps_code_bytes() {
+        let code = "local  t = {   -- first   \n\ta=1,--second\t\n  b = \"-- not a comment   \",\r\n}   \n\n\n";
+        let output = format_comments(code, Config::default()).unwrap();
+        assert_eq!(
+            output,
+            "local  t = {   -- first\n\ta=1,--second\n  b = \"-- not a comment   \",\r\n}   \n\n\n"
+        );
+
+        let code_tokens = |code: &str| {
+            whitespace::tokenize(code, Config::default())
+                .unwrap()
+                .into_iter()
+                .filter(|token| {
+                    !matches!(
+                        token.token_kind(),
+                        full_moon::tokenizer::TokenKind::SingleLineComment
+                            | full_moon::tokenizer::TokenKind::MultiLineComment
+                    )
+                })
+                .map(|token| token.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(code_tokens(&output), code_tokens(code));
+    }
+
+    #[test]
+    fn test_format_comments_keeps_ignored_comments() {
+        let code = "-- stylua: ignore start\nlocal x = 1 -- kept   \n-- stylua: ignore end\nlocal y = 2 -- trimmed   \n";
+        let output = format_comments(code, Config::default()).unwrap();
+        assert_eq!(
+            output,
+            "-- stylua: ignore start\nlocal x = 1 -- kept   \n-- stylua: ignore end\nlocal y = 2 -- trimmed\n"
+        );
+    }
+
+    #[test]
+    fn test_display_width_tabs() {
+        let config = Config {
+            indent_width: 2,
+            ..Config::default()
+        };
+        assert_eq!(display_width("local x = 1", config), 11);
+        assert_eq!(display_width("\t\tlocal x = 1", config), 15);
+        assert_eq!(display_width("\tlocal x = '\t'", config), 15);
+        assert_eq!(display_width("do\n\t\tlocal x = 1\nend", config), 15);
+    }
+
+    #[test]
+    fn test_display_width_wide_characters() {
+        let config = Config::default();
+        assert_eq!(display_width("日本語", config), 9);
+        assert_eq!(display_width("local x = \"日本語\"", config), 21);
+        assert_eq!(display_width("", config), 0);
+    }
+
+    #[test]
+    fn test_literal_tabs_preserved() {
+        // Only indentation is converted to the indent type, tabs within strings and comments are kept as written
+        let input = "do\n\tlocal x = \"a\tb\" -- a\tcomment\n\tlocal y = [[\n\tliteral\n]]\n\t--[[\tblock\t]]\nend\n";
+        let config = Config {
+            indent_type: IndentType::Spaces,
+            ..Config::default()
+        };
+        let expected = "do\n    local x = \"a\tb\" -- a\tcomment\n    local y = [[\n\tliteral\n]]\n    --[[\tblock\t]]\nend\n";
+
+        let output = format_code(input, config, None, OutputVerification::Full).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(normalize_whitespace(input, config).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_code_with_cursor() {
+        let format = |code: &str, cursor: usize| {
+            format_code_with_cursor(
+                code,
+                Config::default(),
+                cursor,
+                None,
+                OutputVerification::None,
+            )
+            .unwrap()
+        };
+
+        // The cursor is moved through the inserted indentation, remaining before the statement
+        let (output, cursor) = format("do\nlocal x = 1\nend", 3);
+        assert_eq!(output, "do\n\tlocal x = 1\nend\n");
+        assert_eq!(&output[cursor..], "local x = 1\nend\n");
+
+        // The cursor stays in the middle of an identifier
+        let (output, cursor) = format("do\nlocal foo = 1\nend", 11);
+        assert_eq!(&output[cursor..], "o = 1\nend\n");
+
+        // A cursor within removed whitespace is placed where the whitespace was
+        let (output, cursor) = format("local   x = 1", 7);
+        assert_eq!(output, "local x = 1\n");
+        assert_eq!(&output[cursor..], "x = 1\n");
+
+        // A cursor at the end of the code is placed at the end of the output
+        let (output, cursor) = format("local x = 1", 11);
+        assert_eq!(cursor, output.len());
+    }
+
+    #[test]
+    fn test_format_code_with_cursor_multibyte() {
+        // Offsets are in bytes, so characters before the cursor wider than a byte are accounted for
+        let (output, cursor) = format_code_with_cursor(
+            "do\nlocal x = '日本語' local y = 1\nend",
+            Config::default(),
+            25,
+            None,
+            OutputVerification::None,
+        )
+        .unwrap();
+        assert_eq!(&output[cursor..], "local y = 1\nend\n");
+    }
 }
@@ -0,0 +1,144 @@
+//! Normalize Elseif CodeMod
+//! This is an optional extension which will rewrite nested `else if` statements into `elseif` clauses before formatting the file
+//!
+//! An `else` block is only merged into its parent if statement when it contains exactly one statement, which is an if statement,
+//! as at that point the two forms are structurally equivalent:
+//!
+//! ```lua
+//! if a then
+//!     foo()
+//! else
+//!     if b then
+//!         bar()
+//!     end
+//! end
+//! ```
+//!
+//! becomes
+//!
+//! ```lua
+//! if a then
+//!     foo()
+//! elseif b then
+//!     bar()
+//! end
+//! ```
+//!
+//! If there are any comments on the tokens which would be removed (the `else` and the nested `if` and `end`),
+//! the statement is left alone, so that we do not lose or misplace the comments.
+
+use full_moon::{
+    ast::{Ast, Block, ElseIf, If, LastStmt, Stmt},
+    tokenizer::{Symbol, Token, TokenReference, TokenType},
+    visitors::VisitorMut,
+};
+
+use crate::{
+    context::{Context, FormatNode},
+    formatters::trivia_util,
+};
+
+/// Returns the nested if statement, if it is the only statement in the `else` block of the given if statement
+fn nested_else_if(if_node: &If) -> Option<&If> {
+    let block = if_node.else_block()?;
+    let mut stmts = block.stmts_with_semicolon();
+
+    match (stmts.next(), stmts.next(), block.last_stmt()) {
+        (Some((Stmt::If(nested), None)), None, None) => Some(nested),
+        _ => None,
+    }
+}
+
+/// Whether merging the nested if statement into an `elseif` would remove any comments
+fn merge_would_remove_comments(else_token: &TokenReference, nested: &If) -> bool {
+    else_token
+        .trailing_trivia()
+        .chain(nested.if_token().leading_trivia())
+        .chain(nested.end_token().leading_trivia())
+        .chain(nested.end_token().trailing_trivia())
+        .any(trivia_util::trivia_is_comment)
+}
+
+/// Repeatedly merges an `else` block containing only a nested if statement into an `elseif` clause.
+/// If `preserve_comments` is set, we stop merging when doing so would remove a comment.
+pub(crate) fn merge_else_if(mut if_node: If, preserve_comments: bool) -> If {
+    loop {
+        let (Some(else_token), Some(nested)) = (if_node.else_token(), nested_else_if(&if_node))
+        else {
+            return if_node;
+        };
+
+        if preserve_comments && merge_would_remove_comments(else_token, nested) {
+            return if_node;
+        }
+
+        let else_if_token = TokenReference::new(
+            else_token.leading_trivia().cloned().collect(),
+            Token::new(TokenType::Symbol {
+                symbol: Symbol::ElseIf,
+            }),
+            nested.if_token().trailing_trivia().cloned().collect(),
+        );
+        let nested = nested.to_owned();
+
+        let mut else_ifs = if_node.else_if().cloned().unwrap_or_default();
+        else_ifs.push(
+            ElseIf::new(nested.condition().to_owned())
+                .with_else_if_token(else_if_token)
+                .with_then_token(nested.then_token().to_owned())
+                .with_block(nested.block().to_owned()),
+        );
+        else_ifs.extend(nested.else_if().cloned().unwrap_or_default());
+
+        if_node = if_node
+            .with_else_if(Some(else_ifs))
+            .with_else_token(nested.else_token().cloned())
+            .with_else(nested.else_block().cloned());
+    }
+}
+
+struct ElseIfNormalizer {
+    ctx: Context,
+    /// The contexts of the enclosing blocks, so that toggling formatting within a block does not leak out of it
+    parent_contexts: Vec<Context>,
+}
+
+impl VisitorMut for ElseIfNormalizer {
+    fn visit_block(&mut self, node: Block) -> Block {
+        self.parent_contexts.push(self.ctx);
+        node
+    }
+
+    fn visit_block_end(&mut self, node: Block) -> Block {
+        if let Some(ctx) = self.parent_contexts.pop() {
+            self.ctx = ctx;
+        }
+        node
+    }
+
+    fn visit_stmt(&mut self, node: Stmt) -> Stmt {
+        self.ctx = self.ctx.check_toggle_formatting(&node);
+        node
+    }
+
+    fn visit_last_stmt(&mut self, node: LastStmt) -> LastStmt {
+        self.ctx = self.ctx.check_toggle_formatting(&node);
+        node
+    }
+
+    fn visit_if(&mut self, node: If) -> If {
+        match self.ctx.should_format_node(&node) {
+            FormatNode::Normal => merge_else_if(node, true),
+            _ => node,
+        }
+    }
+}
+
+pub(crate) fn normalize_elseif(ctx: &Context, input_ast: Ast) -> Ast {
+    let mut normalizer = ElseIfNormalizer {
+        ctx: *ctx,
+        parent_contexts: Vec::new(),
+    };
+
+    normalizer.visit_ast(input_ast)
+}
@@ -95,6 +95,14 @@ impl Indent {
             ..*self
         }
     }
+
+    /// Increases the block indentation level by amount specified
+    pub fn add_block_indent_level(&self, amount: usize) -> Self {
+        Self {
+            block_indent: self.block_indent.saturating_add(amount),
+            ..*self
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -2,7 +2,7 @@ use full_moon::{
     ast::{
         punctuated::{Pair, Punctuated},
         span::ContainedSpan,
-        Ast, Block, Expression, FunctionArgs, TableConstructor,
+        Ast, Block, Expression, FunctionArgs, If, TableConstructor,
     },
     node::Node,
     tokenizer::{StringLiteralQuoteType, Token, TokenReference, TokenType},
@@ -31,7 +31,24 @@ impl AstVerifier {
 
 fn remove_parentheses(expression: Expression) -> Expression {
     match expression {
-        Expression::Parentheses { expression, .. } => *expression,
+        Expression::Parentheses {
+            contained,
+            expression,
+        } => match remove_parentheses(*expression) {
+            // Parentheses truncate function calls and varargs to a single value, so we keep a single
+            // layer of them to ensure their removal is still flagged
+            inner @ Expression::FunctionCall(_) => Expression::Parentheses {
+                contained,
+                expression: Box::new(inner),
+            },
+            Expression::Symbol(symbol) if symbol.token().to_string() == "..." => {
+                Expression::Parentheses {
+                    contained,
+                    expression: Box::new(Expression::Symbol(symbol)),
+                }
+            }
+            inner => inner,
+        },
         _ => expression,
     }
 }
@@ -69,6 +86,12 @@ impl VisitorMut for AstVerifier {
         node.with_stmts(stmts).with_last_stmt(last_stmt)
     }
 
+    fn visit_if(&mut self, node: If) -> If {
+        // We may merge an `else` block containing only an if statement into an `elseif`.
+        // We will normalise all such statements into their `elseif` form
+        crate::normalize_elseif::merge_else_if(node, false)
+    }
+
     fn visit_table_constructor(&mut self, node: TableConstructor) -> TableConstructor {
         // We change semicolon field separators to commas
         // We will replace all field separators with commas, and include a trailing comma
@@ -187,6 +210,96 @@ impl VisitorMut for AstVerifier {
     }
 }
 
+/// Returns the value of a string literal, so that changes to the quotes used and removals of unnecessary escapes
+/// are not flagged. All other escape sequences are kept as they are written.
+fn string_literal_value(literal: &str, quote_type: StringLiteralQuoteType) -> String {
+    if let StringLiteralQuoteType::Brackets = quote_type {
+        return literal.replace("\r\n", "\n");
+    }
+
+    let mut value = String::with_capacity(literal.len());
+    let mut characters = literal.chars();
+
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            value.push(character);
+            continue;
+        }
+
+        match characters.next() {
+            Some(
+                escaped @ ('\n'
+                | '\r'
+                | '0'..='9'
+                | '\\'
+                | 'a'
+                | 'b'
+                | 'f'
+                | 'n'
+                | 'r'
+                | 't'
+                | 'u'
+                | 'v'
+                | 'x'
+                | 'z'),
+            ) => {
+                value.push('\\');
+                value.push(escaped);
+            }
+            Some(escaped) => value.push(escaped),
+            None => value.push('\\'),
+        }
+    }
+
+    value
+}
+
+/// Pushes the contents of any comments within the trivia, ignoring trailing whitespace of single line comments
+/// and the type of line endings used, as these are changed by the formatter
+fn push_comments<'a>(trivia: impl Iterator<Item = &'a Token>, contents: &mut Vec<String>) {
+    for token in trivia {
+        match token.token_type() {
+            TokenType::SingleLineComment { comment } => {
+                contents.push(format!("--{}", comment.trim_end()))
+            }
+            TokenType::MultiLineComment { comment, .. } => {
+                contents.push(format!("--[[{}", comment.replace("\r\n", "\n")))
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Collects the contents of all the comments and string literals within the AST
+fn comment_and_string_contents(ast: &Ast) -> Vec<String> {
+    let mut contents = Vec::new();
+
+    for token in ast.nodes().tokens().chain(std::iter::once(ast.eof())) {
+        push_comments(token.leading_trivia(), &mut contents);
+
+        if let TokenType::StringLiteral {
+            literal,
+            quote_type,
+            ..
+        } = token.token_type()
+        {
+            contents.push(format!("\"{}", string_literal_value(literal, *quote_type)));
+        }
+
+        push_comments(token.trailing_trivia(), &mut contents);
+    }
+
+    // Comments and statements may be moved around (e.g. when sorting requires), so the order is not checked
+    contents.sort();
+    contents
+}
+
+/// Checks that the bytes within every comment and string literal of the input are preserved in the output.
+/// Changes which the formatter performs on purpose, such as changing string quotes or line endings, are allowed.
+pub fn compare_comment_and_string_bytes(input_ast: &Ast, reparsed_output: &Ast) -> bool {
+    comment_and_string_contents(input_ast) == comment_and_string_contents(reparsed_output)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -209,6 +322,24 @@ mod tests {
         assert!(!ast_verifier.compare(input_ast, output_ast));
     }
 
+    #[test]
+    fn test_equivalent_nested_parentheses() {
+        let input_ast = full_moon::parse("return ((x)), (((f())))").unwrap();
+        let output_ast = full_moon::parse("return x, (f())").unwrap();
+
+        let mut ast_verifier = AstVerifier::new();
+        assert!(ast_verifier.compare(input_ast, output_ast));
+    }
+
+    #[test]
+    fn test_different_truncating_parentheses() {
+        let input_ast = full_moon::parse("return (f()), (...)").unwrap();
+        let output_ast = full_moon::parse("return f(), ...").unwrap();
+
+        let mut ast_verifier = AstVerifier::new();
+        assert!(!ast_verifier.compare(input_ast, output_ast));
+    }
+
     #[test]
     fn test_equivalent_stmt_semicolons() {
         let input_ast = full_moon::parse("local x = 1;").unwrap();
@@ -386,4 +517,30 @@ mod tests {
         let mut ast_verifier = AstVerifier::new();
         assert!(ast_verifier.compare(input_ast, output_ast));
     }
+
+    #[test]
+    fn test_equivalent_comment_and_string_bytes() {
+        let input_ast =
+            full_moon::parse("local x = 'caf\u{e9} \\\"\\q' -- \u{ff}\u{fe}  \r\n").unwrap();
+        let output_ast =
+            full_moon::parse("local x = \"caf\u{e9} \\\"q\" -- \u{ff}\u{fe}\n").unwrap();
+
+        assert!(compare_comment_and_string_bytes(&input_ast, &output_ast));
+    }
+
+    #[test]
+    fn test_different_comment_bytes() {
+        let input_ast = full_moon::parse("local x = 1 -- caf\u{e9}").unwrap();
+        let output_ast = full_moon::parse("local x = 1 -- cafe").unwrap();
+
+        assert!(!compare_comment_and_string_bytes(&input_ast, &output_ast));
+    }
+
+    #[test]
+    fn test_different_string_escapes() {
+        let input_ast = full_moon::parse("local x = '\\x41'").unwrap();
+        let output_ast = full_moon::parse("local x = 'A'").unwrap();
+
+        assert!(!compare_comment_and_string_bytes(&input_ast, &output_ast));
+    }
 }
@@ -0,0 +1,92 @@
+use full_moon::tokenizer::Position;
+use std::{cell::RefCell, fmt};
+
+/// The kind of non-obvious decision the formatter made
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WarningKind {
+    /// A semicolon was kept (or added) after a statement, as removing it would make the following
+    /// statement ambiguous, e.g. `local x = f; (g or h)()`
+    SemicolonPreserved,
+    /// Comments attached to a removed semicolon were moved to the end of the statement
+    CommentMoved,
+    /// A line of the output is still wider than [`Config::column_width`](crate::Config::column_width), as it
+    /// could not be split, e.g. because it contains a long string literal
+    LineTooLong {
+        /// The line number in the output, starting at 1
+        line: usize,
+        /// The width of the line, as measured by [`display_width`](crate::display_width)
+        width: usize,
+    },
+}
+
+/// A warning about a non-obvious decision the formatter made whilst formatting code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// The kind of warning
+    pub kind: WarningKind,
+    /// The position in the input code the warning refers to, if known
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(position) = self.position {
+            write!(formatter, "{}:{}: ", position.line(), position.character())?;
+        }
+
+        match self.kind {
+            WarningKind::SemicolonPreserved => {
+                write!(formatter, "semicolon preserved to avoid ambiguous syntax")
+            }
+            WarningKind::CommentMoved => write!(
+                formatter,
+                "comment moved to the end of the statement as the semicolon was removed"
+            ),
+            WarningKind::LineTooLong { line, width } => write!(
+                formatter,
+                "{}: line is {} columns wide, which is over the column width",
+                line, width
+            ),
+        }
+    }
+}
+
+thread_local! {
+    // Warnings are collected per-thread, so that the formatters do not need to thread a collector through
+    // the (immutable) Context. This is `None` when warnings are not being collected.
+    static WARNINGS: RefCell<Option<Vec<Warning>>> = const { RefCell::new(None) };
+}
+
+/// Records a warning, if warnings are currently being collected
+pub(crate) fn push_warning(kind: WarningKind, position: Option<Position>) {
+    WARNINGS.with(|warnings| {
+        if let Some(warnings) = warnings.borrow_mut().as_mut() {
+            warnings.push(Warning { kind, position });
+        }
+    })
+}
+
+/// Records a warning for every line of the output which is wider than the column width
+pub(crate) fn push_line_too_long_warnings(output: &str, config: crate::Config) {
+    for (index, line) in output.lines().enumerate() {
+        let width = crate::display_width(line, config);
+        if width > config.column_width {
+            push_warning(
+                WarningKind::LineTooLong {
+                    line: index + 1,
+                    width,
+                },
+                None,
+            );
+        }
+    }
+}
+
+/// Runs the given function, collecting all warnings recorded during it
+pub(crate) fn collect_warnings<T>(function: impl FnOnce() -> T) -> (T, Vec<Warning>) {
+    let previous = WARNINGS.with(|warnings| warnings.replace(Some(Vec::new())));
+    let output = function();
+    let collected = WARNINGS.with(|warnings| warnings.replace(previous));
+
+    (output, collected.unwrap_or_default())
+}
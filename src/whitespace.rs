@@ -0,0 +1,116 @@
+//! A fast path which only normalizes whitespace, without parsing or printing an AST.
+//! The code is tokenized, and only whitespace tokens (and the trailing whitespace of single-line comments) are
+//! updated. All other tokens, such as strings and multi-line comments, are output as they are written in the input.
+
+use full_moon::tokenizer::{Lexer, LexerResult, Token, TokenType};
+
+use crate::{context::line_ending_character, Config, Error, IndentType};
+
+/// Converts the whitespace at the start of a line to the configured indent type.
+/// Tabs count as [`Config::indent_width`] columns, and any columns which do not make up a full indent level are
+/// kept as spaces.
+fn reindent(indent: &str, config: Config) -> String {
+    let width: usize = indent
+        .chars()
+        .map(|character| match character {
+            '\t' => config.indent_width,
+            _ => 1,
+        })
+        .sum();
+
+    match config.indent_type {
+        IndentType::Tabs => {
+            "\t".repeat(width / config.indent_width) + &" ".repeat(width % config.indent_width)
+        }
+        IndentType::Spaces => " ".repeat(width),
+    }
+}
+
+/// Tokenizes the code, without parsing it, returning any tokenizer errors as a [`Error::ParseError`]
+pub(crate) fn tokenize(code: &str, config: Config) -> Result<Vec<Token>, Error> {
+    match Lexer::new(code, config.syntax.into()).collect() {
+        LexerResult::Ok(tokens) => Ok(tokens),
+        LexerResult::Fatal(errors) | LexerResult::Recovered(_, errors) => Err(Error::ParseError(
+            errors
+                .into_iter()
+                .map(full_moon::Error::TokenizerError)
+                .collect(),
+        )),
+    }
+}
+
+pub(crate) fn normalize_whitespace(code: &str, config: Config) -> Result<String, Error> {
+    let tokens = tokenize(code, config)?;
+
+    let line_ending = line_ending_character(config.line_endings);
+    let mut output = String::with_capacity(code.len());
+    // Whitespace which has not yet been output, as we do not yet know whether it is trailing whitespace
+    let mut pending_whitespace = String::new();
+    let mut at_line_start = true;
+    let mut formatting_disabled = false;
+
+    for token in tokens {
+        match token.token_type() {
+            TokenType::Whitespace { characters } if !formatting_disabled => {
+                let mut lines = characters.split('\n');
+                pending_whitespace.push_str(lines.next().unwrap_or_default());
+
+                for line in lines {
+                    output.push_str(&line_ending);
+                    pending_whitespace.clear();
+                    pending_whitespace.push_str(line);
+                    at_line_start = true;
+                }
+
+                // Any carriage return is part of the line ending we have replaced
+                if pending_whitespace.ends_with('\r') {
+                    pending_whitespace.pop();
+                }
+            }
+            TokenType::Eof => (),
+            token_type => {
+                if !formatting_disabled {
+                    if at_line_start {
+                        output.push_str(&reindent(&pending_whitespace, config));
+                    } else {
+                        output.push_str(&pending_whitespace);
+                    }
+                }
+                pending_whitespace.clear();
+                at_line_start = false;
+
+                match token_type {
+                    TokenType::SingleLineComment { comment } => {
+                        match comment.trim() {
+                            "stylua: ignore start" => formatting_disabled = true,
+                            "stylua: ignore end" => formatting_disabled = false,
+                            _ => (),
+                        }
+
+                        if formatting_disabled {
+                            output.push_str(&token.to_string());
+                        } else {
+                            output.push_str(
+                                &Token::new(TokenType::SingleLineComment {
+                                    comment: comment.trim_end().into(),
+                                })
+                                .to_string(),
+                            );
+                        }
+                    }
+                    _ => output.push_str(&token.to_string()),
+                }
+            }
+        }
+    }
+
+    // Ensure the code ends with a single line ending
+    if !formatting_disabled {
+        output.truncate(output.trim_end_matches(['\r', '\n']).len());
+        if !output.is_empty() {
+            output.push_str(&line_ending);
+        }
+    }
+
+    Ok(output)
+}
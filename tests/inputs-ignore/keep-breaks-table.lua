@@ -0,0 +1,31 @@
+-- stylua: keep-breaks
+local points = {
+  0,0,   10,0,
+     10,10,0,10
+}
+
+-- stylua: keep-breaks
+local nested = {
+	{ 1,2,
+	  3,4 },
+	{ x=1,y=2, z=3 }
+}
+
+-- stylua: keep-breaks
+local singleline = {1,2,3,4}
+
+local expanded = {
+	1, 2,
+	3, 4,
+}
+
+local tbl = {
+	-- stylua: keep-breaks
+	offsets = {
+		-1,-1, 1,-1,
+		-1,1, 1,1
+	},
+	other = {
+		1, 2
+	},
+}
@@ -0,0 +1,5 @@
+local Roact = require 'Roact'
+local Llama = require("Llama")
+local Cryo = require [[Cryo]]
+local Dash = require('Dash')
+local Array = require "Array"
@@ -0,0 +1,25 @@
+local function process(items)
+	for _, item in ipairs(items) do
+		if item.enabled then
+			item.callback = function()
+				if item.value then
+					print(item.value)
+				end
+			end
+		end
+	end
+end
+
+local function compact(items)
+	for _, item in ipairs(items) do if item.enabled then item.callback = function() print(item.value) end end end
+end
+
+local function misindented(items)
+for _, item in ipairs(items) do
+if item.enabled then
+    item.callback = function()
+            return item.value
+  end
+      end
+    end
+        end
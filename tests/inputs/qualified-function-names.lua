@@ -0,0 +1,14 @@
+function M.a.b:c() end
+function   M . a . b : c ( x , y ) return x end
+function M.aVeryLongModuleName.anotherVeryLongSubmoduleName.yetAnotherSubmodule:someVeryLongMethodName(argumentOne, argumentTwo) return argumentOne end
+
+-- comments within the name are moved before the definition
+function M.a -- comment
+.b:c() end
+function M.a.b: -- comment
+c() end
+function M.a--[[ comment ]].b:c() end
+function -- comment
+M.a.b:c() end
+local function -- comment
+foo() end
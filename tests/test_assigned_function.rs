@@ -0,0 +1,78 @@
+use stylua_lib::{format_code, AssignedFunction, Config, OutputVerification};
+
+fn format(input: &str, assigned_function: AssignedFunction) -> String {
+    format_code(
+        input,
+        Config {
+            assigned_function,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local handler = function(a, b) local c = a + b print(c) return c end
+self.callbacks.onClick = function() print("clicked") end
+local empty = function() end
+local first, second = function() return 1 end, function() return 2 end
+"###;
+
+#[test]
+fn test_hug() {
+    insta::assert_snapshot!(format(STARTINGCODE, AssignedFunction::Hug), @r###"
+        local handler = function(a, b)
+        	local c = a + b
+        	print(c)
+        	return c
+        end
+        self.callbacks.onClick = function()
+        	print("clicked")
+        end
+        local empty = function() end
+        local first, second = function()
+        	return 1
+        end, function()
+        	return 2
+        end
+    "###);
+}
+
+#[test]
+fn test_wrap() {
+    insta::assert_snapshot!(format(STARTINGCODE, AssignedFunction::Wrap), @r###"
+        local handler =
+        	function(a, b)
+        		local c = a + b
+        		print(c)
+        		return c
+        	end
+        self.callbacks.onClick =
+        	function()
+        		print("clicked")
+        	end
+        local empty = function() end
+        local first, second = function()
+        	return 1
+        end, function()
+        	return 2
+        end
+    "###);
+}
+
+#[test]
+fn test_wrap_parenthesised_function() {
+    let output = format(
+        "x = (function()\n\tprint(1)\nend)\n",
+        AssignedFunction::Wrap,
+    );
+    insta::assert_snapshot!(output, @r###"
+    x =
+    	function()
+    		print(1)
+    	end
+    "###);
+    assert_eq!(format(&output, AssignedFunction::Wrap), output);
+}
@@ -0,0 +1,60 @@
+use stylua_lib::{format_code, BinaryChainIndent, Config, OutputVerification};
+
+fn format(input: &str, binary_chain_indent: BinaryChainIndent) -> String {
+    format_code(
+        input,
+        Config {
+            binary_chain_indent,
+            column_width: 80,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local a = someCondition or someVeryLongConditionName and anotherVeryLongValueName and yetAnotherValue or fallbackValue
+local b = someVeryLongConditionName and anotherVeryLongValueName or yetAnotherConditionName and theFinalFallbackValue
+if someCondition or someVeryLongConditionName and anotherVeryLongValueName and yetAnotherValueName or fallbackValue then
+end
+"###;
+
+#[test]
+fn test_minimal() {
+    insta::assert_snapshot!(format(STARTINGCODE, BinaryChainIndent::Minimal), @r###"
+        local a = someCondition
+        	or someVeryLongConditionName and anotherVeryLongValueName and yetAnotherValue
+        	or fallbackValue
+        local b = someVeryLongConditionName and anotherVeryLongValueName
+        	or yetAnotherConditionName and theFinalFallbackValue
+        if
+        	someCondition
+        	or someVeryLongConditionName and anotherVeryLongValueName and yetAnotherValueName
+        	or fallbackValue
+        then
+        end
+    "###);
+}
+
+#[test]
+fn test_precedence() {
+    insta::assert_snapshot!(format(STARTINGCODE, BinaryChainIndent::Precedence), @r###"
+        local a = someCondition
+        	or someVeryLongConditionName
+        		and anotherVeryLongValueName
+        		and yetAnotherValue
+        	or fallbackValue
+        local b = someVeryLongConditionName and anotherVeryLongValueName
+        	or yetAnotherConditionName and theFinalFallbackValue
+        if
+        	someCondition
+        	or someVeryLongConditionName
+        		and anotherVeryLongValueName
+        		and yetAnotherValueName
+        	or fallbackValue
+        then
+        end
+    "###);
+}
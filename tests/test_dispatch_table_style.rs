@@ -0,0 +1,87 @@
+use stylua_lib::{
+    format_code, CollapseSimpleStatement, Config, DispatchTableStyle, OutputVerification,
+};
+
+fn format(input: &str, dispatch_table_style: DispatchTableStyle) -> String {
+    format_code(
+        input,
+        Config {
+            dispatch_table_style,
+            collapse_simple_statement: CollapseSimpleStatement::Always,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local handlers = { [Action.Open] = function(state) state.open = true return state end, [Action.Close] = function(state) state.open = false end, [Action.Toggle] = function(state) state.open = not state.open end }
+local small = { [A] = function() end, [B] = function() return 1 end }
+local mixed = { [A] = function() return 1 end, 2 }
+"###;
+
+#[test]
+fn test_default() {
+    insta::assert_snapshot!(format(STARTINGCODE, DispatchTableStyle::Default), @r###"
+        local handlers = {
+        	[Action.Open] = function(state)
+        		state.open = true
+        		return state
+        	end,
+        	[Action.Close] = function(state) state.open = false end,
+        	[Action.Toggle] = function(state) state.open = not state.open end,
+        }
+        local small = { [A] = function() end, [B] = function() return 1 end }
+        local mixed = { [A] = function() return 1 end, 2 }
+    "###);
+}
+
+#[test]
+fn test_switch() {
+    insta::assert_snapshot!(format(STARTINGCODE, DispatchTableStyle::Switch), @r###"
+        local handlers = {
+        	[Action.Open] = function(state)
+        		state.open = true
+        		return state
+        	end,
+        	[Action.Close] = function(state)
+        		state.open = false
+        	end,
+        	[Action.Toggle] = function(state)
+        		state.open = not state.open
+        	end,
+        }
+        local small = {
+        	[A] = function() end,
+        	[B] = function()
+        		return 1
+        	end,
+        }
+        local mixed = { [A] = function() return 1 end, 2 }
+    "###);
+}
+
+#[test]
+fn test_switch_does_not_expand_nested_functions() {
+    insta::assert_snapshot!(
+        format(
+            r###"
+local handlers = { [A] = function() local f = function() return 1 end return f end, [B] = function() return { 1, function() return 2 end } end }
+"###,
+            DispatchTableStyle::Switch
+        ),
+        @r###"
+        local handlers = {
+        	[A] = function()
+        		local f = function() return 1 end
+        		return f
+        	end,
+        	[B] = function()
+        		return { 1, function() return 2 end }
+        	end,
+        }
+    "###
+    );
+}
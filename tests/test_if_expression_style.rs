@@ -0,0 +1,83 @@
+#[cfg(feature = "luau")]
+use stylua_lib::{format_code, Config, IfExpressionStyle, OutputVerification};
+
+#[cfg(feature = "luau")]
+fn format(input: &str, if_expression_style: IfExpressionStyle) -> String {
+    format_code(
+        input,
+        Config {
+            if_expression_style,
+            column_width: 80,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+#[cfg(feature = "luau")]
+const STARTINGCODE: &str = r###"
+local short = if x then 1 else 2
+local value = if someCondition then someLongValueName elseif otherCondition then otherLongValueName elseif yetAnotherCondition then yetAnotherValue else fallbackValueName
+local nested = if first then (if second then secondValueThatIsLong else thirdValueThatIsLong) elseif fourth then fourthValue else fifthValue
+local deep = if a then if b then someVeryLongValueNameNumberOne elseif c then someVeryLongValueNameNumberTwo else someVeryLongValueNameNumberThree else d
+"###;
+
+#[test]
+#[cfg(feature = "luau")]
+fn test_if_expression_style_hanging() {
+    insta::assert_snapshot!(format(STARTINGCODE, IfExpressionStyle::Hanging), @r###"
+        local short = if x then 1 else 2
+        local value = if someCondition
+        	then someLongValueName
+        	elseif otherCondition then otherLongValueName
+        	elseif yetAnotherCondition then yetAnotherValue
+        	else fallbackValueName
+        local nested = if first
+        	then (if second then secondValueThatIsLong else thirdValueThatIsLong)
+        	elseif fourth then fourthValue
+        	else fifthValue
+        local deep = if a
+        	then if b
+        		then someVeryLongValueNameNumberOne
+        		elseif c then someVeryLongValueNameNumberTwo
+        		else someVeryLongValueNameNumberThree
+        	else d
+    "###);
+}
+
+#[test]
+#[cfg(feature = "luau")]
+fn test_if_expression_style_break_after_keywords() {
+    insta::assert_snapshot!(format(STARTINGCODE, IfExpressionStyle::BreakAfterKeywords), @r###"
+        local short = if x then 1 else 2
+        local value = if someCondition
+        	then
+        		someLongValueName
+        	elseif otherCondition then
+        		otherLongValueName
+        	elseif yetAnotherCondition then
+        		yetAnotherValue
+        	else
+        		fallbackValueName
+        local nested = if first
+        	then
+        		(if second then secondValueThatIsLong else thirdValueThatIsLong)
+        	elseif fourth then
+        		fourthValue
+        	else
+        		fifthValue
+        local deep = if a
+        	then
+        		if b
+        			then
+        				someVeryLongValueNameNumberOne
+        			elseif c then
+        				someVeryLongValueNameNumberTwo
+        			else
+        				someVeryLongValueNameNumberThree
+        	else
+        		d
+    "###);
+}
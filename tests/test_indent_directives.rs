@@ -0,0 +1,79 @@
+use stylua_lib::{format_code, Config, OutputVerification};
+
+fn format(input: &str) -> String {
+    format_code(input, Config::default(), None, OutputVerification::None).unwrap()
+}
+
+#[test]
+fn test_indent_region_one_level() {
+    insta::assert_snapshot!(format(r###"
+local x = 1
+-- stylua: indent +1
+local function foo()
+return bar
+end
+local y = 2
+-- stylua: indent reset
+local z = 3
+"###), @r###"
+        local x = 1
+        	-- stylua: indent +1
+        	local function foo()
+        		return bar
+        	end
+        	local y = 2
+        -- stylua: indent reset
+        local z = 3
+    "###);
+}
+
+#[test]
+fn test_indent_directive_is_cumulative() {
+    insta::assert_snapshot!(format(r###"
+local x = 1
+-- stylua: indent +1
+local y = 2
+-- stylua: indent +2
+local z = 3
+"###), @r###"
+        local x = 1
+        	-- stylua: indent +1
+        	local y = 2
+        			-- stylua: indent +2
+        			local z = 3
+    "###);
+}
+
+#[test]
+fn test_indent_directive_is_scoped_to_block() {
+    insta::assert_snapshot!(format(r###"
+do
+-- stylua: indent +1
+local x = 1
+end
+local y = 2
+"###), @r###"
+        do
+        		-- stylua: indent +1
+        		local x = 1
+        end
+        local y = 2
+    "###);
+}
+
+#[test]
+fn test_indent_directive_applies_to_last_stmt() {
+    insta::assert_snapshot!(format(r###"
+local function foo()
+local x = 1
+-- stylua: indent +1
+return x
+end
+"###), @r###"
+        local function foo()
+        	local x = 1
+        		-- stylua: indent +1
+        		return x
+        end
+    "###);
+}
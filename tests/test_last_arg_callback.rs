@@ -0,0 +1,65 @@
+use stylua_lib::{format_code, Config, LastArgCallback, OutputVerification};
+
+fn format(input: &str, last_arg_callback: LastArgCallback) -> String {
+    format_code(
+        input,
+        Config {
+            last_arg_callback,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+foo(function() print("hello") return true end)
+describe("some test", function() setup() run() end)
+local value = call(function() end)
+"###;
+
+#[test]
+fn test_hug() {
+    insta::assert_snapshot!(format(STARTINGCODE, LastArgCallback::Hug), @r###"
+        foo(function()
+        	print("hello")
+        	return true
+        end)
+        describe("some test", function()
+        	setup()
+        	run()
+        end)
+        local value = call(function() end)
+    "###);
+}
+
+#[test]
+fn test_wrap() {
+    insta::assert_snapshot!(format(STARTINGCODE, LastArgCallback::Wrap), @r###"
+        foo(
+        	function()
+        		print("hello")
+        		return true
+        	end
+        )
+        describe(
+        	"some test",
+        	function()
+        		setup()
+        		run()
+        	end
+        )
+        local value = call(function() end)
+    "###);
+}
+
+#[test]
+fn test_wrap_keeps_callback_which_is_not_last() {
+    insta::assert_snapshot!(format("foo(function() a() b() end, bar)", LastArgCallback::Wrap), @r###"
+        foo(function()
+        	a()
+        	b()
+        end, bar)
+    "###);
+}
@@ -0,0 +1,110 @@
+use stylua_lib::{format_code, Config, LeadingCommentNewlineGap, OutputVerification};
+
+fn format(input: &str, leading_comment_newline_gap: LeadingCommentNewlineGap) -> String {
+    format_code(
+        input,
+        Config {
+            leading_comment_newline_gap,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local function documented()
+    -- Returns the answer
+
+    return 42
+end
+
+local function undocumented()
+    -- Computes the value
+    local value = 1
+    return value
+end
+
+local function spaced()
+
+
+    -- First line
+    -- Second line
+
+
+
+    print("hello")
+end
+"###;
+
+#[test]
+fn test_preserve() {
+    insta::assert_snapshot!(format(STARTINGCODE, LeadingCommentNewlineGap::Preserve), @r###"
+        local function documented()
+        	-- Returns the answer
+
+        	return 42
+        end
+
+        local function undocumented()
+        	-- Computes the value
+        	local value = 1
+        	return value
+        end
+
+        local function spaced()
+        	-- First line
+        	-- Second line
+
+        	print("hello")
+        end
+    "###);
+}
+
+#[test]
+fn test_never() {
+    insta::assert_snapshot!(format(STARTINGCODE, LeadingCommentNewlineGap::Never), @r###"
+        local function documented()
+        	-- Returns the answer
+        	return 42
+        end
+
+        local function undocumented()
+        	-- Computes the value
+        	local value = 1
+        	return value
+        end
+
+        local function spaced()
+        	-- First line
+        	-- Second line
+        	print("hello")
+        end
+    "###);
+}
+
+#[test]
+fn test_always() {
+    insta::assert_snapshot!(format(STARTINGCODE, LeadingCommentNewlineGap::Always), @r###"
+        local function documented()
+        	-- Returns the answer
+
+        	return 42
+        end
+
+        local function undocumented()
+        	-- Computes the value
+
+        	local value = 1
+        	return value
+        end
+
+        local function spaced()
+        	-- First line
+        	-- Second line
+
+        	print("hello")
+        end
+    "###);
+}
@@ -0,0 +1,72 @@
+use stylua_lib::{format_code, Config, NestedTableExpansion, OutputVerification};
+
+fn format(input: &str, nested_table_expansion: NestedTableExpansion) -> String {
+    format_code(
+        input,
+        Config {
+            nested_table_expansion,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local outer = {
+    point = { 1, 2 },
+    { name = "value" },
+    empty = {},
+    call = f({ 1 }),
+}
+local short = { { 1, 2 }, { 3, 4 } }
+"###;
+
+#[test]
+fn test_independent() {
+    insta::assert_snapshot!(format(STARTINGCODE, NestedTableExpansion::Independent), @r###"
+        local outer = {
+        	point = { 1, 2 },
+        	{ name = "value" },
+        	empty = {},
+        	call = f({ 1 }),
+        }
+        local short = { { 1, 2 }, { 3, 4 } }
+    "###);
+}
+
+#[test]
+fn test_propagate() {
+    insta::assert_snapshot!(format(STARTINGCODE, NestedTableExpansion::Propagate), @r###"
+        local outer = {
+        	point = {
+        		1,
+        		2,
+        	},
+        	{
+        		name = "value",
+        	},
+        	empty = {},
+        	call = f({ 1 }),
+        }
+        local short = { { 1, 2 }, { 3, 4 } }
+    "###);
+}
+
+#[test]
+fn test_propagate_through_multiple_levels() {
+    insta::assert_snapshot!(format(r###"
+local outer = {
+    nested = { inner = { 1 } },
+}
+"###, NestedTableExpansion::Propagate), @r###"
+        local outer = {
+        	nested = {
+        		inner = {
+        			1,
+        		},
+        	},
+        }
+    "###);
+}
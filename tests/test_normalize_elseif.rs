@@ -0,0 +1,213 @@
+use stylua_lib::{format_code, Config, OutputVerification};
+
+fn format(input: &str) -> String {
+    format_code(
+        input,
+        Config {
+            normalize_elseif: true,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_merge_nested_if() {
+    insta::assert_snapshot!(format(r###"
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+end
+"###), @r###"
+        if a then
+        	foo()
+        elseif b then
+        	bar()
+        end
+    "###);
+}
+
+#[test]
+fn test_merge_repeatedly() {
+    insta::assert_snapshot!(format(r###"
+if a then
+    foo()
+elseif b then
+    bar()
+else
+    if c then
+        baz()
+    elseif d then
+        qux()
+    else
+        if e then
+            quux()
+        else
+            corge()
+        end
+    end
+end
+"###), @r###"
+        if a then
+        	foo()
+        elseif b then
+        	bar()
+        elseif c then
+        	baz()
+        elseif d then
+        	qux()
+        elseif e then
+        	quux()
+        else
+        	corge()
+        end
+    "###);
+}
+
+#[test]
+fn test_keep_else_with_other_statements() {
+    insta::assert_snapshot!(format(r###"
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+    baz()
+end
+
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+    return
+end
+"###), @r###"
+        if a then
+        	foo()
+        else
+        	if b then
+        		bar()
+        	end
+        	baz()
+        end
+
+        if a then
+        	foo()
+        else
+        	if b then
+        		bar()
+        	end
+        	return
+        end
+    "###);
+}
+
+#[test]
+fn test_keep_else_with_comments() {
+    insta::assert_snapshot!(format(r###"
+if a then
+    foo()
+else -- comment
+    if b then
+        bar()
+    end
+end
+
+if a then
+    foo()
+else
+    -- comment
+    if b then
+        bar()
+    end
+end
+
+if a then
+    foo()
+else
+    if b then
+        bar()
+        -- comment
+    end
+end
+"###), @r###"
+        if a then
+        	foo()
+        else -- comment
+        	if b then
+        		bar()
+        	end
+        end
+
+        if a then
+        	foo()
+        else
+        	-- comment
+        	if b then
+        		bar()
+        	end
+        end
+
+        if a then
+        	foo()
+        else
+        	if b then
+        		bar()
+        		-- comment
+        	end
+        end
+    "###);
+}
+
+#[test]
+fn test_keep_else_when_ignored() {
+    let input = r###"-- stylua: ignore
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+end
+
+-- stylua: ignore start
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+end
+-- stylua: ignore end
+"###;
+    assert_eq!(format(input), input);
+}
+
+#[test]
+fn test_disabled_by_default() {
+    insta::assert_snapshot!(format_code(r###"
+if a then
+    foo()
+else
+    if b then
+        bar()
+    end
+end
+"###, Config::default(), None, OutputVerification::Full).unwrap(), @r###"
+        if a then
+        	foo()
+        else
+        	if b then
+        		bar()
+        	end
+        end
+    "###);
+}
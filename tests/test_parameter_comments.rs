@@ -0,0 +1,93 @@
+use stylua_lib::{format_code, Config, OutputVerification, ParameterComments};
+
+fn format(input: &str, parameter_comments: ParameterComments) -> String {
+    format_code(
+        input,
+        Config {
+            parameter_comments,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+function between(a, --[[reserved]] b) end
+function before(--[[first]] a, b) end
+function after(a, b --[[last]]) end
+function around(a --[[x]], --[[y]] b, ... --[[rest]]) end
+local anonymous = function(a,   --[[spaced]]   b) return a end
+function empty(--[[none]]) end
+function line(a, -- line comment
+	b) end
+function multiline(a, --[[ spans
+multiple lines ]] b) end
+"###;
+
+#[test]
+fn test_wrap() {
+    insta::assert_snapshot!(format(STARTINGCODE, ParameterComments::Wrap), @r###"
+    function between(
+    	a, --[[reserved]]
+    	b
+    )
+    end
+    function before(--[[first]]a, b) end
+    function after(
+    	a,
+    	b --[[last]]
+    )
+    end
+    function around(
+    	a --[[x]], --[[y]]
+    	b,
+    	... --[[rest]]
+    )
+    end
+    local anonymous = function(
+    	a, --[[spaced]]
+    	b
+    )
+    	return a
+    end
+    function empty(--[[none]]) end
+    function line(
+    	a, -- line comment
+    	b
+    )
+    end
+    function multiline(
+    	a, --[[ spans
+    multiple lines ]]
+    	b
+    )
+    end
+    "###);
+}
+
+#[test]
+fn test_inline() {
+    insta::assert_snapshot!(format(STARTINGCODE, ParameterComments::Inline), @r###"
+    function between(a, --[[reserved]] b) end
+    function before(--[[first]] a, b) end
+    function after(a, b --[[last]]) end
+    function around(a --[[x]], --[[y]] b, ... --[[rest]]) end
+    local anonymous = function(a, --[[spaced]] b)
+    	return a
+    end
+    function empty(--[[none]]) end
+    function line(
+    	a, -- line comment
+    	b
+    )
+    end
+    function multiline(
+    	a, --[[ spans
+    multiple lines ]]
+    	b
+    )
+    end
+    "###);
+}
@@ -0,0 +1,74 @@
+use stylua_lib::{format_code, Config, OutputVerification, ReturnParentheses};
+
+fn format(input: &str, return_parentheses: ReturnParentheses) -> String {
+    format_code(
+        input,
+        Config {
+            return_parentheses,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local function truncate()
+    return (f())
+end
+
+local function varargs(...)
+    return (...)
+end
+
+local function multiple()
+    return (a), (b and c), ((d))
+end
+
+local function nested()
+    return ((f()))
+end
+"###;
+
+#[test]
+fn test_remove_redundant_return_parentheses() {
+    insta::assert_snapshot!(format(STARTINGCODE, ReturnParentheses::RemoveRedundant), @r###"
+        local function truncate()
+        	return (f())
+        end
+
+        local function varargs(...)
+        	return (...)
+        end
+
+        local function multiple()
+        	return a, (b and c), d
+        end
+
+        local function nested()
+        	return (f())
+        end
+    "###);
+}
+
+#[test]
+fn test_input_return_parentheses() {
+    insta::assert_snapshot!(format(STARTINGCODE, ReturnParentheses::Input), @r###"
+        local function truncate()
+        	return (f())
+        end
+
+        local function varargs(...)
+        	return (...)
+        end
+
+        local function multiple()
+        	return (a), (b and c), (d)
+        end
+
+        local function nested()
+        	return (f())
+        end
+    "###);
+}
@@ -0,0 +1,78 @@
+#[cfg(feature = "luau")]
+use stylua_lib::{format_code, Config, LuaVersion, OutputVerification, ReturnTypeStyle};
+
+#[cfg(feature = "luau")]
+fn format(input: &str, return_type_style: ReturnTypeStyle) -> String {
+    format_code(
+        input,
+        Config {
+            syntax: LuaVersion::Luau,
+            return_type_style,
+            column_width: 80,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+#[cfg(feature = "luau")]
+const STARTINGCODE: &str = r###"
+local function short(a: number): number
+	return a
+end
+
+local function parse(input: string, position: number): (number, string, { [string]: any }) -> boolean
+	return nil
+end
+
+function Module.someMethodWithLongName(): (first: number, second: string, third: boolean) -> ()
+	return nil
+end
+"###;
+
+#[test]
+#[cfg(feature = "luau")]
+fn test_return_type_style_same_line() {
+    insta::assert_snapshot!(format(STARTINGCODE, ReturnTypeStyle::SameLine), @r###"
+        local function short(a: number): number
+        	return a
+        end
+
+        local function parse(
+        	input: string,
+        	position: number
+        ): (number, string, { [string]: any }) -> boolean
+        	return nil
+        end
+
+        function Module.someMethodWithLongName(): (
+        	first: number,
+        	second: string,
+        	third: boolean
+        ) -> ()
+        	return nil
+        end
+    "###);
+}
+
+#[test]
+#[cfg(feature = "luau")]
+fn test_return_type_style_break_when_long() {
+    insta::assert_snapshot!(format(STARTINGCODE, ReturnTypeStyle::BreakWhenLong), @r###"
+        local function short(a: number): number
+        	return a
+        end
+
+        local function parse(input: string, position: number)
+        	: (number, string, { [string]: any }) -> boolean
+        	return nil
+        end
+
+        function Module.someMethodWithLongName()
+        	: (first: number, second: string, third: boolean) -> ()
+        	return nil
+        end
+    "###);
+}
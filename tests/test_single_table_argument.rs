@@ -0,0 +1,89 @@
+use stylua_lib::{format_code, Config, OutputVerification, SingleTableArgument};
+
+fn format(input: &str, single_table_argument: SingleTableArgument) -> String {
+    format_code(
+        input,
+        Config {
+            single_table_argument,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+createButton({ name = "submitButton", text = "Submit the form", position = UDim2.new(0, 10, 0, 20), size = buttonSize, visible = true })
+createLabel({
+    name = "title",
+    text = "Hello",
+})
+local short = f({ name = x, value = y })
+"###;
+
+#[test]
+fn test_hug() {
+    insta::assert_snapshot!(format(STARTINGCODE, SingleTableArgument::Hug), @r###"
+        createButton({
+        	name = "submitButton",
+        	text = "Submit the form",
+        	position = UDim2.new(0, 10, 0, 20),
+        	size = buttonSize,
+        	visible = true,
+        })
+        createLabel({
+        	name = "title",
+        	text = "Hello",
+        })
+        local short = f({ name = x, value = y })
+    "###);
+}
+
+#[test]
+fn test_wrap() {
+    insta::assert_snapshot!(format(STARTINGCODE, SingleTableArgument::Wrap), @r###"
+        createButton(
+        	{
+        		name = "submitButton",
+        		text = "Submit the form",
+        		position = UDim2.new(0, 10, 0, 20),
+        		size = buttonSize,
+        		visible = true,
+        	}
+        )
+        createLabel(
+        	{
+        		name = "title",
+        		text = "Hello",
+        	}
+        )
+        local short = f({ name = x, value = y })
+    "###);
+}
+
+#[test]
+fn test_wrap_table_call_without_parentheses() {
+    let output = format("f {\n\ta = 1,\n}\n", SingleTableArgument::Wrap);
+    insta::assert_snapshot!(output, @r###"
+    f(
+    	{
+    		a = 1,
+    	}
+    )
+    "###);
+    assert_eq!(format(&output, SingleTableArgument::Wrap), output);
+}
+
+#[test]
+fn test_wrap_parenthesised_table() {
+    let output = format("f(({\n\ta = 1,\n}))\n", SingleTableArgument::Wrap);
+    insta::assert_snapshot!(output, @r###"
+    f(
+    	{
+    		a = 1,
+    	}
+    )
+    "###);
+    assert_eq!(format(&output, SingleTableArgument::Wrap), output);
+}
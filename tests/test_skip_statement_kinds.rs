@@ -0,0 +1,176 @@
+use stylua_lib::{format_code, Config, OutputVerification, StatementKind};
+
+fn format(input: &str, skip_statement_kinds: &[StatementKind]) -> String {
+    format_code(
+        input,
+        Config {
+            skip_statement_kinds: skip_statement_kinds.iter().copied().collect(),
+            ..Config::default()
+        },
+        None,
+        OutputVerification::None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_skip_return_statements() {
+    insta::assert_snapshot!(
+        format(r###"
+local   x   =   1
+local function foo()
+        local y   =   {1,2,3}
+        return   x,y
+end
+"###,
+            &[StatementKind::Return]
+        ),
+        @r###"
+    local x = 1
+    local function foo()
+    	local y = { 1, 2, 3 }
+    	return   x,y
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skip_multiline_statement() {
+    insta::assert_snapshot!(
+        format(r###"
+if true then
+        x   =   {
+            1,   2,
+            3
+        }
+        call(  x  )
+end
+"###,
+            &[StatementKind::Assignment]
+        ),
+        @r###"
+    if true then
+    	x   =   {
+                1,   2,
+                3
+            }
+    	call(x)
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_prevents_collapse() {
+    insta::assert_snapshot!(
+        format(r###"
+local function foo() return   1 end
+"###,
+            &[StatementKind::Return]
+        ),
+        @r###"
+    local function foo()
+    	return   1
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_in_single_line_do_block() {
+    insta::assert_snapshot!(
+        format(r###"
+do local x=1 end
+"###,
+            &[StatementKind::LocalAssignment]
+        ),
+        @r###"
+    do
+    	local x=1
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_in_single_line_if_block() {
+    insta::assert_snapshot!(
+        format(r###"
+if a then return   1 end
+"###,
+            &[StatementKind::Return]
+        ),
+        @r###"
+    if a then
+    	return   1
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_in_single_line_function() {
+    insta::assert_snapshot!(
+        format(r###"
+function foo() call( x )   end
+"###,
+            &[StatementKind::FunctionCall]
+        ),
+        @r###"
+    function foo()
+    	call( x )
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_keeps_trailing_comments() {
+    insta::assert_snapshot!(
+        format(r###"
+local x  =  {
+    1,  2 }   -- numbers
+local y=2 --[[ why ]]   -- not formatted
+"###,
+            &[StatementKind::LocalAssignment]
+        ),
+        @r###"
+    local x  =  {
+        1,  2 } -- numbers
+    local y=2 --[[ why ]] -- not formatted
+    "###
+    );
+}
+
+#[test]
+fn test_skipped_statement_reindents_leading_comments() {
+    insta::assert_snapshot!(
+        format("local function foo()\n\tlocal x = 1\n\n\n\n        -- comment here\n  --[[ another ]]\n\treturn   x   ,  2\nend\n",
+            &[StatementKind::Return]
+        ),
+        @r###"
+    local function foo()
+    	local x = 1
+
+    	-- comment here
+    	--[[ another ]]
+    	return   x   ,  2
+    end
+    "###
+    );
+}
+
+#[test]
+fn test_skip_statement_kinds_from_toml() {
+    let config: Config = toml::from_str(r#"skip_statement_kinds = ["Return", "FunctionCall"]"#)
+        .expect("config should deserialize");
+
+    assert!(config.skip_statement_kinds.contains(StatementKind::Return));
+    assert!(config
+        .skip_statement_kinds
+        .contains(StatementKind::FunctionCall));
+    assert!(!config
+        .skip_statement_kinds
+        .contains(StatementKind::Assignment));
+}
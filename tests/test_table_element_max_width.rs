@@ -0,0 +1,87 @@
+use stylua_lib::{format_code, Config, OutputVerification};
+
+fn format(input: &str, table_element_max_width: usize) -> String {
+    format_code(
+        input,
+        Config {
+            table_element_max_width,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local names = { "alice", "bob", key = "value" }
+local nested = { { 1, 2 }, 3 }
+local empty = {}
+"###;
+
+#[test]
+fn test_disabled() {
+    insta::assert_snapshot!(format(STARTINGCODE, 0), @r###"
+        local names = { "alice", "bob", key = "value" }
+        local nested = { { 1, 2 }, 3 }
+        local empty = {}
+    "###);
+}
+
+#[test]
+fn test_element_at_max_width() {
+    // `key = "value"` is exactly 13 columns wide
+    insta::assert_snapshot!(format(r###"
+local names = { "alice", "bob", key = "value" }
+"###, 13), @r###"
+        local names = { "alice", "bob", key = "value" }
+    "###);
+}
+
+#[test]
+fn test_nested_table_at_max_width() {
+    // `{ 1, 2 }` is exactly 8 columns wide
+    insta::assert_snapshot!(format(r###"
+local nested = { { 1, 2 }, 3 }
+"###, 8), @r###"
+        local nested = { { 1, 2 }, 3 }
+    "###);
+}
+
+#[test]
+fn test_element_over_max_width() {
+    insta::assert_snapshot!(format(r###"
+local names = { "alice", "bob", key = "value" }
+"###, 12), @r###"
+        local names = {
+        	"alice",
+        	"bob",
+        	key = "value",
+        }
+    "###);
+}
+
+#[test]
+fn test_nested_table_over_max_width() {
+    insta::assert_snapshot!(format(r###"
+local nested = { { 1, 2 }, 3 }
+"###, 7), @r###"
+        local nested = {
+        	{ 1, 2 },
+        	3,
+        }
+    "###);
+}
+
+#[test]
+fn test_comments_always_expand() {
+    insta::assert_snapshot!(format(r###"
+local names = { "alice", "bob" -- comment
+}
+"###, 120), @r###"
+        local names = {
+        	"alice",
+        	"bob", -- comment
+        }
+    "###);
+}
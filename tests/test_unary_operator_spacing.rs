@@ -0,0 +1,62 @@
+use stylua_lib::{format_code, Config, OutputVerification, UnaryOperatorSpacing};
+
+fn format(input: &str, unary_operator_spacing: UnaryOperatorSpacing) -> String {
+    format_code(
+        input,
+        Config {
+            unary_operator_spacing,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local a = - x
+local b = -x
+local c = -   x
+local d = not x
+local e = not   x
+local f = not(x)
+local g = #t
+local h = #  t
+local i = - -x
+local j = -(x) + 1
+local k = a - -b
+"###;
+
+#[test]
+fn test_tight() {
+    insta::assert_snapshot!(format(STARTINGCODE, UnaryOperatorSpacing::Tight), @r###"
+        local a = -x
+        local b = -x
+        local c = -x
+        local d = not x
+        local e = not x
+        local f = not x
+        local g = #t
+        local h = #t
+        local i = -(-x)
+        local j = -x + 1
+        local k = a - -b
+    "###);
+}
+
+#[test]
+fn test_spaced() {
+    insta::assert_snapshot!(format(STARTINGCODE, UnaryOperatorSpacing::Spaced), @r###"
+        local a = - x
+        local b = - x
+        local c = - x
+        local d = not x
+        local e = not x
+        local f = not x
+        local g = # t
+        local h = # t
+        local i = - (- x)
+        local j = - x + 1
+        local k = a - - b
+    "###);
+}
@@ -0,0 +1,71 @@
+use stylua_lib::{format_code, Config, OutputVerification};
+
+fn format(input: &str, wrap_index_chains: bool) -> String {
+    format_code(
+        input,
+        Config {
+            column_width: 60,
+            wrap_index_chains,
+            ..Config::default()
+        },
+        None,
+        OutputVerification::Full,
+    )
+    .unwrap()
+}
+
+const STARTINGCODE: &str = r###"
+local value = game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart
+game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart = value
+print(game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart)
+local short = game.workspace.folder
+"###;
+
+#[test]
+fn test_disabled() {
+    insta::assert_snapshot!(format(STARTINGCODE, false), @r###"
+        local value =
+        	game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart
+        game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart =
+        	value
+        print(
+        	game.workspace.someVeryLongFolderName.anotherVeryLongFolderName.finalPart
+        )
+        local short = game.workspace.folder
+    "###);
+}
+
+#[test]
+fn test_enabled() {
+    insta::assert_snapshot!(format(STARTINGCODE, true), @r###"
+        local value = game
+        	.workspace
+        	.someVeryLongFolderName
+        	.anotherVeryLongFolderName
+        	.finalPart
+        game
+        	.workspace
+        	.someVeryLongFolderName
+        	.anotherVeryLongFolderName
+        	.finalPart = value
+        print(
+        	game
+        		.workspace
+        		.someVeryLongFolderName
+        		.anotherVeryLongFolderName
+        		.finalPart
+        )
+        local short = game.workspace.folder
+    "###);
+}
+
+#[test]
+fn test_chain_containing_calls_is_not_wrapped() {
+    insta::assert_snapshot!(format(r###"
+local value = game.workspace:FindFirstChild("someVeryLongFolderName").anotherVeryLongFolderName.finalPart
+"###, true), @r###"
+        local value = game.workspace:FindFirstChild(
+        	"someVeryLongFolderName"
+        ).anotherVeryLongFolderName.finalPart
+    "###);
+}
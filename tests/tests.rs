@@ -19,6 +19,20 @@ fn test_standard() {
     })
 }
 
+#[test]
+fn test_standard_is_idempotent() {
+    insta::glob!("inputs/*.lua", |path| {
+        let contents = std::fs::read_to_string(path).unwrap();
+        let formatted = format(&contents, LuaVersion::Lua51);
+        assert_eq!(
+            format(&formatted, LuaVersion::Lua51),
+            formatted,
+            "formatting {} a second time changed the output",
+            path.display()
+        );
+    })
+}
+
 #[test]
 fn test_full_moon_test_suite() {
     insta::glob!("inputs-full_moon/*.lua", |path| {
@@ -116,6 +130,59 @@ fn test_collapse_single_statement() {
     })
 }
 
+#[test]
+fn test_collapse_getters_setters_only() {
+    insta::assert_snapshot!(
+        format_code(
+            r###"
+            function Class:getName()
+                return self.name
+            end
+
+            function Class:setName(name)
+                self.name = name
+            end
+
+            local function notify()
+                callback()
+            end
+
+            local function finish()
+                return
+            end
+
+            if ready then
+                return
+            end
+            "###,
+            Config {
+                collapse_simple_statement: CollapseSimpleStatement::GettersSettersOnly,
+                ..Config::default()
+            },
+            None,
+            OutputVerification::None
+        )
+        .unwrap(),
+        @r###"
+    function Class:getName() return self.name end
+
+    function Class:setName(name) self.name = name end
+
+    local function notify()
+    	callback()
+    end
+
+    local function finish()
+    	return
+    end
+
+    if ready then
+    	return
+    end
+    "###
+    );
+}
+
 #[test]
 fn test_preserve_block_newline_gaps() {
     insta::glob!("inputs-preserve-block-newline-gaps/*.lua", |path| {
@@ -212,3 +279,45 @@ local x = 1
     let output = format(&code_crlf, LuaVersion::Lua51);
     assert_eq!(output.find("\r\n"), None);
 }
+
+#[test]
+fn test_multiline_string_as_first_statement() {
+    // A bare multiline string is not a valid statement, so it cannot be formatted
+    assert!(format_code(
+        "local function foo()\n\t[[\n  docs\n]]\n\treturn 1\nend\n",
+        Config::default(),
+        None,
+        OutputVerification::None
+    )
+    .is_err());
+
+    // When a multiline string (or comment) is used as a docstring, only its start is indented.
+    // Its contents are always kept as written
+    insta::assert_snapshot!(
+        format(
+            r###"
+local function foo()
+      --[[
+  comment docs
+      ]]
+      local _ = [[
+  string docs
+      ]]
+  return 1
+end
+"###,
+            LuaVersion::Lua51
+        ),
+        @r###"
+        local function foo()
+        	--[[
+          comment docs
+              ]]
+        	local _ = [[
+          string docs
+              ]]
+        	return 1
+        end
+    "###
+    );
+}